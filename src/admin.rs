@@ -0,0 +1,138 @@
+use crate::location::{home_dir_of, user_name};
+use crate::platform::MetadataExt;
+use crate::query::size_or_zero;
+use crate::{list_trash_in, DeletionDate, TrashError, TrashLocation, TrashedItem};
+use std::path::{Path, PathBuf};
+
+/// Where the home trash lives relative to a home directory, as long
+/// as the user has not moved `$XDG_DATA_HOME`
+const HOME_TRASH: &str = ".local/share/Trash";
+
+/// What is in one trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrashSummary {
+    /// Number of items
+    pub count: usize,
+    /// Combined size of the items in bytes
+    pub size: u64,
+    /// When the least recently deleted item was deleted
+    pub oldest: Option<DeletionDate>,
+    /// When the most recently deleted item was deleted
+    pub newest: Option<DeletionDate>,
+}
+
+impl TrashSummary {
+    /// Sums up some items, in any order
+    pub(crate) fn of(items: &[TrashedItem]) -> Result<Self, TrashError> {
+        let mut size = 0;
+        for item in items {
+            size += size_or_zero(item)?;
+        }
+        Ok(TrashSummary {
+            count: items.len(),
+            size,
+            oldest: items.iter().map(|item| item.deleted_at()).min(),
+            newest: items.iter().map(|item| item.deleted_at()).max(),
+        })
+    }
+}
+
+/// The home trash of one user, found by `scan_user_trashes`
+#[derive(Debug)]
+pub struct UserTrash {
+    /// Owner of the home directory
+    pub uid: u32,
+    /// Their user name, if the password database knows it
+    pub user: Option<String>,
+    /// Their home trash
+    pub trash_dir: PathBuf,
+    /// What is in it, or why it could not be read
+    pub summary: Result<TrashSummary, TrashError>,
+}
+
+/// Reports on the home trash of every user with a home directory in
+/// `/home`, and of root, for cleaning up shared machines
+/// Reading other users' trashes takes root; a trash which cannot be
+/// read is reported with the error rather than failing the scan
+pub fn scan_user_trashes() -> Result<Vec<UserTrash>, TrashError> {
+    let mut homes = home_dirs(Path::new("/home"))?;
+    if let Some(root_home) = home_dir_of(0) {
+        if !homes.contains(&root_home) {
+            homes.push(root_home);
+        }
+    }
+    Ok(scan_homes(homes))
+}
+
+/// Reports on the home trash of every home directory in `homes_root`,
+/// such as `/srv/homes`
+pub fn scan_user_trashes_in<P: AsRef<Path>>(homes_root: P) -> Result<Vec<UserTrash>, TrashError> {
+    Ok(scan_homes(home_dirs(homes_root.as_ref())?))
+}
+
+/// The directories in `homes_root`, in order of name
+fn home_dirs(homes_root: &Path) -> Result<Vec<PathBuf>, TrashError> {
+    let mut homes = Vec::new();
+    for entry in std::fs::read_dir(homes_root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            homes.push(entry.path());
+        }
+    }
+    homes.sort();
+    Ok(homes)
+}
+
+/// Reports on the homes which have a trash
+fn scan_homes(homes: Vec<PathBuf>) -> Vec<UserTrash> {
+    homes
+        .into_iter()
+        .filter_map(|home| {
+            let trash_dir = home.join(HOME_TRASH);
+            let uid = home.metadata().ok()?.uid();
+            if !trash_dir.is_dir() {
+                return None;
+            }
+            Some(UserTrash {
+                uid,
+                user: user_name(uid),
+                summary: summarize(&trash_dir),
+                trash_dir,
+            })
+        })
+        .collect()
+}
+
+fn summarize(trash_dir: &Path) -> Result<TrashSummary, TrashError> {
+    TrashSummary::of(&list_trash_in(&TrashLocation::Custom(
+        trash_dir.to_path_buf(),
+    ))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{move_to_trash_in, scan_user_trashes_in, TrashLocation};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_user_trashes() {
+        let homes = tempdir().expect("temp dir creation failed");
+        let alice_trash = homes.path().join("alice/.local/share/Trash");
+        std::fs::create_dir_all(&alice_trash).unwrap();
+        // No trash, so not reported
+        std::fs::create_dir(homes.path().join("bob")).unwrap();
+        for name in &["a", "b"] {
+            let path = homes.path().join("alice").join(name);
+            std::fs::write(&path, b"123").unwrap();
+            move_to_trash_in(&path, &TrashLocation::Custom(alice_trash.clone())).unwrap();
+        }
+
+        let report = scan_user_trashes_in(homes.path()).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].trash_dir, alice_trash);
+        assert_eq!(report[0].user, crate::location::user_name(report[0].uid));
+        let summary = report[0].summary.as_ref().unwrap();
+        assert_eq!((summary.count, summary.size), (2, 6));
+        assert!(summary.oldest <= summary.newest);
+    }
+}
@@ -0,0 +1,250 @@
+//! The operations an app performs on a trash, behind a trait, so that
+//! code deleting files can be tested against `MemoryTrash` without
+//! touching the real trash or any files at all
+
+use crate::clock::{Clock, SystemClock};
+use crate::{CollisionStrategy, Trash, TrashError, TrashInfo};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// What an app does with a trash: trash files, list, restore and purge
+/// items
+/// `Trash` does it for real, `MemoryTrash` only pretends to
+///
+/// ```
+/// use trash::{MemoryTrash, TrashBackend};
+///
+/// fn delete_drafts(trash: &dyn TrashBackend, drafts: &[&str]) -> Result<(), trash::TrashError> {
+///     for draft in drafts {
+///         trash.trash(draft.as_ref())?;
+///     }
+///     Ok(())
+/// }
+///
+/// let trash = MemoryTrash::new();
+/// trash.add_file("/home/me/draft.txt", "text");
+/// delete_drafts(&trash, &["/home/me/draft.txt"])?;
+/// assert!(!trash.exists("/home/me/draft.txt".as_ref()));
+/// assert_eq!(trash.list()?.len(), 1);
+/// # Ok::<(), trash::TrashError>(())
+/// ```
+pub trait TrashBackend {
+    /// Moves a file or directory to the trash, returning its info
+    fn trash(&self, path: &Path) -> Result<TrashInfo, TrashError>;
+
+    /// The items in the trash
+    fn list(&self) -> Result<Vec<TrashInfo>, TrashError>;
+
+    /// Moves an item back to where it was trashed from, returning that path
+    fn restore(&self, internal_filename: &OsStr) -> Result<PathBuf, TrashError>;
+
+    /// Permanently deletes an item
+    fn purge(&self, internal_filename: &OsStr) -> Result<(), TrashError>;
+}
+
+impl TrashBackend for Trash {
+    fn trash(&self, path: &Path) -> Result<TrashInfo, TrashError> {
+        let files = self.move_to_trash(path)?;
+        TrashInfo::from_path(&files.info_file)
+    }
+
+    fn list(&self) -> Result<Vec<TrashInfo>, TrashError> {
+        Ok(Trash::list(self)?
+            .into_iter()
+            .map(|item| item.info().clone())
+            .collect())
+    }
+
+    fn restore(&self, internal_filename: &OsStr) -> Result<PathBuf, TrashError> {
+        Trash::restore(self, internal_filename)
+    }
+
+    fn purge(&self, internal_filename: &OsStr) -> Result<(), TrashError> {
+        Trash::purge(self, internal_filename)
+    }
+}
+
+/// A trash and a filesystem which only exist in memory, for tests
+///
+/// Files are added with `add_file`; a path stands for a whole file or
+/// directory, and paths are taken as given, without being resolved
+pub struct MemoryTrash {
+    state: Mutex<MemoryState>,
+    clock: Box<dyn Clock>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    trashed: Vec<(TrashInfo, Vec<u8>)>,
+}
+
+impl std::fmt::Debug for MemoryTrash {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MemoryTrash")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl Default for MemoryTrash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryTrash {
+    /// An empty trash, with no files to trash yet
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+
+    /// An empty trash taking deletion dates from `clock`
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        MemoryTrash {
+            state: Mutex::new(MemoryState::default()),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Adds a file which can then be trashed, replacing any at `path`
+    pub fn add_file<P: Into<PathBuf>, C: Into<Vec<u8>>>(&self, path: P, contents: C) {
+        self.lock().files.insert(path.into(), contents.into());
+    }
+
+    /// Whether there is a file at `path`, outside of the trash
+    pub fn exists(&self, path: &Path) -> bool {
+        self.lock().files.contains_key(path)
+    }
+
+    /// The contents of the file at `path`, outside of the trash
+    pub fn contents(&self, path: &Path) -> Option<Vec<u8>> {
+        self.lock().files.get(path).cloned()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TrashBackend for MemoryTrash {
+    fn trash(&self, path: &Path) -> Result<TrashInfo, TrashError> {
+        let mut state = self.lock();
+        let contents = state
+            .files
+            .remove(path)
+            .ok_or_else(|| TrashError::SourceNotFound(path.to_path_buf()))?;
+        let base = path.file_name().unwrap_or(path.as_os_str());
+        let mut attempt = 1;
+        let internal_filename = loop {
+            let name = CollisionStrategy::Counter.internal_name(base, attempt);
+            if !state
+                .trashed
+                .iter()
+                .any(|(info, _)| info.internal_filename == name)
+            {
+                break name;
+            }
+            attempt += 1;
+        };
+        let info = TrashInfo::unchecked(
+            internal_filename,
+            path.as_os_str().to_os_string(),
+            self.clock.now(),
+        );
+        state.trashed.push((info.clone(), contents));
+        Ok(info)
+    }
+
+    fn list(&self) -> Result<Vec<TrashInfo>, TrashError> {
+        let mut infos: Vec<_> = self
+            .lock()
+            .trashed
+            .iter()
+            .map(|(info, _)| info.clone())
+            .collect();
+        infos.sort_by_key(|info| std::cmp::Reverse(info.deletion_date));
+        Ok(infos)
+    }
+
+    fn restore(&self, internal_filename: &OsStr) -> Result<PathBuf, TrashError> {
+        let mut state = self.lock();
+        let index = state
+            .trashed
+            .iter()
+            .position(|(info, _)| info.internal_filename == internal_filename)
+            .ok_or_else(|| TrashError::ItemNotFound(internal_filename.to_os_string()))?;
+        let original = PathBuf::from(&state.trashed[index].0.path);
+        if state.files.contains_key(&original) {
+            return Err(TrashError::RestoreConflict(original));
+        }
+        let (_, contents) = state.trashed.remove(index);
+        state.files.insert(original.clone(), contents);
+        Ok(original)
+    }
+
+    fn purge(&self, internal_filename: &OsStr) -> Result<(), TrashError> {
+        let mut state = self.lock();
+        let before = state.trashed.len();
+        state
+            .trashed
+            .retain(|(info, _)| info.internal_filename != internal_filename);
+        if state.trashed.len() == before {
+            return Err(TrashError::ItemNotFound(internal_filename.to_os_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryTrash, TrashBackend};
+    use crate::tests::fixture;
+    use crate::{Trash, TrashError, TrashOptions};
+    use std::path::Path;
+
+    #[test]
+    fn test_memory_trash() {
+        let trash = MemoryTrash::new();
+        let path = Path::new("/home/me/a.txt");
+        trash.add_file(path, "first");
+        let first = trash.trash(path).unwrap();
+        trash.add_file(path, "second");
+        let second = trash.trash(path).unwrap();
+        assert_eq!(first.internal_filename, "a.txt");
+        assert_eq!(second.internal_filename, "a.txt.2");
+        assert!(!trash.exists(path));
+        assert!(matches!(
+            trash.trash(path),
+            Err(TrashError::SourceNotFound(_))
+        ));
+
+        assert_eq!(trash.restore("a.txt".as_ref()).unwrap(), path);
+        assert_eq!(trash.contents(path).unwrap(), b"first");
+        assert!(matches!(
+            trash.restore("a.txt.2".as_ref()),
+            Err(TrashError::RestoreConflict(_))
+        ));
+        trash.purge("a.txt.2".as_ref()).unwrap();
+        assert!(trash.list().unwrap().is_empty());
+        assert!(matches!(
+            trash.purge("a.txt.2".as_ref()),
+            Err(TrashError::ItemNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_trash_backend() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("file");
+        std::fs::write(&path, b"x").unwrap();
+
+        let trash = Trash::with_options(TrashOptions::new().location(location));
+        let backend: &dyn TrashBackend = &trash;
+        let info = backend.trash(&path).unwrap();
+        assert_eq!(info.internal_filename, "file");
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,192 @@
+//! Shell completion scripts, printed by `trash completions SHELL`
+//! Item names for `trash restore` are completed by calling back into
+//! `trash __complete-names`, which prints one name per line
+
+/// Shells a completion script is available for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// The completion script for a shell
+pub fn script(shell: &str) -> Option<&'static str> {
+    match shell {
+        "bash" => Some(BASH),
+        "zsh" => Some(ZSH),
+        "fish" => Some(FISH),
+        _ => None,
+    }
+}
+
+const BASH: &str = r#"_trash() {
+    local cur prev command i
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    command=""
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            --json|--porcelain) ;;
+            *) command="${COMP_WORDS[i]}"; break ;;
+        esac
+    done
+
+    case "$command" in
+        "")
+            COMPREPLY=($(compgen -W "put rm list restore autoclean manifest completions help --json --porcelain" -- "$cur"))
+            ;;
+        put|rm)
+            if [[ "$prev" == "--files-from" ]]; then
+                COMPREPLY=($(compgen -f -- "$cur"))
+            elif [[ "$cur" == -* ]]; then
+                COMPREPLY=($(compgen -W "-0 --null --files-from -f --force -i -I --interactive" -- "$cur"))
+            else
+                COMPREPLY=($(compgen -f -- "$cur"))
+            fi
+            ;;
+        list)
+            if [[ "$prev" == "--sort" ]]; then
+                COMPREPLY=($(compgen -W "date size path" -- "$cur"))
+            else
+                COMPREPLY=($(compgen -W "--sort --reverse --limit --color" -- "$cur"))
+            fi
+            ;;
+        restore)
+            local IFS=$'\n'
+            if [[ "$cur" == -* ]]; then
+                COMPREPLY=($(compgen -W "--last" -- "$cur"))
+            else
+                COMPREPLY=($(compgen -W "$(trash __complete-names 2>/dev/null)" -- "$cur"))
+            fi
+            ;;
+        autoclean)
+            if [[ "$prev" == "--socket" ]]; then
+                COMPREPLY=($(compgen -f -- "$cur"))
+            else
+                COMPREPLY=($(compgen -W "--max-age --max-size --all-trashes --daemon --interval --socket --metrics --install-timer" -- "$cur"))
+            fi
+            ;;
+        completions)
+            COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur"))
+            ;;
+    esac
+}
+complete -o filenames -F _trash trash
+"#;
+
+const ZSH: &str = r#"#compdef trash
+
+_trash_names() {
+    local -a names
+    names=("${(@f)$(trash __complete-names 2>/dev/null)}")
+    compadd -a names
+}
+
+_trash() {
+    local -a commands
+    commands=(
+        'put:move files to the trash'
+        'rm:move files to the trash'
+        'list:list trashed items'
+        'restore:restore an item'
+        'autoclean:purge old items'
+        'manifest:print the metadata of trashed items'
+        'completions:print a shell completion script'
+        'help:show usage'
+    )
+    _arguments -C \
+        '(--porcelain)--json[print JSON output]' \
+        '(--json)--porcelain[print JSON output]' \
+        '1:command:->command' \
+        '*::arg:->args'
+    case $state in
+        command)
+            _describe 'command' commands
+            ;;
+        args)
+            case $words[1] in
+                put|rm)
+                    _arguments \
+                        '(-0 --null)'{-0,--null}'[paths in lists are NUL separated]' \
+                        '(-f --force -i -I)'{-f,--force}'[never ask, ignore missing files]' \
+                        '(-f --force -I)-i[ask before each file]' \
+                        '(-f --force -i)-I[ask once before many files]' \
+                        '*--files-from[read paths from a file]:list:_files' \
+                        '*:file:_files'
+                    ;;
+                list)
+                    _arguments \
+                        '--sort[order items by]:key:(date size path)' \
+                        '(-r --reverse)'{-r,--reverse}'[reverse the order]' \
+                        '--limit[show only the first N items]:count:' \
+                        '--color=-[color the output]::when:(always never auto)'
+                    ;;
+                restore)
+                    _arguments \
+                        '--last[restore the most recently trashed items]' \
+                        '*:name:_trash_names'
+                    ;;
+                autoclean)
+                    _arguments \
+                        '--max-age[purge items older than DAYS]:days:' \
+                        '--max-size[keep the trash below SIZE]:size:' \
+                        '--all-trashes[cover the trashes on every device]' \
+                        '--daemon[keep running]' \
+                        '--interval[seconds between runs]:seconds:' \
+                        '--socket[control socket]:socket:_files' \
+                        '--metrics[serve Prometheus metrics on ADDR]:address:' \
+                        '--install-timer[install a systemd user timer]'
+                    ;;
+                completions)
+                    _values 'shell' bash zsh fish
+                    ;;
+            esac
+            ;;
+    esac
+}
+
+_trash "$@"
+"#;
+
+const FISH: &str = r#"set -l commands put rm list restore autoclean manifest completions help
+complete -c trash -f
+complete -c trash -l json -d 'print JSON output'
+complete -c trash -l porcelain -d 'print JSON output'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a put -d 'move files to the trash'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a rm -d 'move files to the trash'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a list -d 'list trashed items'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a restore -d 'restore an item'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a autoclean -d 'purge old items'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a manifest -d 'print the metadata of trashed items'
+complete -c trash -n "not __fish_seen_subcommand_from $commands" -a completions -d 'print a shell completion script'
+complete -c trash -n "__fish_seen_subcommand_from put rm" -F
+complete -c trash -n "__fish_seen_subcommand_from put rm" -s 0 -l null -d 'paths in lists are NUL separated'
+complete -c trash -n "__fish_seen_subcommand_from put rm" -l files-from -r -F -d 'read paths from a file'
+complete -c trash -n "__fish_seen_subcommand_from put rm" -s f -l force -d 'never ask, ignore missing files'
+complete -c trash -n "__fish_seen_subcommand_from put rm" -s i -d 'ask before each file'
+complete -c trash -n "__fish_seen_subcommand_from put rm" -s I -d 'ask once before many files'
+complete -c trash -n "__fish_seen_subcommand_from list" -l sort -x -a 'date size path' -d 'order items by'
+complete -c trash -n "__fish_seen_subcommand_from list" -s r -l reverse -d 'reverse the order'
+complete -c trash -n "__fish_seen_subcommand_from list" -l limit -x -d 'show only the first N items'
+complete -c trash -n "__fish_seen_subcommand_from list" -l color -x -a 'always never auto' -d 'color the output'
+complete -c trash -n "__fish_seen_subcommand_from restore" -l last -d 'restore the most recently trashed items'
+complete -c trash -n "__fish_seen_subcommand_from restore" -a '(trash __complete-names 2>/dev/null)'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l max-age -r -d 'purge items older than DAYS'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l max-size -r -d 'keep the trash below SIZE'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l all-trashes -d 'cover the trashes on every device'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l daemon -d 'keep running'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l interval -r -d 'seconds between runs'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l socket -r -F -d 'control socket'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l metrics -r -d 'serve Prometheus metrics on ADDR'
+complete -c trash -n "__fish_seen_subcommand_from autoclean" -l install-timer -d 'install a systemd user timer'
+complete -c trash -n "__fish_seen_subcommand_from completions" -a 'bash zsh fish'
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::{script, SHELLS};
+
+    #[test]
+    fn test_scripts_complete_names() {
+        for shell in SHELLS {
+            assert!(script(shell).unwrap().contains("trash __complete-names"));
+        }
+        assert!(script("tcsh").is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set by SIGUSR1 to run a cleanup without waiting for the interval
+static CLEAN_NOW: AtomicBool = AtomicBool::new(false);
+/// Set by SIGINT or SIGTERM to leave the loop
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    if signal == libc::SIGUSR1 {
+        CLEAN_NOW.store(true, Ordering::SeqCst);
+    } else {
+        STOP.store(true, Ordering::SeqCst);
+    }
+}
+
+fn install_signal_handlers() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for &signal in &[libc::SIGUSR1, libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            libc::signal(signal, handler);
+        }
+    }
+}
+
+/// Calls `clean` every `interval` until asked to stop
+/// Sending SIGUSR1 runs `clean` immediately, SIGINT or SIGTERM
+/// finish the loop after any cleanup in progress
+pub fn run<F: FnMut()>(interval: Duration, mut clean: F) {
+    install_signal_handlers();
+    // Signals are only checked between naps, keep them short
+    let nap = Duration::from_millis(200).min(interval);
+    loop {
+        clean();
+        let started = Instant::now();
+        loop {
+            if STOP.load(Ordering::SeqCst) {
+                return;
+            }
+            if CLEAN_NOW.swap(false, Ordering::SeqCst) || started.elapsed() >= interval {
+                break;
+            }
+            std::thread::sleep(nap);
+        }
+    }
+}
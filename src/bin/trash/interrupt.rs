@@ -0,0 +1,67 @@
+//! SIGINT and SIGTERM as a flag rather than the end of the process, so
+//! that the file being trashed is either in the trash or left where it
+//! was, with nothing half copied, before exiting
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Set once a signal is received, and shared with the library through
+/// `TrashOptions::cancel_flag`
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+/// Set while waiting for an answer, when there is nothing to clean up
+static PROMPTING: AtomicBool = AtomicBool::new(false);
+
+/// The flag which a signal sets
+pub fn flag() -> Arc<AtomicBool> {
+    Arc::clone(INTERRUPTED.get_or_init(Default::default))
+}
+
+/// Whether a signal was received
+pub fn requested() -> bool {
+    INTERRUPTED
+        .get()
+        .is_some_and(|flag| flag.load(Ordering::SeqCst))
+}
+
+/// Runs `ask`, which waits on the user, exiting at once if interrupted
+/// meanwhile, as reading the answer is not cut short by the signal
+pub fn while_prompting<T, F: FnOnce() -> T>(ask: F) -> T {
+    PROMPTING.store(true, Ordering::SeqCst);
+    let answer = ask();
+    PROMPTING.store(false, Ordering::SeqCst);
+    answer
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    if PROMPTING.load(Ordering::SeqCst) {
+        unsafe {
+            libc::write(libc::STDERR_FILENO, b"\n".as_ptr().cast(), 1);
+            libc::_exit(crate::EXIT_INTERRUPTED);
+        }
+    }
+    if let Some(flag) = INTERRUPTED.get() {
+        flag.store(true, Ordering::SeqCst);
+    }
+    // Another signal ends the process right away, as it would otherwise
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+/// Sets the flag on SIGINT or SIGTERM instead of exiting
+#[cfg(unix)]
+pub fn install() {
+    flag();
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for &signal in &[libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            libc::signal(signal, handler);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {
+    flag();
+}
@@ -0,0 +1,167 @@
+use std::cmp::Reverse;
+use std::time::Duration;
+use trash::TrashedItem;
+
+/// What `trash list` orders items by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Most recently deleted first
+    Date,
+    /// Largest first
+    Size,
+    /// By original path
+    Path,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "date" | "time" => Some(SortKey::Date),
+            "size" => Some(SortKey::Size),
+            "path" | "name" => Some(SortKey::Path),
+            _ => None,
+        }
+    }
+}
+
+/// One item as shown by `trash list`, its size measured once
+pub struct Row {
+    pub item: TrashedItem,
+    /// `None` if the item is gone or could not be measured
+    pub size: Option<u64>,
+}
+
+impl Row {
+    pub fn new(item: TrashedItem) -> Self {
+        let size = item.size().ok();
+        Row { item, size }
+    }
+}
+
+/// Orders rows by `key`, reversed if asked to
+pub fn sort_rows(rows: &mut [Row], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Date => rows.sort_by_key(|row| Reverse(row.item.deleted_at())),
+        SortKey::Size => rows.sort_by_key(|row| Reverse(row.size)),
+        SortKey::Path => rows.sort_by(|a, b| a.item.original_path().cmp(b.item.original_path())),
+    }
+    if reverse {
+        rows.reverse();
+    }
+}
+
+/// Sizes in powers of 1024 with one decimal below 10, as `ls -h` shows
+/// them
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+/// How long ago something happened, in the largest whole unit
+pub fn relative_age(age: Duration) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+    let secs = age.as_secs();
+    for &(name, unit) in &UNITS {
+        let count = secs / unit;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            return format!("{} {}{} ago", count, name, plural);
+        }
+    }
+    "just now".to_owned()
+}
+
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Lines of a table with sizes right aligned and ages left aligned,
+/// directories in bold blue and items which are gone in red when `color`
+pub fn format_rows(rows: &[Row], color: bool) -> Vec<String> {
+    let sizes: Vec<String> = rows
+        .iter()
+        .map(|row| row.size.map_or_else(|| "?".to_owned(), human_size))
+        .collect();
+    let ages: Vec<String> = rows
+        .iter()
+        .map(|row| relative_age(row.item.age()))
+        .collect();
+    let size_width = sizes.iter().map(String::len).max().unwrap_or(0);
+    let age_width = ages.iter().map(String::len).max().unwrap_or(0);
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text
+        }
+    };
+
+    rows.iter()
+        .zip(sizes)
+        .zip(ages)
+        .map(|((row, size), age)| {
+            let size = format!("{:>width$}", size, width = size_width);
+            let age = format!("{:<width$}", age, width = age_width);
+            let path = row.item.original_path().display().to_string();
+            let path = if row.size.is_none() {
+                paint(RED, path)
+            } else if row.item.trash_file().is_dir() {
+                paint(BOLD_BLUE, path)
+            } else {
+                path
+            };
+            format!("{}  {}  {}", size, paint(DIM, age), path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{human_size, relative_age, SortKey};
+    use std::time::Duration;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(200 << 20), "200M");
+        assert_eq!(human_size(3 << 30), "3.0G");
+    }
+
+    #[test]
+    fn test_relative_age() {
+        let hour = 60 * 60;
+        assert_eq!(relative_age(Duration::from_secs(5)), "just now");
+        assert_eq!(relative_age(Duration::from_secs(60)), "1 minute ago");
+        assert_eq!(relative_age(Duration::from_secs(3 * hour)), "3 hours ago");
+        assert_eq!(relative_age(Duration::from_secs(50 * hour)), "2 days ago");
+        assert_eq!(
+            relative_age(Duration::from_secs(400 * 24 * hour)),
+            "1 year ago"
+        );
+        assert_eq!(SortKey::parse("name"), Some(SortKey::Path));
+        assert_eq!(SortKey::parse("age"), None);
+    }
+}
@@ -0,0 +1,1180 @@
+mod completions;
+#[cfg(unix)]
+mod daemon;
+mod interrupt;
+mod listing;
+#[cfg(unix)]
+mod metrics;
+mod output;
+#[cfg(unix)]
+mod socket;
+#[cfg(unix)]
+mod timer;
+
+use crate::listing::{format_rows, sort_rows, Row, SortKey};
+use crate::output::{path_value, Output};
+use serde_json::{json, Value};
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process::exit;
+use std::time::Duration;
+use trash::{
+    apply_retention, apply_retention_with, list_all_trash, list_trash, restore_from_trash,
+    RetentionPolicy, Trash, TrashConfig, TrashLocation, TrashedItem,
+};
+
+const USAGE: &str = "Usage: trash [--json] <command> [args]
+
+Commands:
+  put [-f|-i|-I] [-0] [--files-from LIST] [--max-size SIZE] FILE...
+                    move files to the trash, including those listed one per
+                    line in LIST, or in stdin when FILE or LIST is -
+                    with -0, listed paths are separated by NUL bytes instead
+                    files holding more than SIZE are refused unless -f
+                    as with rm, -i asks before each file, -I asks once
+                    before more than three files or any directory, and
+                    -f never asks and ignores files which do not exist;
+                    the last of them given wins, and -v prints each file
+                    trashed; names starting with - go after --
+  rm [OPTION]... FILE...
+                    put, standing in for rm: directories are refused
+                    without -r or -R, or -d for empty ones, FILE - is a
+                    file, and unknown options and those trashing cannot
+                    honor, such as --one-file-system, are errors
+  list [--sort date|size|path] [-r] [--limit N] [--color[=WHEN]]
+                    list trashed items with their sizes and how long ago
+                    they were deleted, most recent first unless sorted by
+                    size, largest first, or by path; -r reverses the order
+                    and --limit shows only the first N
+                    WHEN is always, never or auto (the default), coloring
+                    only a terminal and only without NO_COLOR set
+  restore [--last [N] | NAME...]
+                    restore items, choosing from a list when no NAME is
+                    given, or the N most recently trashed (default 1)
+  autoclean [--max-age DAYS] [--max-size SIZE] [--all-trashes]
+            [--daemon [--interval SECS] [--socket PATH] [--metrics ADDR]
+             | --install-timer]
+                    purge items older than DAYS, then the oldest items until
+                    the trash fits in SIZE (such as 500M or 10G), which
+                    default to retention_days and max_size from the
+                    configuration file
+                    with --all-trashes, cover the trashes on every mounted
+                    device, SIZE capping them combined
+                    with --daemon, repeat every SECS seconds (default 3600)
+                    or whenever SIGUSR1 is received, and take trash, list,
+                    restore and empty requests on the unix socket PATH
+                    and serve Prometheus metrics over HTTP on ADDR (such
+                    as 127.0.0.1:9464)
+                    with --install-timer, write and enable a systemd user
+                    timer running this cleanup every SECS seconds instead
+  manifest          print the metadata of every trashed item, but none of
+                    its content, as JSON Lines for backup tools
+  completions SHELL print a completion script for bash, zsh or fish
+
+Options:
+  --json, --porcelain
+                    print a single JSON document on stdout, including errors
+
+Configuration:
+  $XDG_CONFIG_HOME/trash-rs/config.toml, or ~/.config/trash-rs/config.toml,
+  sets retention_days, max_size, all_trashes, collisions, cross_device and
+  excluded_mounts as defaults for all commands
+  TRASH_RETENTION_DAYS, TRASH_MAX_SIZE, TRASH_ALL_TRASHES, TRASH_COLLISIONS,
+  TRASH_CROSS_DEVICE and TRASH_EXCLUDED_MOUNTS (separated by ':') override
+  the file
+
+Exit status:
+  0  all operations succeeded
+  1  one or more files could not be trashed or restored
+  2  invalid command line
+  130
+     interrupted by SIGINT or SIGTERM: the file being trashed is left in
+     place, and later files are not trashed or restored";
+
+/// All operations succeeded
+const EXIT_SUCCESS: i32 = 0;
+/// At least one operand failed, the others were still processed
+const EXIT_FAILURE: i32 = 1;
+/// The command line could not be understood
+const EXIT_USAGE: i32 = 2;
+/// Stopped by SIGINT or SIGTERM, as shells report a process killed by SIGINT
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// A mistake in how the command was invoked
+#[derive(Debug)]
+struct UsageError(String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}\nTry 'trash --help' for more information.", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+type CmdResult = Result<i32, Box<dyn std::error::Error>>;
+
+fn main() {
+    let mut out = Output { json: false };
+    let mut args = Vec::new();
+    let mut options_done = false;
+    for arg in std::env::args_os().skip(1) {
+        if !options_done && (arg == "--json" || arg == "--porcelain") {
+            out.json = true;
+        } else {
+            options_done |= arg == "--";
+            args.push(arg);
+        }
+    }
+
+    let res = if args.is_empty() {
+        Err(UsageError("missing command".to_owned()).into())
+    } else {
+        let command = args.remove(0);
+        match command.to_str() {
+            Some("put") => cmd_put(&out, &args, false),
+            Some("rm") => cmd_put(&out, &args, true),
+            Some("list") => cmd_list(&out, &args),
+            Some("restore") => cmd_restore(&out, &args),
+            Some("autoclean") => cmd_autoclean(&out, &args),
+            Some("manifest") => cmd_manifest(&args),
+            Some("completions") => cmd_completions(&args),
+            Some("__complete-names") => cmd_complete_names(),
+            Some("-h") | Some("--help") | Some("help") => {
+                println!("{}", USAGE);
+                Ok(EXIT_SUCCESS)
+            }
+            _ => Err(UsageError(format!("unknown command {:?}", command)).into()),
+        }
+    };
+
+    match res {
+        Ok(code) => exit(code),
+        Err(e) => {
+            out.error(e.as_ref());
+            if e.is::<UsageError>() {
+                exit(EXIT_USAGE);
+            }
+            exit(EXIT_FAILURE);
+        }
+    }
+}
+
+/// Mirrors the operands `rm` refuses to delete: `/` and anything
+/// whose last component is `.` or `..`
+fn refuse_reason(path: &OsStr) -> Option<&'static str> {
+    let bytes = path.as_encoded_bytes();
+    let trimmed = match bytes.iter().rposition(|&b| b != b'/') {
+        Some(last) => &bytes[..=last],
+        None => return Some("it is dangerous to operate recursively on '/'"),
+    };
+    let base = match trimmed.iter().rposition(|&b| b == b'/') {
+        Some(slash) => &trimmed[slash + 1..],
+        None => trimmed,
+    };
+    if base == b"." || base == b".." {
+        return Some("refusing to remove '.' or '..' directory");
+    }
+    None
+}
+
+/// When `put` asks before trashing, following `rm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    /// Never, as with `-f`, which also ignores files which do not exist
+    Never,
+    /// Only once before trashing more than three files or any directory,
+    /// as with `-I`
+    Once,
+    /// Before each file, as with `-i`
+    Always,
+}
+
+/// The `rm` options given together as in `-rf`, or `None` if `arg`
+/// is not made only of them, in which case it is taken as a file
+fn rm_flags(arg: &OsStr) -> Option<&str> {
+    let flags = arg.to_str()?.strip_prefix('-')?;
+    if !flags.is_empty() && flags.chars().all(|c| "fiIrRdv".contains(c)) {
+        Some(flags)
+    } else {
+        None
+    }
+}
+
+/// Asks the user on stderr, true if the answer starts with `y`
+fn confirm(question: &str) -> std::io::Result<bool> {
+    eprint!("trash: {} ", question);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    interrupt::while_prompting(|| std::io::stdin().lock().read_line(&mut line))?;
+    Ok(line.trim_start().starts_with(['y', 'Y']))
+}
+
+/// The question asked before trashing `path` with `-i`
+fn prompt_for(path: &OsStr) -> Option<String> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    let kind = if metadata.is_dir() {
+        "directory"
+    } else if metadata.file_type().is_symlink() {
+        "symbolic link"
+    } else if metadata.len() == 0 {
+        "regular empty file"
+    } else {
+        "regular file"
+    };
+    Some(format!("remove {} '{}'?", kind, path.to_string_lossy()))
+}
+
+/// Why `trash rm` refuses an option which `put` would take as a file:
+/// `rm` options which trashing cannot honor, and anything unknown
+fn unsupported_rm_option(arg: &OsStr) -> UsageError {
+    let arg = arg.to_string_lossy();
+    let reason = match arg.as_ref() {
+        "--one-file-system" => "directories are trashed whole, across file systems",
+        "--no-preserve-root" => "'/' is never trashed",
+        "--preserve-root=all" => "operands on other devices are not refused",
+        _ if arg.starts_with("--") => {
+            return UsageError(format!("rm: unrecognized option {:?}", arg))
+        }
+        _ => {
+            let unknown = arg
+                .chars()
+                .skip(1)
+                .find(|&c| !"fiIrRdv".contains(c))
+                .unwrap_or('-');
+            return UsageError(format!("rm: invalid option -- '{}'", unknown));
+        }
+    };
+    UsageError(format!("rm: {} is not supported: {}", arg, reason))
+}
+
+/// Why `trash rm` refuses to trash a directory, as `rm` would delete
+/// one only with `-r`, or with `-d` if it is empty
+fn directory_refusal(path: &OsStr, recursive: bool, empty_dirs: bool) -> Option<&'static str> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    if !metadata.is_dir() || recursive {
+        return None;
+    }
+    if !empty_dirs {
+        return Some("Is a directory");
+    }
+    let is_empty = std::fs::read_dir(path).map_or(true, |mut entries| entries.next().is_none());
+    if is_empty {
+        None
+    } else {
+        Some("Directory not empty")
+    }
+}
+
+/// Trashes files for `put`, or for `rm` when `rm_mode` is set, which
+/// refuses what `rm` itself would and options it cannot honor, so
+/// that it can stand in for `rm`
+fn cmd_put(out: &Output, args: &[OsString], rm_mode: bool) -> CmdResult {
+    let mut paths: Vec<OsString> = Vec::new();
+    let mut null_separated = false;
+    let mut max_size = None;
+    let mut prompt = None;
+    let mut recursive = false;
+    let mut empty_dirs = false;
+    let mut verbose = false;
+    let mut lists = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--" {
+            paths.extend(args[i + 1..].iter().cloned());
+            break;
+        } else if args[i] == "-0" || args[i] == "--null" {
+            null_separated = true;
+        } else if args[i] == "--force" {
+            prompt = Some(Prompt::Never);
+        } else if args[i] == "--recursive" {
+            recursive = true;
+        } else if args[i] == "--dir" {
+            empty_dirs = true;
+        } else if args[i] == "--verbose" {
+            verbose = true;
+        } else if args[i] == "--preserve-root" {
+        } else if let Some(when) = args[i]
+            .to_str()
+            .and_then(|arg| arg.strip_prefix("--interactive"))
+        {
+            prompt = Some(match when {
+                "" | "=always" | "=yes" => Prompt::Always,
+                "=once" => Prompt::Once,
+                "=never" | "=no" | "=none" => Prompt::Never,
+                _ => {
+                    return Err(UsageError(format!(
+                        "invalid argument {:?} for --interactive",
+                        when.trim_start_matches('=')
+                    ))
+                    .into())
+                }
+            });
+        } else if let Some(flags) = rm_flags(&args[i]) {
+            for flag in flags.chars() {
+                match flag {
+                    'f' => prompt = Some(Prompt::Never),
+                    'i' => prompt = Some(Prompt::Always),
+                    'I' => prompt = Some(Prompt::Once),
+                    'r' | 'R' => recursive = true,
+                    'd' => empty_dirs = true,
+                    _ => verbose = true,
+                }
+            }
+        } else if rm_mode && args[i] != "-" && args[i].as_encoded_bytes().starts_with(b"-") {
+            return Err(unsupported_rm_option(&args[i]).into());
+        } else if args[i] == "-" && !rm_mode {
+            lists.push("-".to_owned());
+        } else if let Some(size) = option_value(args, &mut i, "--max-size")? {
+            max_size =
+                Some(parse_size(&size).ok_or_else(|| {
+                    UsageError(format!("invalid size {:?} for --max-size", size))
+                })?);
+        } else if let Some(list) = option_value(args, &mut i, "--files-from")? {
+            lists.push(list);
+        } else if args[i].as_encoded_bytes().starts_with(b"-") {
+            // Names starting with `-` can still be given after `--`
+            return Err(UsageError(format!("put: unknown option {:?}", args[i])).into());
+        } else {
+            paths.push(args[i].clone());
+        }
+        i += 1;
+    }
+    let mut read_stdin = false;
+    for list in lists {
+        let listed = if list == "-" {
+            if std::mem::replace(&mut read_stdin, true) {
+                continue;
+            }
+            read_path_list(std::io::stdin().lock(), null_separated)
+        } else {
+            std::fs::File::open(&list).and_then(|file| read_path_list(file, null_separated))
+        };
+        paths.extend(listed.map_err(|e| format!("cannot read paths from {}: {}", list, e))?);
+    }
+    let force = prompt == Some(Prompt::Never);
+    if paths.is_empty() && !read_stdin && !force {
+        let command = if rm_mode { "rm" } else { "put" };
+        return Err(UsageError(format!("{}: missing file operand", command)).into());
+    }
+    if prompt == Some(Prompt::Once)
+        && (paths.len() > 3 || paths.iter().any(|path| Path::new(path).is_dir()))
+    {
+        let recursively = paths.iter().any(|path| Path::new(path).is_dir());
+        let question = format!(
+            "remove {} argument{}{}?",
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" },
+            if recursively { " recursively" } else { "" }
+        );
+        if !confirm(&question)? {
+            return Ok(EXIT_SUCCESS);
+        }
+    }
+
+    interrupt::install();
+    let options = TrashConfig::load()?
+        .options()
+        .max_size(if force { None } else { max_size })
+        .cancel_flag(interrupt::flag());
+    let trash = Trash::with_options(options);
+    let mut code = EXIT_SUCCESS;
+    let mut trashed = Vec::new();
+    let mut failed = Vec::new();
+    for path in &paths {
+        if interrupt::requested() {
+            break;
+        }
+        if force && std::fs::symlink_metadata(path).is_err() {
+            continue;
+        }
+        if prompt == Some(Prompt::Always) {
+            if let Some(question) = prompt_for(path) {
+                if !confirm(&question)? {
+                    continue;
+                }
+            }
+        }
+        let refused = refuse_reason(path)
+            .or_else(|| directory_refusal(path, recursive, empty_dirs).filter(|_| rm_mode));
+        let res = match refused {
+            Some(reason) => Err(reason.to_owned()),
+            None => trash.move_to_trash(path).map_err(|e| e.to_string()),
+        };
+        match res {
+            Ok(files) => {
+                if verbose && !out.json {
+                    println!("trashed '{}'", path.to_string_lossy());
+                }
+                trashed.push(json!({
+                "path": path_value(path),
+                "trash_file": path_value(&files.trash_file),
+                "info_file": path_value(&files.info_file),
+                "trash_dir": path_value(&files.trash_dir),
+                "location": location_value(&files.location),
+                }))
+            }
+            // Left in place, as if it had not been reached
+            Err(_) if interrupt::requested() => break,
+            Err(message) => {
+                code = EXIT_FAILURE;
+                if !out.json {
+                    eprintln!("trash: cannot trash {:?}: {}", path, message);
+                }
+                failed.push(json!({ "path": path_value(path), "message": message }));
+            }
+        }
+    }
+    out.result(
+        json!({
+            "trashed": trashed,
+            "failed": failed,
+            "interrupted": interrupt::requested(),
+        }),
+        || {},
+    );
+    Ok(interrupted_or(code))
+}
+
+fn cmd_completions(args: &[OsString]) -> CmdResult {
+    let shell = match args {
+        [shell] => shell.to_string_lossy(),
+        _ => return Err(UsageError("completions: expected one SHELL".to_owned()).into()),
+    };
+    match completions::script(&shell) {
+        Some(script) => {
+            print!("{}", script);
+            Ok(EXIT_SUCCESS)
+        }
+        None => Err(UsageError(format!(
+            "completions: unknown shell {:?}, expected one of {}",
+            shell,
+            completions::SHELLS.join(", ")
+        ))
+        .into()),
+    }
+}
+
+/// Metadata of everything in the trash for backup tools, see
+/// `trash::export_manifest`
+fn cmd_manifest(args: &[OsString]) -> CmdResult {
+    if let Some(arg) = args.first() {
+        return Err(UsageError(format!("manifest: unexpected argument {:?}", arg)).into());
+    }
+    let stdout = std::io::stdout();
+    trash::export_manifest(stdout.lock())?;
+    Ok(EXIT_SUCCESS)
+}
+
+/// Names of the items `restore` could put back, for shell completion
+fn cmd_complete_names() -> CmdResult {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for item in list_trash()? {
+        if item.is_restorable() {
+            stdout.write_all(item.internal_filename().as_encoded_bytes())?;
+            stdout.write_all(b"\n")?;
+        }
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+/// Reads paths separated by newlines, or by NUL bytes as printed by
+/// `find -print0`, skipping empty ones
+fn read_path_list<R: std::io::Read>(
+    mut reader: R,
+    null_separated: bool,
+) -> std::io::Result<Vec<OsString>> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    Ok(content
+        .split(|&b| b == separator)
+        .filter(|path| !path.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// A listed path, which other platforms only take as UTF-8
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> OsString {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+fn cmd_list(out: &Output, args: &[OsString]) -> CmdResult {
+    let mut sort = SortKey::Date;
+    let mut reverse = false;
+    let mut limit = None;
+    let mut color = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "-r" || args[i] == "--reverse" {
+            reverse = true;
+        } else if args[i] == "--color" {
+            color = Some(true);
+        } else if let Some(value) = option_value(args, &mut i, "--sort")? {
+            sort = SortKey::parse(&value)
+                .ok_or_else(|| UsageError(format!("invalid sort key {:?}", value)))?;
+        } else if let Some(value) = option_value(args, &mut i, "--limit")? {
+            limit = Some(parse_number::<usize>(&value, "--limit")?);
+        } else if let Some(value) = option_value(args, &mut i, "--color")? {
+            color = match value.as_str() {
+                "always" | "yes" => Some(true),
+                "never" | "no" => Some(false),
+                "auto" => None,
+                _ => {
+                    return Err(UsageError(format!("invalid value {:?} for --color", value)).into())
+                }
+            };
+        } else {
+            return Err(UsageError(format!("list: unexpected argument {:?}", args[i])).into());
+        }
+        i += 1;
+    }
+    let color = color.unwrap_or_else(|| {
+        std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+    });
+
+    let mut rows: Vec<Row> = list_trash()?.into_iter().map(Row::new).collect();
+    sort_rows(&mut rows, sort, reverse);
+    rows.truncate(limit.unwrap_or(rows.len()));
+    let items: Vec<TrashedItem> = rows.iter().map(|row| row.item.clone()).collect();
+    out.result(item_values(&items), || {
+        for line in format_rows(&rows, color) {
+            println!("{}", line);
+        }
+    });
+    Ok(EXIT_SUCCESS)
+}
+
+/// Splits the arguments of `restore` into the names to restore and the
+/// count of `--last`, which is 1 when given bare
+fn parse_restore_args(args: &[OsString]) -> Result<(Vec<OsString>, Option<usize>), UsageError> {
+    let mut names = Vec::new();
+    let mut last = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--" {
+            names.extend(args[i + 1..].iter().cloned());
+            break;
+        } else if args[i] == "--last" {
+            // The count is optional, so only a number is taken as one
+            match args.get(i + 1).and_then(|next| next.to_str()) {
+                Some(next) if !next.is_empty() && next.bytes().all(|b| b.is_ascii_digit()) => {
+                    last = Some(parse_number(next, "--last")?);
+                    i += 1;
+                }
+                _ => last = Some(1),
+            }
+        } else if let Some(count) = option_value(args, &mut i, "--last")? {
+            last = Some(parse_number(&count, "--last")?);
+        } else {
+            names.push(args[i].clone());
+        }
+        i += 1;
+    }
+    match last {
+        Some(0) => Err(UsageError(
+            "restore: --last needs a count of at least 1".to_owned(),
+        )),
+        Some(_) if !names.is_empty() => Err(UsageError(
+            "restore: --last cannot be given with a NAME".to_owned(),
+        )),
+        _ => Ok((names, last)),
+    }
+}
+
+fn cmd_restore(out: &Output, args: &[OsString]) -> CmdResult {
+    let (names, last) = parse_restore_args(args)?;
+    let names = match last {
+        Some(count) => {
+            let items = list_trash()?;
+            if items.is_empty() {
+                return Err("the trash is empty".into());
+            }
+            items
+                .iter()
+                .take(count)
+                .map(|item| item.internal_filename().to_os_string())
+                .collect()
+        }
+        None if names.is_empty() => pick_items()?,
+        None => names,
+    };
+
+    interrupt::install();
+    let mut code = EXIT_SUCCESS;
+    let mut restored = Vec::new();
+    let mut failed = Vec::new();
+    for name in names {
+        if interrupt::requested() {
+            break;
+        }
+        match restore_from_trash(&name) {
+            Ok(path) => {
+                if !out.json {
+                    println!("restored {}", path.display());
+                }
+                restored.push(json!({
+                    "name": path_value(&name),
+                    "path": path_value(&path),
+                }));
+            }
+            Err(e) => {
+                code = EXIT_FAILURE;
+                if !out.json {
+                    eprintln!("trash: cannot restore {:?}: {}", name, e);
+                }
+                failed.push(json!({ "name": path_value(&name), "message": e.to_string() }));
+            }
+        }
+    }
+    out.result(
+        json!({
+            "restored": restored,
+            "failed": failed,
+            "interrupted": interrupt::requested(),
+        }),
+        || {},
+    );
+    Ok(interrupted_or(code))
+}
+
+/// `EXIT_INTERRUPTED` if a signal stopped the command, telling the user
+/// so, otherwise `code`
+fn interrupted_or(code: i32) -> i32 {
+    if !interrupt::requested() {
+        return code;
+    }
+    eprintln!("trash: interrupted");
+    EXIT_INTERRUPTED
+}
+
+fn cmd_autoclean(out: &Output, args: &[OsString]) -> CmdResult {
+    let mut policy = TrashConfig::load()?.retention();
+    let mut daemon = false;
+    let mut install_timer = false;
+    // The policy as given, for the command line of the timer's service
+    let mut policy_args = Vec::new();
+    let mut interval = Duration::from_secs(60 * 60);
+    let mut socket_path = None;
+    let mut metrics_addr = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        if let Some(days) = option_value(args, &mut i, "--max-age")? {
+            policy.max_age = Some(parse_max_age(&days)?);
+            policy_args.push(format!("--max-age={}", days));
+        } else if let Some(size) = option_value(args, &mut i, "--max-size")? {
+            policy.max_size =
+                Some(parse_size(&size).ok_or_else(|| {
+                    UsageError(format!("invalid size {:?} for --max-size", size))
+                })?);
+            policy_args.push(format!("--max-size={}", size));
+        } else if let Some(secs) = option_value(args, &mut i, "--interval")? {
+            let secs: u64 = parse_number(&secs, "--interval")?;
+            interval = Duration::from_secs(secs.max(1));
+        } else if let Some(path) = option_value(args, &mut i, "--socket")? {
+            socket_path = Some(std::path::PathBuf::from(path));
+        } else if let Some(addr) = option_value(args, &mut i, "--metrics")? {
+            metrics_addr = Some(addr);
+        } else if args[i] == "--all-trashes" {
+            policy.all_trashes = true;
+            policy_args.push("--all-trashes".to_owned());
+        } else if args[i] == "--daemon" {
+            daemon = true;
+        } else if args[i] == "--install-timer" {
+            install_timer = true;
+        } else {
+            return Err(UsageError(format!("autoclean: unknown option {:?}", args[i])).into());
+        }
+        i += 1;
+    }
+    if policy.is_unlimited() {
+        return Err(UsageError(
+            "autoclean: give --max-age and/or --max-size, or set retention_days or max_size \
+             in the configuration file"
+                .to_owned(),
+        )
+        .into());
+    }
+    if socket_path.is_some() && !daemon {
+        return Err(UsageError("autoclean: --socket requires --daemon".to_owned()).into());
+    }
+    if metrics_addr.is_some() && !daemon {
+        return Err(UsageError("autoclean: --metrics requires --daemon".to_owned()).into());
+    }
+    if install_timer {
+        if daemon {
+            return Err(UsageError(
+                "autoclean: --install-timer cannot be used with --daemon".to_owned(),
+            )
+            .into());
+        }
+        return install_timer_units(out, &policy_args, interval);
+    }
+
+    if !daemon {
+        let purged = apply_retention(&policy)?;
+        report_purged(out, &purged);
+        return Ok(EXIT_SUCCESS);
+    }
+
+    run_daemon(
+        out,
+        &policy,
+        interval,
+        socket_path.as_deref(),
+        metrics_addr.as_deref(),
+    )
+}
+
+/// Repeats a cleanup until a signal is received, serving socket requests
+#[cfg(unix)]
+fn run_daemon(
+    out: &Output,
+    policy: &RetentionPolicy,
+    interval: Duration,
+    socket_path: Option<&std::path::Path>,
+    metrics_addr: Option<&str>,
+) -> CmdResult {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    // Cleanups and socket requests take turns with the trash
+    let lock = Arc::new(Mutex::new(()));
+    if let Some(path) = socket_path {
+        socket::serve(path, Arc::clone(&lock))?;
+    }
+    if let Some(addr) = metrics_addr {
+        metrics::serve(addr)?;
+    }
+    daemon::run(interval, || {
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let started = Instant::now();
+        let result = apply_retention_with(policy, metrics::record_purge);
+        metrics::record_cleanup(started.elapsed(), result.is_ok());
+        match result {
+            Ok(purged) => report_purged(out, &purged),
+            // Keep running, the next round may well succeed
+            Err(e) => out.error(&e),
+        }
+        if metrics::enabled() {
+            let remaining = if policy.all_trashes {
+                list_all_trash()
+            } else {
+                list_trash()
+            };
+            if let Ok(items) = remaining {
+                metrics::record_trash(&items);
+            }
+        }
+    });
+    if let Some(path) = socket_path {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+/// Signals and unix sockets are needed to run as a daemon
+#[cfg(not(unix))]
+fn run_daemon(
+    _out: &Output,
+    _policy: &RetentionPolicy,
+    _interval: Duration,
+    _socket_path: Option<&std::path::Path>,
+    _metrics_addr: Option<&str>,
+) -> CmdResult {
+    Err(UsageError("autoclean: --daemon is only supported on unix".to_owned()).into())
+}
+
+/// Leaves the periodic cleanups to systemd
+#[cfg(unix)]
+fn install_timer_units(out: &Output, policy_args: &[String], interval: Duration) -> CmdResult {
+    let unit_dir = timer::user_unit_dir().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "neither XDG_CONFIG_HOME nor HOME is set",
+        )
+    })?;
+    let exe = std::env::current_exe()?;
+    let units = timer::install(&unit_dir, &exe, policy_args, interval)?;
+    // The units are still useful when systemd can't be reached right now
+    let enabled = timer::enable();
+    out.result(
+        json!({
+            "installed": units.iter().map(path_value).collect::<Vec<_>>(),
+            "enabled": enabled.is_ok(),
+        }),
+        || {
+            for unit in &units {
+                println!("installed {}", unit.display());
+            }
+            match &enabled {
+                Ok(()) => println!("enabled {}.timer", timer::UNIT_NAME),
+                Err(e) => eprintln!(
+                    "trash: could not enable the timer ({}), run: systemctl --user enable --now {}.timer",
+                    e,
+                    timer::UNIT_NAME
+                ),
+            }
+        },
+    );
+    Ok(EXIT_SUCCESS)
+}
+
+/// systemd timers are only found on unix
+#[cfg(not(unix))]
+fn install_timer_units(_out: &Output, _policy_args: &[String], _interval: Duration) -> CmdResult {
+    Err(UsageError("autoclean: --install-timer is only supported on unix".to_owned()).into())
+}
+
+fn report_purged(out: &Output, purged: &[TrashedItem]) {
+    out.result(json!({ "purged": item_values(purged) }), || {
+        for item in purged {
+            println!("purged {}", format_item(item));
+        }
+    });
+}
+
+/// Reads the value of `args[*i]` if it is the option `name`, written
+/// either as `name=VALUE` or as `name VALUE` (moving `i` onto the value)
+fn option_value(
+    args: &[OsString],
+    i: &mut usize,
+    name: &str,
+) -> Result<Option<String>, UsageError> {
+    let arg = match args[*i].to_str() {
+        Some(arg) => arg,
+        None => return Ok(None),
+    };
+    if arg == name {
+        *i += 1;
+        return match args.get(*i).and_then(|value| value.to_str()) {
+            Some(value) => Ok(Some(value.to_owned())),
+            None => Err(UsageError(format!("option {} requires a value", name))),
+        };
+    }
+    match arg
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('='))
+    {
+        Some(value) => Ok(Some(value.to_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, UsageError> {
+    value
+        .parse()
+        .map_err(|_| UsageError(format!("invalid number {:?} for {}", value, name)))
+}
+
+/// Parses the days of `--max-age` into how long that is
+fn parse_max_age(value: &str) -> Result<Duration, UsageError> {
+    let days: u64 = parse_number(value, "--max-age")?;
+    days.checked_mul(24 * 60 * 60)
+        .map(Duration::from_secs)
+        .ok_or_else(|| UsageError(format!("--max-age {} is too large", days)))
+}
+
+/// Parses sizes such as `1024`, `500K`, `20M` or `1.5G` (powers of 1024)
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" | "KI" => 1 << 10,
+        "M" | "MI" => 1 << 20,
+        "G" | "GI" => 1 << 30,
+        "T" | "TI" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Shows the trash contents and asks which items to restore
+/// The menu goes to stderr so stdout only carries results
+fn pick_items() -> Result<Vec<OsString>, Box<dyn std::error::Error>> {
+    let items = list_trash()?;
+    if items.is_empty() {
+        return Err("the trash is empty".into());
+    }
+
+    let width = items.len().to_string().len();
+    for (i, item) in items.iter().enumerate() {
+        eprintln!("{:>width$}) {}", i + 1, format_item(item), width = width);
+    }
+
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("Restore which items? [1-{}, q to quit]: ", items.len());
+        std::io::stderr().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            // End of input, treat like quitting
+            return Ok(Vec::new());
+        }
+        let line = line.trim();
+        if line.is_empty() || line == "q" {
+            return Ok(Vec::new());
+        }
+
+        match parse_selection(line, items.len()) {
+            Some(selected) => {
+                return Ok(selected
+                    .into_iter()
+                    .map(|i| items[i].internal_filename().to_os_string())
+                    .collect())
+            }
+            None => eprintln!("Invalid selection {:?}", line),
+        }
+    }
+}
+
+/// Parses a list of 1-based item numbers and ranges such as "1 3,5-7"
+/// into 0-based indexes, rejecting anything out of range
+fn parse_selection(line: &str, count: usize) -> Option<Vec<usize>> {
+    let mut selected = Vec::new();
+    for part in line.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+        let (start, end) = match part.find('-') {
+            Some(dash) => (
+                part[..dash].parse::<usize>().ok()?,
+                part[dash + 1..].parse::<usize>().ok()?,
+            ),
+            None => {
+                let n = part.parse::<usize>().ok()?;
+                (n, n)
+            }
+        };
+        if start == 0 || start > end || end > count {
+            return None;
+        }
+        for n in start..=end {
+            if !selected.contains(&(n - 1)) {
+                selected.push(n - 1);
+            }
+        }
+    }
+    if selected.is_empty() {
+        None
+    } else {
+        Some(selected)
+    }
+}
+
+fn location_value(location: &TrashLocation) -> Value {
+    match location {
+        TrashLocation::Home => json!({ "kind": "home" }),
+        TrashLocation::TopDir(topdir) => json!({ "kind": "topdir", "topdir": path_value(topdir) }),
+        TrashLocation::TopDirUser(topdir) => {
+            json!({ "kind": "topdir-user", "topdir": path_value(topdir) })
+        }
+        TrashLocation::Custom(trash_dir) => {
+            json!({ "kind": "custom", "trash_dir": path_value(trash_dir) })
+        }
+    }
+}
+
+fn item_values(items: &[TrashedItem]) -> Value {
+    Value::Array(items.iter().map(item_value).collect())
+}
+
+fn item_value(item: &TrashedItem) -> Value {
+    json!({
+        "name": path_value(item.internal_filename()),
+        "path": path_value(item.original_path()),
+        "uri": item.info().uri().to_string(),
+        "deletion_date": item.info().deletion_date_string(),
+    })
+}
+
+fn format_item(item: &TrashedItem) -> String {
+    format!(
+        "{}  {}",
+        item.info().deletion_date_string().replacen('T', " ", 1),
+        item.original_path().display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::Output;
+    use crate::{
+        cmd_put, directory_refusal, item_value, location_value, parse_max_age, parse_restore_args,
+        parse_selection, parse_size, read_path_list, refuse_reason, rm_flags,
+        unsupported_rm_option, UsageError,
+    };
+    use serde_json::json;
+    use std::ffi::{OsStr, OsString};
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("2", 3), Some(vec![1]));
+        assert_eq!(parse_selection("1, 3", 3), Some(vec![0, 2]));
+        assert_eq!(parse_selection("1-3 2", 3), Some(vec![0, 1, 2]));
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("3-1", 3), None);
+        assert_eq!(parse_selection("x", 3), None);
+    }
+
+    #[test]
+    fn test_refuse_reason() {
+        assert!(refuse_reason(OsStr::new("/")).is_some());
+        assert!(refuse_reason(OsStr::new("//")).is_some());
+        assert!(refuse_reason(OsStr::new(".")).is_some());
+        assert!(refuse_reason(OsStr::new("a/..")).is_some());
+        assert!(refuse_reason(OsStr::new("a/./")).is_some());
+        assert!(refuse_reason(OsStr::new("a/.b")).is_none());
+        assert!(refuse_reason(OsStr::new("dir/")).is_none());
+        assert!(refuse_reason(OsStr::new("..file")).is_none());
+    }
+
+    #[test]
+    fn test_rm_flags() {
+        assert_eq!(rm_flags(OsStr::new("-rf")), Some("rf"));
+        assert_eq!(rm_flags(OsStr::new("-I")), Some("I"));
+        assert_eq!(rm_flags(OsStr::new("-")), None);
+        assert_eq!(rm_flags(OsStr::new("-rx")), None);
+        assert_eq!(rm_flags(OsStr::new("--force")), None);
+        assert_eq!(rm_flags(OsStr::new("f")), None);
+    }
+
+    #[test]
+    fn test_rm_mode_refusals() {
+        let message = |arg: &str| unsupported_rm_option(OsStr::new(arg)).0;
+        assert!(message("--one-file-system").contains("not supported"));
+        assert!(message("--no-preserve-root").contains("not supported"));
+        assert_eq!(message("--shred"), "rm: unrecognized option \"--shred\"");
+        assert_eq!(message("-rx"), "rm: invalid option -- 'x'");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().as_os_str();
+        assert_eq!(
+            directory_refusal(path, false, false),
+            Some("Is a directory")
+        );
+        assert_eq!(directory_refusal(path, false, true), None);
+        assert_eq!(directory_refusal(path, true, false), None);
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        assert_eq!(
+            directory_refusal(path, false, true),
+            Some("Directory not empty")
+        );
+        let file = dir.path().join("file");
+        assert_eq!(directory_refusal(file.as_os_str(), false, false), None);
+    }
+
+    #[test]
+    fn test_put_rejects_unknown_options() {
+        let out = Output { json: false };
+        for arg in &["--bogus", "-x"] {
+            let error = cmd_put(&out, &[arg.into()], false).unwrap_err();
+            let error = error.downcast_ref::<UsageError>().unwrap();
+            assert_eq!(error.0, format!("put: unknown option {:?}", arg));
+        }
+    }
+
+    #[test]
+    fn test_parse_max_age() {
+        assert_eq!(parse_max_age("2").unwrap().as_secs(), 2 * 24 * 60 * 60);
+        assert!(parse_max_age("a week").is_err());
+        let error = parse_max_age(&u64::MAX.to_string()).unwrap_err();
+        assert!(error.0.contains("too large"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("500K"), Some(500 * 1024));
+        assert_eq!(parse_size("1.5G"), Some(3 << 29));
+        assert_eq!(parse_size("10MiB"), Some(10 << 20));
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("G"), None);
+    }
+
+    #[test]
+    fn test_read_path_list() {
+        let listed = read_path_list(&b"a b\n\nc\n"[..], false).unwrap();
+        assert_eq!(listed, ["a b", "c"]);
+        let listed = read_path_list(&b"with\nnewline\0d\0"[..], true).unwrap();
+        assert_eq!(listed, ["with\nnewline", "d"]);
+    }
+
+    #[test]
+    fn test_parse_restore_last() {
+        let parse = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            parse_restore_args(&args)
+        };
+        let names = |names: &[&str]| names.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(parse(&["--last"]).unwrap(), (vec![], Some(1)));
+        assert_eq!(parse(&["--last", "3"]).unwrap(), (vec![], Some(3)));
+        assert_eq!(parse(&["--last=3"]).unwrap(), (vec![], Some(3)));
+        assert_eq!(parse(&["a", "b"]).unwrap(), (names(&["a", "b"]), None));
+        assert_eq!(
+            parse(&["--", "--last"]).unwrap(),
+            (names(&["--last"]), None)
+        );
+
+        // A path after --last is not its count but a NAME, which it cannot go with
+        let error = parse(&["--last", "3rd.txt"]).unwrap_err();
+        assert_eq!(error.0, "restore: --last cannot be given with a NAME");
+        let error = parse(&["--last", "0"]).unwrap_err();
+        assert_eq!(error.0, "restore: --last needs a count of at least 1");
+        assert!(parse(&["--last=x"]).is_err());
+    }
+
+    #[test]
+    fn test_json_values() {
+        let file_dir = tempfile::tempdir().unwrap();
+        let trash_dir = tempfile::tempdir().unwrap();
+        let location = trash::TrashLocation::Custom(trash_dir.path().to_path_buf());
+        let path = file_dir.path().join("a b.txt");
+        std::fs::write(&path, b"x").unwrap();
+        trash::move_to_trash_in(&path, &location).unwrap();
+        let item = trash::list_trash_in(&location).unwrap().remove(0);
+
+        let value = item_value(&item);
+        let mut keys: Vec<_> = value.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["deletion_date", "name", "path", "uri"]);
+        assert_eq!(value["name"], "a b.txt");
+        assert_eq!(value["path"], path.to_str().unwrap());
+        assert_eq!(value["deletion_date"], item.info().deletion_date_string());
+
+        assert_eq!(
+            location_value(&trash::TrashLocation::Home),
+            json!({ "kind": "home" })
+        );
+        assert_eq!(
+            location_value(&trash::TrashLocation::TopDir("/mnt".into())),
+            json!({ "kind": "topdir", "topdir": "/mnt" })
+        );
+        assert_eq!(
+            location_value(&trash::TrashLocation::TopDirUser("/mnt".into())),
+            json!({ "kind": "topdir-user", "topdir": "/mnt" })
+        );
+        assert_eq!(
+            location_value(&location),
+            json!({ "kind": "custom", "trash_dir": trash_dir.path().to_str().unwrap() })
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Prometheus metrics of the daemon, served as text over plain HTTP
+//!
+//! Counted are the requests answered on the control socket, the cleanups
+//! run and the items they purged, next to the size of the trash after the
+//! last cleanup, and histograms of how long cleanups took and how old the
+//! purged items were.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use trash::{PurgeReason, TrashedItem};
+
+/// Operations of the control socket, anything else is counted as `unknown`
+const OPS: [&str; 5] = ["trash", "list", "restore", "empty", "unknown"];
+/// Upper bounds of the cleanup duration buckets, in seconds
+const DURATION_BUCKETS: [f64; 8] = [0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0, 120.0];
+/// Upper bounds of the purged item age buckets, in days
+const AGE_BUCKETS: [f64; 7] = [1.0, 7.0, 30.0, 90.0, 180.0, 365.0, 730.0];
+
+/// Set once the endpoint is up, nothing is measured before
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+struct Histogram<const N: usize> {
+    bounds: [f64; N],
+    /// How many observations fell at or below each bound
+    counts: [u64; N],
+    count: u64,
+    sum: f64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(bounds: [f64; N]) -> Self {
+        Histogram {
+            bounds,
+            counts: [0; N],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+struct Metrics {
+    /// Socket requests per entry of `OPS`, succeeded and failed
+    requests: [[u64; 2]; OPS.len()],
+    cleanups: u64,
+    cleanup_errors: u64,
+    purged_by_age: u64,
+    purged_by_size: u64,
+    trash_bytes: u64,
+    trash_items: u64,
+    cleanup_seconds: Histogram<{ DURATION_BUCKETS.len() }>,
+    purged_age_days: Histogram<{ AGE_BUCKETS.len() }>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            requests: [[0; 2]; OPS.len()],
+            cleanups: 0,
+            cleanup_errors: 0,
+            purged_by_age: 0,
+            purged_by_size: 0,
+            trash_bytes: 0,
+            trash_items: 0,
+            cleanup_seconds: Histogram::new(DURATION_BUCKETS),
+            purged_age_days: Histogram::new(AGE_BUCKETS),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP trash_requests_total Requests answered on the control socket\n");
+        out.push_str("# TYPE trash_requests_total counter\n");
+        for (op, counts) in OPS.iter().zip(self.requests.iter()) {
+            for (result, count) in ["ok", "error"].iter().zip(counts.iter()) {
+                let _ = writeln!(
+                    out,
+                    "trash_requests_total{{op=\"{}\",result=\"{}\"}} {}",
+                    op, result, count
+                );
+            }
+        }
+        let counters = [
+            ("trash_cleanups_total", "Cleanups run", self.cleanups),
+            (
+                "trash_cleanup_errors_total",
+                "Cleanups which failed",
+                self.cleanup_errors,
+            ),
+        ];
+        for (name, help, value) in counters.iter() {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out.push_str("# HELP trash_purged_items_total Items purged by the retention policy\n");
+        out.push_str("# TYPE trash_purged_items_total counter\n");
+        let _ = writeln!(
+            out,
+            "trash_purged_items_total{{reason=\"age\"}} {}",
+            self.purged_by_age
+        );
+        let _ = writeln!(
+            out,
+            "trash_purged_items_total{{reason=\"size\"}} {}",
+            self.purged_by_size
+        );
+        let gauges = [
+            (
+                "trash_size_bytes",
+                "Size of the trash after the last cleanup",
+                self.trash_bytes,
+            ),
+            (
+                "trash_items",
+                "Items in the trash after the last cleanup",
+                self.trash_items,
+            ),
+        ];
+        for (name, help, value) in gauges.iter() {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        self.cleanup_seconds.render(
+            &mut out,
+            "trash_cleanup_duration_seconds",
+            "Time taken by each cleanup",
+        );
+        self.purged_age_days.render(
+            &mut out,
+            "trash_purged_item_age_days",
+            "How long purged items had been in the trash",
+        );
+        out
+    }
+}
+
+fn with_metrics<F: FnOnce(&mut Metrics)>(update: F) {
+    if ENABLED.load(Ordering::SeqCst) {
+        update(&mut METRICS.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+/// Whether the endpoint is up, so measurements are wanted
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Counts a request of the control socket
+pub fn record_request(op: Option<&str>, ok: bool) {
+    let index = OPS
+        .iter()
+        .position(|known| Some(*known) == op)
+        .unwrap_or(OPS.len() - 1);
+    with_metrics(|metrics| metrics.requests[index][!ok as usize] += 1);
+}
+
+/// Counts an item purged during a cleanup
+pub fn record_purge(item: &TrashedItem, reason: PurgeReason) {
+    with_metrics(|metrics| {
+        match reason {
+            PurgeReason::Age => metrics.purged_by_age += 1,
+            PurgeReason::Size => metrics.purged_by_size += 1,
+        }
+        let days = item.age().as_secs_f64() / (24.0 * 60.0 * 60.0);
+        metrics.purged_age_days.observe(days);
+    });
+}
+
+/// Counts a finished cleanup which took `elapsed`
+pub fn record_cleanup(elapsed: Duration, ok: bool) {
+    with_metrics(|metrics| {
+        metrics.cleanups += 1;
+        if !ok {
+            metrics.cleanup_errors += 1;
+        }
+        metrics.cleanup_seconds.observe(elapsed.as_secs_f64());
+    });
+}
+
+/// Sets what is left in the trash, given its items
+pub fn record_trash(items: &[TrashedItem]) {
+    // Measured outside of the lock, sizes may take a while
+    let bytes = items
+        .iter()
+        .map(|item| {
+            item.size()
+                .ok()
+                .or_else(|| item.recorded_size())
+                .unwrap_or(0)
+        })
+        .sum();
+    with_metrics(|metrics| {
+        metrics.trash_bytes = bytes;
+        metrics.trash_items = items.len() as u64;
+    });
+}
+
+/// Starts serving the metrics on `addr` from a background thread
+/// Any path answers, as `/metrics` is all there is
+pub fn serve<A: ToSocketAddrs>(addr: A) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    ENABLED.store(true, Ordering::SeqCst);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(answer);
+            if let Err(e) = result {
+                eprintln!("trash: metrics: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn answer(stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are of no interest, but are read so the client sees
+    // its request taken whole
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let mut stream = reader.into_inner();
+
+    let (status, body) = if request_line.starts_with("GET ") {
+        let body = METRICS.lock().unwrap_or_else(|e| e.into_inner()).render();
+        ("200 OK", body)
+    } else {
+        ("405 Method Not Allowed", String::new())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Histogram, Metrics};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::new([1.0, 10.0]);
+        for value in &[0.5, 1.0, 5.0, 50.0] {
+            histogram.observe(*value);
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "h", "test");
+        assert!(out.contains("h_bucket{le=\"1\"} 2\n"));
+        assert!(out.contains("h_bucket{le=\"10\"} 3\n"));
+        assert!(out.contains("h_bucket{le=\"+Inf\"} 4\n"));
+        assert!(out.contains("h_sum 56.5\n"));
+        assert!(out.contains("h_count 4\n"));
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        super::serve(addr).unwrap();
+        super::record_request(Some("list"), true);
+        super::record_request(Some("bogus"), false);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("trash_requests_total{op=\"list\",result=\"ok\"} 1\n"));
+        assert!(response.contains("trash_requests_total{op=\"unknown\",result=\"error\"} 1\n"));
+        assert!(response.contains("trash_cleanups_total 0\n"));
+        assert!(Metrics::new().render().contains("trash_size_bytes 0\n"));
+    }
+}
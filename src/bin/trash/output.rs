@@ -0,0 +1,54 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// How results and errors are reported to the user
+pub struct Output {
+    /// Emit a single JSON document on stdout instead of human readable text
+    pub json: bool,
+}
+
+impl Output {
+    /// Prints the outcome of a command: `value` in JSON mode,
+    /// otherwise whatever `human` prints
+    pub fn result<F: FnOnce()>(&self, value: Value, human: F) {
+        if self.json {
+            println!("{}", value);
+        } else {
+            human();
+        }
+    }
+
+    /// Reports an error which ended the command
+    pub fn error(&self, e: &dyn std::error::Error) {
+        if self.json {
+            println!("{}", error_value(e));
+        } else {
+            eprintln!("trash: {}", e);
+        }
+    }
+}
+
+/// The document an error is reported as in JSON mode
+fn error_value(e: &dyn std::error::Error) -> Value {
+    json!({ "error": { "message": e.to_string() } })
+}
+
+/// Paths are written as (lossily converted) UTF-8 strings
+pub fn path_value<P: AsRef<Path>>(path: P) -> Value {
+    Value::String(path.as_ref().to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error_value;
+    use serde_json::json;
+
+    #[test]
+    fn test_error_value() {
+        let e = std::io::Error::other("no space left");
+        assert_eq!(
+            error_value(&e),
+            json!({ "error": { "message": "no space left" } })
+        );
+    }
+}
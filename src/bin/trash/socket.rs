@@ -0,0 +1,214 @@
+//! Control protocol spoken over a unix socket while running as a daemon
+//!
+//! Every message in either direction is a 4 byte big endian length
+//! followed by that many bytes of UTF-8 JSON. Requests are objects
+//! with an `op` of `trash` (with `path`), `list`, `restore` (with
+//! `name`) or `empty`. Each request gets one response, either
+//! `{"ok": true, "result": ...}` or `{"ok": false, "error": "..."}`.
+
+use crate::item_values;
+use crate::metrics;
+use crate::output::path_value;
+use serde_json::{json, Value};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Larger requests are refused rather than buffered
+const MAX_FRAME_LEN: u32 = 1 << 20;
+
+/// Starts answering requests on a socket at `path` from a background thread
+/// `lock` is held while a request runs so it never overlaps a cleanup
+pub fn serve(path: &Path, lock: Arc<Mutex<()>>) -> io::Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is in use by another daemon", path.display()),
+            ));
+        }
+        // Left behind by a daemon which did not exit cleanly
+        std::fs::remove_file(path)?;
+    }
+    let listener = bind_private(path)?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                // A thread each, so a client which idles does not keep
+                // the others waiting
+                Ok(stream) => {
+                    let lock = Arc::clone(&lock);
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, &lock) {
+                            eprintln!("trash: socket client: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("trash: socket: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Binds a socket at `path` which only its owner can connect to
+/// It is bound inside a directory of its own, which no one else can
+/// enter, and moved to `path` once restricted, so that there is no
+/// moment where others could connect to it
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket path", path.display()),
+        )
+    })?;
+    let mut private_name = OsString::from(".");
+    private_name.push(name);
+    private_name.push(format!(".{}", std::process::id()));
+    let private_dir = path.with_file_name(private_name);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)?;
+    let bound = private_dir.join(name);
+    let listener = UnixListener::bind(&bound).and_then(|listener| {
+        std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&bound);
+    std::fs::remove_dir(&private_dir)?;
+    listener
+}
+
+fn handle_client(mut stream: UnixStream, lock: &Mutex<()>) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        let response = match serde_json::from_slice::<Value>(&frame) {
+            Ok(request) => {
+                let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                let result = dispatch(&request);
+                metrics::record_request(request["op"].as_str(), result.is_ok());
+                match result {
+                    Ok(result) => json!({ "ok": true, "result": result }),
+                    Err(e) => json!({ "ok": false, "error": e }),
+                }
+            }
+            Err(e) => json!({ "ok": false, "error": format!("invalid JSON: {}", e) }),
+        };
+        write_frame(&mut stream, response.to_string().as_bytes())?;
+    }
+    Ok(())
+}
+
+fn dispatch(request: &Value) -> Result<Value, String> {
+    let string_field = |name: &str| {
+        request[name]
+            .as_str()
+            .ok_or_else(|| format!("missing string field {:?}", name))
+    };
+
+    match request["op"].as_str() {
+        Some("trash") => {
+            let path = PathBuf::from(string_field("path")?);
+            if let Err(e) = path.symlink_metadata() {
+                return Err(e.to_string());
+            }
+            let files = trash::move_to_trash(&path).map_err(|e| e.to_string())?;
+            Ok(json!({
+                "trash_file": path_value(&files.trash_file),
+                "info_file": path_value(&files.info_file),
+            }))
+        }
+        Some("list") => trash::list_trash()
+            .map(|items| item_values(&items))
+            .map_err(|e| e.to_string()),
+        Some("restore") => {
+            let name = string_field("name")?;
+            trash::restore_from_trash(OsStr::new(name))
+                .map(|path| json!({ "path": path_value(path) }))
+                .map_err(|e| e.to_string())
+        }
+        Some("empty") => trash::empty_trash()
+            .map(|items| item_values(&items))
+            .map_err(|e| e.to_string()),
+        Some(op) => Err(format!("unknown op {:?}", op)),
+        None => Err("missing string field \"op\"".to_owned()),
+    }
+}
+
+/// Reads one message, or `None` when the peer has hung up
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes is too large", len),
+        ));
+    }
+    let mut frame = vec![0u8; len as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, read_frame, serve, write_frame};
+    use serde_json::{json, Value};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_frame_round_trip() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        write_frame(&mut a, b"{\"op\":\"list\"}").unwrap();
+        write_frame(&mut a, b"").unwrap();
+        drop(a);
+        assert_eq!(
+            read_frame(&mut b).unwrap().unwrap(),
+            b"{\"op\":\"list\"}".to_vec()
+        );
+        assert_eq!(read_frame(&mut b).unwrap().unwrap(), Vec::<u8>::new());
+        assert!(read_frame(&mut b).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_serve_private_and_concurrent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trash.sock");
+        serve(&path, Arc::new(Mutex::new(()))).unwrap();
+        let mode = path.symlink_metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Connected and silent, which must not hold up the next client
+        let _idle = UnixStream::connect(&path).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        write_frame(&mut client, b"{\"op\":\"explode\"}").unwrap();
+        let response = read_frame(&mut client).unwrap().unwrap();
+        let response: Value = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response["ok"], false);
+    }
+
+    #[test]
+    fn test_dispatch_rejects_bad_requests() {
+        assert!(dispatch(&json!({ "op": "explode" })).is_err());
+        assert!(dispatch(&json!({ "op": "restore" })).is_err());
+        assert!(dispatch(&json!({})).is_err());
+    }
+}
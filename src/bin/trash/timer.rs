@@ -0,0 +1,137 @@
+//! User level systemd units running `trash autoclean` periodically
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name shared by the service and the timer
+pub const UNIT_NAME: &str = "trash-autoclean";
+
+/// Where systemd looks for the current user's own units
+pub fn user_unit_dir() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("systemd").join("user"))
+}
+
+/// Writes a service running `exe autoclean args` and a timer starting it
+/// every `interval`, replacing earlier ones
+/// Returns the paths of the units written
+pub fn install(
+    unit_dir: &Path,
+    exe: &Path,
+    args: &[String],
+    interval: Duration,
+) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(unit_dir)?;
+    let service = unit_dir.join(format!("{}.service", UNIT_NAME));
+    let timer = unit_dir.join(format!("{}.timer", UNIT_NAME));
+    std::fs::write(&service, service_unit(exe, args))?;
+    std::fs::write(&timer, timer_unit(interval))?;
+    Ok(vec![service, timer])
+}
+
+/// Reloads the user's systemd and starts the timer, now and at each login
+pub fn enable() -> io::Result<()> {
+    let timer = format!("{}.timer", UNIT_NAME);
+    for args in &[
+        &["--user", "daemon-reload"][..],
+        &["--user", "enable", "--now", &timer][..],
+    ] {
+        let status = std::process::Command::new("systemctl")
+            .args(args.iter())
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "systemctl {} failed with {}",
+                args.join(" "),
+                status
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn service_unit(exe: &Path, args: &[String]) -> String {
+    let mut command = quote(&exe.to_string_lossy());
+    command.push_str(" autoclean");
+    for arg in args {
+        command.push(' ');
+        command.push_str(&quote(arg));
+    }
+    format!(
+        "[Unit]\n\
+         Description=Purge old items from the trash\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        command
+    )
+}
+
+fn timer_unit(interval: Duration) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Purge old items from the trash periodically\n\
+         \n\
+         [Timer]\n\
+         OnStartupSec=15min\n\
+         OnUnitActiveSec={}s\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        interval.as_secs()
+    )
+}
+
+/// Quotes a word of a systemd command line, where `%` starts a specifier
+/// and `$` a variable
+fn quote(word: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in word.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            '$' => quoted.push_str("$$"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{install, UNIT_NAME};
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_install() {
+        let unit_dir = tempfile::tempdir().expect("temp dir creation failed");
+        let args = ["--max-age=30".to_owned(), "--max-size=10%".to_owned()];
+        let units = install(
+            unit_dir.path(),
+            Path::new("/opt/my bin/trash"),
+            &args,
+            Duration::from_secs(3600),
+        )
+        .unwrap();
+        assert_eq!(units.len(), 2);
+
+        let service = std::fs::read_to_string(&units[0]).unwrap();
+        assert!(units[0].ends_with(format!("{}.service", UNIT_NAME)));
+        assert!(service.contains(
+            "ExecStart=\"/opt/my bin/trash\" autoclean \"--max-age=30\" \"--max-size=10%%\"\n"
+        ));
+        let timer = std::fs::read_to_string(&units[1]).unwrap();
+        assert!(timer.contains("OnUnitActiveSec=3600s\n"));
+        assert!(timer.contains("WantedBy=timers.target\n"));
+    }
+}
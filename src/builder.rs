@@ -0,0 +1,185 @@
+use crate::options::is_valid_metadata;
+use crate::platform::OsStrExt;
+use crate::{date, DeletionDate, TrashInfo};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// How far ahead of the clock a deletion date may be, allowing for
+/// clocks of machines sharing a trash being a little apart
+const FUTURE_SLACK: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Makes a `TrashInfo` from `TrashInfo::builder`, checking what it is
+/// given
+///
+/// ```
+/// let info = trash::TrashInfo::builder()
+///     .path("/home/me/report.pdf")
+///     .metadata("X-Reason", "superseded")
+///     .build()?;
+/// assert_eq!(info.internal_filename, "report.pdf");
+/// # Ok::<(), trash::InvalidTrashInfo>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrashInfoBuilder {
+    internal_filename: Option<OsString>,
+    path: Option<OsString>,
+    deletion_date: Option<DeletionDate>,
+    extra: Vec<(String, String)>,
+}
+
+/// Why `TrashInfoBuilder::build` refused to make a `TrashInfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidTrashInfo {
+    /// No path was given, or an empty one
+    EmptyPath,
+    /// The path holds a NUL byte, which no file name can
+    NulInPath,
+    /// The name in the trash is empty, `.`, `..`, or holds a `/` or a NUL
+    /// byte, so it is not the name of a single file
+    InvalidName(OsString),
+    /// The deletion date is more than a day ahead of the clock
+    FutureDate(DeletionDate),
+    /// An extra key which is not an `X-` key, or a value spanning lines
+    InvalidKey(String),
+}
+
+impl std::fmt::Display for InvalidTrashInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InvalidTrashInfo::EmptyPath => write!(f, "no path given"),
+            InvalidTrashInfo::NulInPath => write!(f, "path contains a NUL byte"),
+            InvalidTrashInfo::InvalidName(name) => {
+                write!(f, "{:?} is not a valid name in the trash", name)
+            }
+            InvalidTrashInfo::FutureDate(deletion_date) => write!(
+                f,
+                "deletion date {} is in the future",
+                date::format(deletion_date)
+            ),
+            InvalidTrashInfo::InvalidKey(key) => {
+                write!(f, "{:?} cannot be written to an info file", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidTrashInfo {}
+
+impl TrashInfoBuilder {
+    /// The path the item was trashed from, absolute or relative to the
+    /// top directory of its device
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().as_os_str().to_os_string());
+        self
+    }
+
+    /// The name in the trash, the file name of the path if not given
+    pub fn internal_filename<S: AsRef<OsStr>>(mut self, name: S) -> Self {
+        self.internal_filename = Some(name.as_ref().to_os_string());
+        self
+    }
+
+    /// When the item was trashed, now if not given
+    pub fn deletion_date(mut self, deletion_date: DeletionDate) -> Self {
+        self.deletion_date = Some(deletion_date);
+        self
+    }
+
+    /// Adds an `X-` key, as `TrashOptions::metadata` does
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.extra.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn build(self) -> Result<TrashInfo, InvalidTrashInfo> {
+        let path = match self.path {
+            Some(path) if !path.is_empty() => path,
+            _ => return Err(InvalidTrashInfo::EmptyPath),
+        };
+        if path.as_bytes().contains(&0) {
+            return Err(InvalidTrashInfo::NulInPath);
+        }
+        let internal_filename = match self.internal_filename {
+            Some(name) => name,
+            None => Path::new(&path)
+                .file_name()
+                .unwrap_or_default()
+                .to_os_string(),
+        };
+        let bytes = internal_filename.as_bytes();
+        if bytes.is_empty()
+            || bytes == b"."
+            || bytes == b".."
+            || bytes.contains(&b'/')
+            || bytes.contains(&0)
+        {
+            return Err(InvalidTrashInfo::InvalidName(internal_filename));
+        }
+        let deletion_date = match self.deletion_date {
+            Some(deletion_date)
+                if date::saturating_sub(deletion_date, FUTURE_SLACK) > date::now() =>
+            {
+                return Err(InvalidTrashInfo::FutureDate(deletion_date))
+            }
+            Some(deletion_date) => deletion_date,
+            None => date::now(),
+        };
+        if let Some((key, _)) = self
+            .extra
+            .iter()
+            .find(|(key, value)| !is_valid_metadata(key, value))
+        {
+            return Err(InvalidTrashInfo::InvalidKey(key.clone()));
+        }
+        Ok(TrashInfo {
+            internal_filename,
+            path,
+            deletion_date,
+            extra: self.extra,
+            malformed_path: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{date, InvalidTrashInfo, TrashInfo};
+
+    #[test]
+    fn test_builder_validates() {
+        let info = TrashInfo::builder()
+            .path("/home/me/a")
+            .metadata("X-Tool", "test")
+            .build()
+            .unwrap();
+        assert_eq!(info.internal_filename, "a");
+        assert_eq!(info.metadata("X-Tool"), Some("test"));
+
+        let invalid = |builder: crate::TrashInfoBuilder| builder.build().unwrap_err();
+        assert_eq!(invalid(TrashInfo::builder()), InvalidTrashInfo::EmptyPath);
+        assert_eq!(
+            invalid(TrashInfo::builder().path("a\0b")),
+            InvalidTrashInfo::NulInPath
+        );
+        assert_eq!(
+            invalid(TrashInfo::builder().path("/")),
+            InvalidTrashInfo::InvalidName("".into())
+        );
+        assert_eq!(
+            invalid(TrashInfo::builder().path("/a").internal_filename("../a")),
+            InvalidTrashInfo::InvalidName("../a".into())
+        );
+        assert_eq!(
+            invalid(TrashInfo::builder().path("/a").metadata("Path", "/b")),
+            InvalidTrashInfo::InvalidKey("Path".into())
+        );
+
+        let now = TrashInfo::builder().path("/a").deletion_date(date::now());
+        assert!(now.build().is_ok());
+        let far = date::parse("3000-01-01T00:00:00").unwrap();
+        assert_eq!(
+            invalid(TrashInfo::builder().path("/a").deletion_date(far)),
+            InvalidTrashInfo::FutureDate(far)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{
+    move_to_trash_with, RestoreStrategy, TrashError, TrashFiles, TrashOptions, TrashedItem,
+};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Callbacks made for each item of a batch as soon as it is done, so
+/// that results can be shown as they come rather than once all are done
+///
+/// Each is given the path of the item: the path being trashed, or the
+/// original path of an item in the trash. An item is skipped when it is
+/// already gone, such as a path to trash which does not exist or an item
+/// someone else restored meanwhile; its result is still an error.
+/// With several workers the callbacks are made from their threads.
+///
+/// ```no_run
+/// use trash::{move_items_to_trash, BatchEvents, TrashOptions};
+///
+/// let events = BatchEvents::new()
+///     .on_success(|path| println!("trashed {}", path.display()))
+///     .on_error(|path, e| eprintln!("{}: {}", path.display(), e));
+/// move_items_to_trash(&["a.txt", "b.txt"], &TrashOptions::new(), &events);
+/// ```
+#[derive(Default)]
+pub struct BatchEvents<'a> {
+    on_success: Option<PathCallback<'a>>,
+    on_skip: Option<PathCallback<'a>>,
+    on_error: Option<ErrorCallback<'a>>,
+}
+
+type PathCallback<'a> = Box<dyn Fn(&Path) + Sync + 'a>;
+type ErrorCallback<'a> = Box<dyn Fn(&Path, &TrashError) + Sync + 'a>;
+
+impl std::fmt::Debug for BatchEvents<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BatchEvents")
+            .field("on_success", &self.on_success.is_some())
+            .field("on_skip", &self.on_skip.is_some())
+            .field("on_error", &self.on_error.is_some())
+            .finish()
+    }
+}
+
+impl<'a> BatchEvents<'a> {
+    /// No callbacks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called for each item done
+    pub fn on_success<F: Fn(&Path) + Sync + 'a>(mut self, callback: F) -> Self {
+        self.on_success = Some(Box::new(callback));
+        self
+    }
+
+    /// Called for each item which was already gone
+    pub fn on_skip<F: Fn(&Path) + Sync + 'a>(mut self, callback: F) -> Self {
+        self.on_skip = Some(Box::new(callback));
+        self
+    }
+
+    /// Called for each item which failed
+    pub fn on_error<F: Fn(&Path, &TrashError) + Sync + 'a>(mut self, callback: F) -> Self {
+        self.on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Reports the result for one item, or that it was skipped if `gone`
+    fn report<T>(&self, path: &Path, result: &Result<T, TrashError>, gone: bool) {
+        match result {
+            Ok(_) => {
+                if let Some(callback) = &self.on_success {
+                    callback(path);
+                }
+            }
+            Err(_) if gone => {
+                if let Some(callback) = &self.on_skip {
+                    callback(path);
+                }
+            }
+            Err(e) => {
+                if let Some(callback) = &self.on_error {
+                    callback(path, e);
+                }
+            }
+        }
+    }
+}
+
+/// Trashes many files or directories one after the other, as
+/// configured by `options`
+/// Every path is attempted and the results come back in the order the
+/// paths were given
+pub fn move_items_to_trash<P: AsRef<Path>>(
+    paths: &[P],
+    options: &TrashOptions,
+    events: &BatchEvents,
+) -> Vec<Result<TrashFiles, TrashError>> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let result = move_to_trash_with(path, options);
+            let gone = matches!(result, Err(TrashError::SourceNotFound(_)));
+            events.report(path, &result, gone);
+            result
+        })
+        .collect()
+}
+
+/// Permanently deletes many items one after the other
+/// Every item is attempted and the results come back in the order the
+/// items were given
+pub fn purge_items(items: Vec<TrashedItem>, events: &BatchEvents) -> Vec<Result<(), TrashError>> {
+    items
+        .into_iter()
+        .map(|item| {
+            let original_path = item.original_path().to_path_buf();
+            let (result, gone) = match gone_from_trash(&item) {
+                Some(e) => (Err(e), true),
+                None => (item.purge(), false),
+            };
+            events.report(&original_path, &result, gone);
+            result
+        })
+        .collect()
+}
+
+/// `ItemNotFound` if the item was taken out of the trash meanwhile
+fn gone_from_trash(item: &TrashedItem) -> Option<TrashError> {
+    match item.info_file().symlink_metadata() {
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Some(TrashError::ItemNotFound(
+            item.internal_filename().to_os_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// What became of one item of `restore_items`
+#[derive(Debug)]
+pub struct RestoreOutcome {
+    /// Internal filename of the item in its trash
+    pub internal_filename: OsString,
+    /// Where the item was deleted from
+    pub original_path: PathBuf,
+    /// Where the item was restored to, or why it was not
+    pub result: Result<PathBuf, TrashError>,
+}
+
+/// Restores many items at once, with up to `workers` restores in flight,
+/// which makes up for the latency of spinning disks and network mounts
+///
+/// Every item is attempted and the outcomes come back in the order the
+/// items were given. Items which would be restored to the same place,
+/// including the `name.N` names `RestoreStrategy::Rename` picks, are
+/// restored one after the other in the order given.
+pub fn restore_items(
+    items: Vec<TrashedItem>,
+    strategy: RestoreStrategy,
+    workers: usize,
+) -> Vec<RestoreOutcome> {
+    restore_items_with(items, strategy, workers, &BatchEvents::default())
+}
+
+/// Like `restore_items`, reporting each item to `events` as it is done
+pub fn restore_items_with(
+    items: Vec<TrashedItem>,
+    strategy: RestoreStrategy,
+    workers: usize,
+    events: &BatchEvents,
+) -> Vec<RestoreOutcome> {
+    let count = items.len();
+    let groups = group_by_destination(items);
+    let next = AtomicUsize::new(0);
+    let workers = workers.clamp(1, groups.len().max(1));
+
+    let mut done: Vec<(usize, RestoreOutcome)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    while let Some(group) = groups.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        for (index, item) in group {
+                            let outcome = restore_one(item.clone(), strategy, events);
+                            done.push((*index, outcome));
+                        }
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("restore worker panicked"))
+            .collect()
+    });
+    debug_assert_eq!(done.len(), count);
+    done.sort_by_key(|&(index, _)| index);
+    done.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn restore_one(
+    item: TrashedItem,
+    strategy: RestoreStrategy,
+    events: &BatchEvents,
+) -> RestoreOutcome {
+    let internal_filename = item.internal_filename().to_os_string();
+    let original_path = item.original_path().to_path_buf();
+    let (result, gone) = match gone_from_trash(&item) {
+        Some(e) => (Err(e), true),
+        None => (item.restore(strategy), false),
+    };
+    events.report(&original_path, &result, gone);
+    RestoreOutcome {
+        internal_filename,
+        original_path,
+        result,
+    }
+}
+
+/// Items along with their position, grouped so that those which could
+/// end up at the same path are in one group, kept in order
+fn group_by_destination(items: Vec<TrashedItem>) -> Vec<Vec<(usize, TrashedItem)>> {
+    let mut groups: Vec<Vec<(usize, TrashedItem)>> = Vec::new();
+    let mut group_of: HashMap<PathBuf, usize> = HashMap::new();
+    for (index, item) in items.into_iter().enumerate() {
+        let key = without_duplicate_suffix(item.original_path());
+        let group = *group_of.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push((index, item));
+    }
+    groups
+}
+
+/// `name.3` as `name`, as restoring `name` may be renamed to it
+fn without_duplicate_suffix(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match name.rsplit_once('.') {
+        Some((base, suffix))
+            if !base.is_empty()
+                && !suffix.is_empty()
+                && suffix.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            path.with_file_name(base)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        list_trash_in, move_items_to_trash, move_to_trash_in, purge_items, restore_items,
+        restore_items_with, BatchEvents, RestoreStrategy, TrashOptions,
+    };
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_restore_items() {
+        let (file_dir, _trash_dir, location) = fixture();
+        for i in 0..20 {
+            let path = file_dir.path().join(format!("file{}", i));
+            std::fs::write(&path, i.to_string()).unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+        // Two versions of one path, the second of which is renamed
+        let twice = file_dir.path().join("twice");
+        for content in &["first", "second"] {
+            std::fs::write(&twice, content).unwrap();
+            move_to_trash_in(&twice, &location).unwrap();
+        }
+        std::fs::write(file_dir.path().join("file0"), b"in the way").unwrap();
+
+        let items = list_trash_in(&location).unwrap();
+        let names: Vec<_> = items
+            .iter()
+            .map(|item| item.internal_filename().to_os_string())
+            .collect();
+        let outcomes = restore_items(items, RestoreStrategy::Rename, 4);
+        assert_eq!(outcomes.len(), 22);
+        for (outcome, name) in outcomes.iter().zip(&names) {
+            assert_eq!(&outcome.internal_filename, name);
+            let restored = outcome.result.as_ref().unwrap();
+            assert!(restored.exists());
+        }
+        assert!(list_trash_in(&location).unwrap().is_empty());
+        assert!(file_dir.path().join("file0.2").exists());
+        let mut versions = vec![
+            std::fs::read_to_string(&twice).unwrap(),
+            std::fs::read_to_string(file_dir.path().join("twice.2")).unwrap(),
+        ];
+        versions.sort();
+        assert_eq!(versions, ["first", "second"]);
+    }
+
+    #[test]
+    fn test_batch_events() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let options = TrashOptions::new().location(location.clone());
+        let a = file_dir.path().join("a");
+        let b = file_dir.path().join("b");
+        let missing = file_dir.path().join("missing");
+        std::fs::write(&a, b"a").unwrap();
+        std::fs::write(&b, b"b").unwrap();
+
+        let succeeded = Mutex::new(Vec::<PathBuf>::new());
+        let skipped = Mutex::new(Vec::<PathBuf>::new());
+        let failed = Mutex::new(Vec::<PathBuf>::new());
+        let events = BatchEvents::new()
+            .on_success(|path| succeeded.lock().unwrap().push(path.to_path_buf()))
+            .on_skip(|path| skipped.lock().unwrap().push(path.to_path_buf()))
+            .on_error(|path, _| failed.lock().unwrap().push(path.to_path_buf()));
+        let results = move_items_to_trash(&[&a, &missing, &b], &options, &events);
+        assert_eq!(results.len(), 3);
+        assert!(results[1].is_err());
+        assert_eq!(*succeeded.lock().unwrap(), [a.clone(), b.clone()]);
+        assert_eq!(*skipped.lock().unwrap(), [missing]);
+
+        // One item taken out of the trash by someone else meanwhile
+        let items = list_trash_in(&location).unwrap();
+        let gone = items[0].original_path().to_path_buf();
+        items[0].clone().purge().unwrap();
+        succeeded.lock().unwrap().clear();
+        skipped.lock().unwrap().clear();
+        let outcomes = restore_items_with(items, RestoreStrategy::FailOnConflict, 2, &events);
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(succeeded.lock().unwrap().len(), 1);
+        assert_eq!(*skipped.lock().unwrap(), [gone]);
+        assert!(failed.lock().unwrap().is_empty());
+
+        move_to_trash_in(&a, &location).unwrap();
+        succeeded.lock().unwrap().clear();
+        let results = purge_items(list_trash_in(&location).unwrap(), &events);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(*succeeded.lock().unwrap(), [a]);
+    }
+}
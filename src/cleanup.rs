@@ -0,0 +1,346 @@
+use crate::{list_trash_in, purge_all, TrashError, TrashLocation, TrashSummary, TrashedItem};
+use glob::{MatchOptions, Pattern};
+use std::path::PathBuf;
+
+/// What to do with an item while emptying the trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyDecision {
+    /// Leave the item in the trash
+    Keep,
+    /// Permanently delete the item
+    Delete,
+    /// Stop emptying, keeping this and all remaining items
+    Abort,
+}
+
+/// What a purge would permanently delete, worked out without deleting
+/// anything, for the user to confirm before going ahead
+#[derive(Debug)]
+pub struct PurgeReport {
+    /// All of the items together
+    pub summary: TrashSummary,
+    /// The items of each trash, and so of each device, in the order
+    /// their trash first comes up in `items`
+    pub trashes: Vec<TrashBreakdown>,
+    /// The items themselves, which can be handed to `TrashedItem::purge`
+    pub items: Vec<TrashedItem>,
+}
+
+/// The part of a `PurgeReport` in one trash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashBreakdown {
+    /// Which trash
+    pub location: TrashLocation,
+    /// Root of the trash
+    pub trash_dir: PathBuf,
+    /// Its items together
+    pub summary: TrashSummary,
+}
+
+impl PurgeReport {
+    /// Reports on purging any selection of items, such as those
+    /// from `list_all_trash`
+    pub fn new(items: Vec<TrashedItem>) -> Result<Self, TrashError> {
+        let mut trashes: Vec<(TrashLocation, PathBuf, Vec<TrashedItem>)> = Vec::new();
+        for item in &items {
+            match trashes
+                .iter_mut()
+                .find(|(_, trash_dir, _)| trash_dir == item.trash_dir())
+            {
+                Some((_, _, in_trash)) => in_trash.push(item.clone()),
+                None => trashes.push((
+                    item.location().clone(),
+                    item.trash_dir().to_path_buf(),
+                    vec![item.clone()],
+                )),
+            }
+        }
+        let trashes = trashes
+            .into_iter()
+            .map(|(location, trash_dir, in_trash)| {
+                Ok(TrashBreakdown {
+                    location,
+                    trash_dir,
+                    summary: TrashSummary::of(&in_trash)?,
+                })
+            })
+            .collect::<Result<_, TrashError>>()?;
+        Ok(Self {
+            summary: TrashSummary::of(&items)?,
+            trashes,
+            items,
+        })
+    }
+}
+
+/// What `empty_trash` would delete
+pub fn preview_empty_trash() -> Result<PurgeReport, TrashError> {
+    preview_empty_trash_in(&TrashLocation::Home)
+}
+
+/// What `empty_trash_in` would delete
+pub fn preview_empty_trash_in(location: &TrashLocation) -> Result<PurgeReport, TrashError> {
+    PurgeReport::new(list_trash_in(location)?)
+}
+
+/// What `purge_matching_in` would delete
+pub fn preview_purge_matching_in(
+    pattern: &str,
+    location: &TrashLocation,
+) -> Result<PurgeReport, TrashError> {
+    PurgeReport::new(matching_in(pattern, location)?)
+}
+
+/// What `purge_missing_origins_in` would delete
+pub fn preview_purge_missing_origins_in(
+    location: &TrashLocation,
+) -> Result<PurgeReport, TrashError> {
+    PurgeReport::new(missing_origins_in(location)?)
+}
+
+/// Empties the home trash, asking `decide` about each item first,
+/// most recently deleted first
+/// Returns the items which were deleted, including those deleted
+/// before an abort
+pub fn empty_trash_with<F>(decide: F) -> Result<Vec<TrashedItem>, TrashError>
+where
+    F: FnMut(&TrashedItem) -> EmptyDecision,
+{
+    empty_trash_in_with(&TrashLocation::Home, decide)
+}
+
+/// Empties the given trash, asking `decide` about each item first
+pub fn empty_trash_in_with<F>(
+    location: &TrashLocation,
+    mut decide: F,
+) -> Result<Vec<TrashedItem>, TrashError>
+where
+    F: FnMut(&TrashedItem) -> EmptyDecision,
+{
+    let mut purged = Vec::new();
+    for item in list_trash_in(location)? {
+        match decide(&item) {
+            EmptyDecision::Keep => {}
+            EmptyDecision::Delete => {
+                item.clone().purge()?;
+                purged.push(item);
+            }
+            EmptyDecision::Abort => break,
+        }
+    }
+    Ok(purged)
+}
+
+/// Permanently deletes the items in the home trash whose original path
+/// matches a glob pattern such as `**/*.iso`
+/// Returns the items which were deleted
+pub fn purge_matching(pattern: &str) -> Result<Vec<TrashedItem>, TrashError> {
+    purge_matching_in(pattern, &TrashLocation::Home)
+}
+
+/// Permanently deletes the items in the given trash whose original path
+/// matches a glob pattern
+/// `*` and `?` stay within a path component, while `**` spans any number
+/// of them; patterns without a leading `/` may match at any depth
+/// Returns the items which were deleted
+pub fn purge_matching_in(
+    pattern: &str,
+    location: &TrashLocation,
+) -> Result<Vec<TrashedItem>, TrashError> {
+    purge_all(matching_in(pattern, location)?)
+}
+
+/// The items in a trash whose original path matches a glob pattern
+fn matching_in(pattern: &str, location: &TrashLocation) -> Result<Vec<TrashedItem>, TrashError> {
+    let pattern = if pattern.starts_with('/') || pattern.starts_with("**") {
+        Pattern::new(pattern)?
+    } else {
+        Pattern::new(&format!("**/{}", pattern))?
+    };
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    Ok(list_trash_in(location)?
+        .into_iter()
+        .filter(|item| pattern.matches_path_with(item.original_path(), options))
+        .collect())
+}
+
+/// Permanently deletes the items in the home trash which were deleted
+/// from a directory that no longer exists, as they cannot be restored
+/// in place
+/// Returns the items which were deleted
+pub fn purge_missing_origins() -> Result<Vec<TrashedItem>, TrashError> {
+    purge_missing_origins_in(&TrashLocation::Home)
+}
+
+/// Permanently deletes the items in the given trash which were deleted
+/// from a directory that no longer exists
+/// Returns the items which were deleted
+pub fn purge_missing_origins_in(location: &TrashLocation) -> Result<Vec<TrashedItem>, TrashError> {
+    purge_all(missing_origins_in(location)?)
+}
+
+/// The items in a trash deleted from a directory that no longer exists
+/// A directory which cannot be looked at, such as for want of permission
+/// or on a mount which is not answering, is an error rather than gone
+fn missing_origins_in(location: &TrashLocation) -> Result<Vec<TrashedItem>, TrashError> {
+    let mut missing = Vec::new();
+    for item in list_trash_in(location)? {
+        if item.origin_missing()? {
+            missing.push(item);
+        }
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        empty_trash_in_with, list_trash_in, move_to_trash_in, preview_empty_trash_in,
+        preview_purge_matching_in, purge_matching_in, purge_missing_origins_in, EmptyDecision,
+        PurgeReport, TrashError,
+    };
+
+    #[test]
+    fn test_purge_matching() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let nested = file_dir.path().join("isos");
+        std::fs::create_dir(&nested).unwrap();
+        for path in &[
+            nested.join("big.iso"),
+            file_dir.path().join("small.iso"),
+            file_dir.path().join("notes.txt"),
+        ] {
+            std::fs::write(path, b"x").unwrap();
+            move_to_trash_in(path, &location).unwrap();
+        }
+
+        let purged = purge_matching_in("isos/*.iso", &location).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].original_path(), nested.join("big.iso"));
+        let purged = purge_matching_in("**/*.iso", &location).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+
+        match purge_matching_in("[", &location) {
+            Err(TrashError::InvalidPattern(_)) => {}
+            other => panic!("expected an invalid pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_purge_missing_origins() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let gone = file_dir.path().join("gone");
+        std::fs::create_dir(&gone).unwrap();
+        for path in &[gone.join("a"), file_dir.path().join("b")] {
+            std::fs::write(path, b"x").unwrap();
+            move_to_trash_in(path, &location).unwrap();
+        }
+        std::fs::remove_dir(&gone).unwrap();
+
+        let purged = purge_missing_origins_in(&location).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].original_path(), gone.join("a"));
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_origin_is_not_missing() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let locked = file_dir.path().join("locked");
+        let parent = locked.join("sub");
+        std::fs::create_dir_all(&parent).unwrap();
+        let path = parent.join("a");
+        std::fs::write(&path, b"x").unwrap();
+        move_to_trash_in(&path, &location).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        let purged = purge_missing_origins_in(&location);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // Root may look anyway and finds the directory, others get EACCES
+        match purged {
+            Ok(purged) => assert!(purged.is_empty()),
+            Err(TrashError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied),
+            Err(e) => panic!("unexpected {:?}", e),
+        }
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_empty_with_decisions() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        for name in &["keep", "delete"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"x").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+        let purged = empty_trash_in_with(&location, |item| {
+            if item.internal_filename() == "keep" {
+                EmptyDecision::Keep
+            } else {
+                EmptyDecision::Delete
+            }
+        })
+        .unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].internal_filename(), "delete");
+
+        let mut asked = 0;
+        let purged = empty_trash_in_with(&location, |_| {
+            asked += 1;
+            EmptyDecision::Abort
+        })
+        .unwrap();
+        assert!(purged.is_empty());
+        assert_eq!(asked, 1);
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_preview() {
+        let (file_dir, _trash_dir, first) = fixture();
+        let (_other_dir, _other_trash_dir, second) = fixture();
+        let locations = [first, second];
+        for (i, name) in ["a.iso", "b.txt", "c.iso"].iter().enumerate() {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, vec![0; i + 1]).unwrap();
+            move_to_trash_in(&path, &locations[i % 2]).unwrap();
+        }
+
+        let report = preview_purge_matching_in("*.iso", &locations[0]).unwrap();
+        assert_eq!((report.summary.count, report.summary.size), (2, 4));
+        assert!(report.summary.oldest <= report.summary.newest);
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(
+            preview_empty_trash_in(&locations[1]).unwrap().summary.size,
+            2
+        );
+        assert_eq!(list_trash_in(&locations[0]).unwrap().len(), 2);
+
+        let mut everything = list_trash_in(&locations[0]).unwrap();
+        everything.extend(list_trash_in(&locations[1]).unwrap());
+        let report = PurgeReport::new(everything).unwrap();
+        assert_eq!(report.summary.count, 3);
+        assert_eq!(report.trashes.len(), 2);
+        assert_eq!(report.trashes[0].location, locations[0]);
+        assert_eq!(
+            (
+                report.trashes[1].summary.count,
+                report.trashes[1].summary.size
+            ),
+            (1, 2)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Where deletion dates come from, so that tests and tools which must
+//! produce the same info files every time can fix them
+
+use crate::{date, DeletionDate};
+
+/// Tells the time recorded as an item's deletion date, see
+/// `TrashOptions::clock`
+pub trait Clock: Send + Sync {
+    /// The current local time
+    fn now(&self) -> DeletionDate;
+}
+
+/// The system clock in local time, used unless another is given
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DeletionDate {
+        date::now()
+    }
+}
+
+/// A clock which is always at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DeletionDate);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DeletionDate {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedClock;
+    use crate::tests::fixture;
+    use crate::{date, list_trash_in, move_to_trash_with, TrashOptions};
+
+    #[test]
+    fn test_fixed_clock() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("file");
+        std::fs::write(&path, b"x").unwrap();
+
+        let fixed = date::parse("2020-01-02T03:04:05").unwrap();
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .clock(FixedClock(fixed));
+        move_to_trash_with(&path, &options).unwrap();
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items[0].info().deletion_date, fixed);
+    }
+}
@@ -0,0 +1,492 @@
+//! Defaults read from `$XDG_CONFIG_HOME/trash-rs/config.toml`, such as
+//!
+//! ```toml
+//! # Purge items after 30 days, and the oldest beyond 10 GiB
+//! retention_days = 30
+//! max_size = "10G"
+//! # ...counting the trashes on all devices together
+//! all_trashes = true
+//! collisions = "counter-before-extension"
+//! cross_device = "fail"
+//! excluded_mounts = ["/mnt/backup"]
+//! ```
+//!
+//! Only this flat subset of TOML is understood: integers, booleans,
+//! strings and arrays of strings on a single line, without tables
+//!
+//! Each setting can be overridden by an environment variable, for
+//! containers and CI where writing the file is awkward:
+//! `TRASH_RETENTION_DAYS`, `TRASH_MAX_SIZE`, `TRASH_ALL_TRASHES`,
+//! `TRASH_COLLISIONS`, `TRASH_CROSS_DEVICE` and `TRASH_EXCLUDED_MOUNTS`,
+//! which holds a list of paths separated like `$PATH`
+
+use crate::{CollisionStrategy, CrossDevicePolicy, RetentionPolicy, TrashError, TrashOptions};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Seconds in a day, as `retention_days` counts them
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Environment variables overriding the settings, with their keys
+const ENV_OVERRIDES: [(&str, &str); 6] = [
+    ("TRASH_RETENTION_DAYS", "retention_days"),
+    ("TRASH_MAX_SIZE", "max_size"),
+    ("TRASH_ALL_TRASHES", "all_trashes"),
+    ("TRASH_COLLISIONS", "collisions"),
+    ("TRASH_CROSS_DEVICE", "cross_device"),
+    ("TRASH_EXCLUDED_MOUNTS", "excluded_mounts"),
+];
+
+/// Defaults for the trash, from the configuration file
+/// Every setting is optional, leaving the built in default when unset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrashConfig {
+    /// Items deleted longer ago than this many days are purged by
+    /// automatic cleanup
+    pub retention_days: Option<u64>,
+    /// Automatic cleanup purges the oldest items once the trash holds
+    /// more bytes than this
+    pub max_size: Option<u64>,
+    /// Whether automatic cleanup counts the trashes on all devices
+    /// together, see `RetentionPolicy::all_trashes`
+    pub all_trashes: Option<bool>,
+    /// How names in the trash are made unique
+    pub collisions: Option<CollisionStrategy>,
+    /// Whether files on another device than their trash may be copied
+    pub cross_device: Option<CrossDevicePolicy>,
+    /// Mount points whose trashes are left alone when going through
+    /// every trash, as with `all_trash_locations`
+    pub excluded_mounts: Vec<PathBuf>,
+}
+
+impl TrashConfig {
+    /// Where the configuration file is looked for: in `$XDG_CONFIG_HOME`,
+    /// or `~/.config` when that is not set
+    pub fn path() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("trash-rs").join("config.toml"))
+    }
+
+    /// Reads the configuration file, all defaults if there is none,
+    /// then applies the overrides from the environment
+    pub fn load() -> Result<Self, TrashError> {
+        let mut config = match Self::path() {
+            Some(path) if path.exists() => Self::load_from(&path)?,
+            _ => Self::default(),
+        };
+        config.apply_env(|name| std::env::var_os(name))?;
+        Ok(config)
+    }
+
+    /// Reads the configuration from the given file alone, without the
+    /// overrides from the environment
+    pub fn load_from(path: &Path) -> Result<Self, TrashError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|(line, message)| TrashError::InvalidConfig {
+            path: path.to_path_buf(),
+            line,
+            message,
+        })
+    }
+
+    /// The configuration for the convenience functions, which have no
+    /// way to report errors: settings which cannot be read keep their
+    /// default, while the others still apply
+    pub(crate) fn current() -> Self {
+        let mut config = match Self::path() {
+            Some(path) => std::fs::read_to_string(path)
+                .map(|text| Self::parse_lenient(&text))
+                .unwrap_or_default(),
+            None => Self::default(),
+        };
+        for (name, key) in ENV_OVERRIDES.iter() {
+            if let Some(value) = std::env::var_os(name) {
+                let _ = config.apply_env_var(name, key, value);
+            }
+        }
+        config
+    }
+
+    /// Parses the configuration, failing with the line number and what
+    /// is wrong with it
+    pub(crate) fn parse(text: &str) -> Result<Self, (usize, String)> {
+        let mut config = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            config
+                .parse_line(line)
+                .map_err(|message| (index + 1, message))?;
+        }
+        Ok(config)
+    }
+
+    /// Parses the configuration, skipping the lines which are invalid
+    fn parse_lenient(text: &str) -> Self {
+        let mut config = Self::default();
+        for line in text.lines() {
+            let _ = config.parse_line(line);
+        }
+        config
+    }
+
+    /// Overrides settings with the environment variables `var` finds
+    pub(crate) fn apply_env<F>(&mut self, var: F) -> Result<(), TrashError>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        for (name, key) in ENV_OVERRIDES.iter() {
+            if let Some(value) = var(name) {
+                self.apply_env_var(name, key, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Overrides the setting named `key` with `value` of the environment
+    /// variable `name`
+    fn apply_env_var(&mut self, name: &str, key: &str, value: OsString) -> Result<(), TrashError> {
+        let invalid = |message: String| TrashError::InvalidEnvVar {
+            name: name.to_owned(),
+            message,
+        };
+        if key == "excluded_mounts" {
+            self.excluded_mounts = std::env::split_paths(&value)
+                .filter(|mount| !mount.as_os_str().is_empty())
+                .collect();
+            return Ok(());
+        }
+        let text = value
+            .into_string()
+            .map_err(|_| invalid("not valid UTF-8".to_owned()))?;
+        let text = text.trim();
+        let value = match text.replace('_', "").parse() {
+            Ok(number) if key == "retention_days" => Value::Integer(number),
+            _ if key == "all_trashes" => match text {
+                "1" | "true" | "yes" => Value::Boolean(true),
+                "0" | "false" | "no" | "" => Value::Boolean(false),
+                _ => return Err(invalid(format!("expected true or false, found {:?}", text))),
+            },
+            _ => Value::String(text.to_owned()),
+        };
+        self.set(key, value).map_err(invalid)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        if line.starts_with('[') {
+            return Err("tables are not supported".to_owned());
+        }
+        match line.find('=') {
+            Some(eq) => self.set(line[..eq].trim(), Value::parse(line[eq + 1..].trim())?),
+            None => Err(format!("expected `key = value`, found {:?}", line)),
+        }
+    }
+
+    /// Sets the setting named `key` as in the configuration file
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "retention_days" => {
+                let days = value.integer(key)?;
+                if days.checked_mul(SECS_PER_DAY).is_none() {
+                    return Err(format!("retention_days {} is too large", days));
+                }
+                self.retention_days = Some(days)
+            }
+            "max_size" => {
+                self.max_size = Some(match value {
+                    Value::Integer(bytes) => bytes,
+                    Value::String(text) => parse_size(&text)
+                        .ok_or_else(|| format!("invalid size {:?} for max_size", text))?,
+                    _ => return Err("max_size must be a number or a string".to_owned()),
+                })
+            }
+            "all_trashes" => match value {
+                Value::Boolean(all) => self.all_trashes = Some(all),
+                _ => return Err("all_trashes must be true or false".to_owned()),
+            },
+            "collisions" => {
+                self.collisions = Some(match value.string(key)?.as_str() {
+                    "counter" => CollisionStrategy::Counter,
+                    "counter-before-extension" => CollisionStrategy::CounterBeforeExtension,
+                    "copy" => CollisionStrategy::Copy,
+                    "timestamp" => CollisionStrategy::Timestamp,
+                    other => return Err(format!("unknown collision strategy {:?}", other)),
+                })
+            }
+            "cross_device" => {
+                self.cross_device = Some(parse_cross_device(&value.string(key)?)?);
+            }
+            "excluded_mounts" => match value {
+                Value::Array(mounts) => {
+                    self.excluded_mounts = mounts.into_iter().map(PathBuf::from).collect()
+                }
+                _ => return Err("excluded_mounts must be an array of strings".to_owned()),
+            },
+            _ => return Err(format!("unknown key {:?}", key)),
+        }
+        Ok(())
+    }
+
+    /// Options for trashing with the configured defaults
+    pub fn options(&self) -> TrashOptions {
+        let mut options = TrashOptions::new();
+        if let Some(collisions) = self.collisions {
+            options = options.collisions(collisions);
+        }
+        if let Some(cross_device) = self.cross_device {
+            options = options.cross_device(cross_device);
+        }
+        options
+    }
+
+    /// The configured retention, unlimited if none is
+    pub fn retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: self
+                .retention_days
+                .map(|days| Duration::from_secs(days.saturating_mul(SECS_PER_DAY))),
+            max_size: self.max_size,
+            all_trashes: self.all_trashes.unwrap_or(false),
+            clock: None,
+        }
+    }
+
+    /// Whether the trashes on the mount at `topdir` are left alone
+    pub(crate) fn is_excluded(&self, topdir: &Path) -> bool {
+        self.excluded_mounts.iter().any(|mount| mount == topdir)
+    }
+}
+
+/// A value as written in the configuration file
+enum Value {
+    Integer(u64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<String>),
+}
+
+impl Value {
+    fn parse(text: &str) -> Result<Self, String> {
+        if let Some(rest) = text.strip_prefix('[') {
+            let inner = rest
+                .strip_suffix(']')
+                .ok_or_else(|| "unterminated array".to_owned())?;
+            let mut items = Vec::new();
+            let mut rest = inner.trim();
+            while !rest.is_empty() {
+                let (item, after) = parse_string(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                rest = match rest.strip_prefix(',') {
+                    Some(after_comma) => after_comma.trim_start(),
+                    None if rest.is_empty() => rest,
+                    None => return Err("expected `,` between array items".to_owned()),
+                };
+            }
+            Ok(Value::Array(items))
+        } else if text.starts_with('"') {
+            match parse_string(text)? {
+                (value, "") => Ok(Value::String(value)),
+                _ => Err("unexpected text after string".to_owned()),
+            }
+        } else if text == "true" || text == "false" {
+            Ok(Value::Boolean(text == "true"))
+        } else {
+            text.replace('_', "")
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("invalid value {:?}", text))
+        }
+    }
+
+    fn integer(self, key: &str) -> Result<u64, String> {
+        match self {
+            Value::Integer(value) => Ok(value),
+            _ => Err(format!("{} must be a number", key)),
+        }
+    }
+
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::String(value) => Ok(value),
+            _ => Err(format!("{} must be a string", key)),
+        }
+    }
+}
+
+/// Parses a basic string at the start of `text`, returning it and
+/// what follows it
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let mut chars = text
+        .strip_prefix('"')
+        .ok_or_else(|| format!("expected a string, found {:?}", text))?
+        .char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &text[i + 2..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => value.push('"'),
+                Some((_, '\\')) => value.push('\\'),
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                _ => return Err("unsupported escape in string".to_owned()),
+            },
+            _ => value.push(c),
+        }
+    }
+    Err("unterminated string".to_owned())
+}
+
+/// The line without a `#` comment, leaving any `#` inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// `copy` or `fail`, or `deny` as another name for `fail`
+pub(crate) fn parse_cross_device(value: &str) -> Result<CrossDevicePolicy, String> {
+    match value {
+        "copy" => Ok(CrossDevicePolicy::Copy),
+        "fail" | "deny" => Ok(CrossDevicePolicy::Fail),
+        other => Err(format!("unknown cross device policy {:?}", other)),
+    }
+}
+
+/// A size in bytes with an optional binary suffix such as `500M` or `10G`
+pub(crate) fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit: u64 = match suffix.trim().to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" | "KI" => 1 << 10,
+        "M" | "MI" => 1 << 20,
+        "G" | "GI" => 1 << 30,
+        "T" | "TI" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * unit as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrashConfig;
+    use crate::{CollisionStrategy, CrossDevicePolicy, TrashError};
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_config() {
+        let config = TrashConfig::parse(
+            "# defaults\n\
+             retention_days = 30\n\
+             max_size = \"1.5G\" # a comment\n\
+             all_trashes = true\n\
+             collisions = \"copy\"\n\
+             cross_device = \"deny\"\n\
+             excluded_mounts = [\"/mnt/a # b\", \"/mnt/c\",]\n",
+        )
+        .unwrap();
+        assert_eq!(config.retention_days, Some(30));
+        assert_eq!(config.max_size, Some(3 << 29));
+        assert_eq!(config.collisions, Some(CollisionStrategy::Copy));
+        assert_eq!(config.cross_device, Some(CrossDevicePolicy::Fail));
+        assert_eq!(
+            config.excluded_mounts,
+            [PathBuf::from("/mnt/a # b"), PathBuf::from("/mnt/c")]
+        );
+        let retention = config.retention();
+        assert_eq!(retention.max_age.unwrap().as_secs(), 30 * 24 * 60 * 60);
+        assert!(retention.all_trashes);
+        assert_eq!(
+            TrashConfig::parse("max_size = 1_024").unwrap().max_size,
+            Some(1024)
+        );
+
+        let error = |text: &str| TrashConfig::parse(text).unwrap_err();
+        assert_eq!(error("\nretention = 3").0, 2);
+        assert_eq!(error("retention_days = \"3\"").0, 1);
+        assert_eq!(error("[trash]").0, 1);
+        assert_eq!(error("collisions = \"random\"").0, 1);
+        assert_eq!(error("excluded_mounts = [\"/a\" \"/b\"]").0, 1);
+        assert_eq!(error("max_size = \"unterminated").0, 1);
+        assert_eq!(error("all_trashes = \"yes\"").0, 1);
+        assert_eq!(error("retention_days = 18446744073709551615").0, 1);
+    }
+
+    #[test]
+    fn test_lenient_parse_keeps_valid_settings() {
+        let config = TrashConfig::parse_lenient(
+            "retention_days = 30\n\
+             collisions = \"random\"\n\
+             max_size = \"10G\"\n",
+        );
+        assert_eq!(config.retention_days, Some(30));
+        assert_eq!(config.collisions, None);
+        assert_eq!(config.max_size, Some(10 << 30));
+
+        let config = TrashConfig {
+            retention_days: Some(u64::MAX),
+            ..TrashConfig::default()
+        };
+        assert_eq!(config.retention().max_age.unwrap().as_secs(), u64::MAX);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = TrashConfig::parse("retention_days = 30\ncollisions = \"copy\"").unwrap();
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        config
+            .apply_env(env(&[
+                ("TRASH_RETENTION_DAYS", "7"),
+                ("TRASH_MAX_SIZE", "500M"),
+                ("TRASH_CROSS_DEVICE", "deny"),
+                ("TRASH_ALL_TRASHES", "1"),
+                ("TRASH_EXCLUDED_MOUNTS", "/mnt/a::/mnt/b"),
+            ]))
+            .unwrap();
+        assert_eq!(config.retention_days, Some(7));
+        assert_eq!(config.max_size, Some(500 << 20));
+        assert_eq!(config.all_trashes, Some(true));
+        assert_eq!(config.collisions, Some(CollisionStrategy::Copy));
+        assert_eq!(config.cross_device, Some(CrossDevicePolicy::Fail));
+        assert_eq!(
+            config.excluded_mounts,
+            [PathBuf::from("/mnt/a"), PathBuf::from("/mnt/b")]
+        );
+
+        let error = config
+            .apply_env(env(&[("TRASH_RETENTION_DAYS", "a week")]))
+            .unwrap_err();
+        match error {
+            TrashError::InvalidEnvVar { name, .. } => assert_eq!(name, "TRASH_RETENTION_DAYS"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Deletion dates, backed by chrono or, with the `time` feature and
+//! without the default `chrono` feature, by the time crate
+//! Either way dates are naive local times, stored as `%Y-%m-%dT%H:%M:%S`
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+use std::time::Duration;
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+compile_error!("either the `chrono` or the `time` feature must be enabled");
+
+// Stand-ins without a backend, so that the error above is not buried
+// under ones about everything using dates
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeletionDate;
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeletionDay;
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+impl DeletionDate {
+    pub fn date(self) -> DeletionDay {
+        DeletionDay
+    }
+}
+
+/// A naive local date and time, as stored in `.trashinfo` files
+#[cfg(feature = "chrono")]
+pub type DeletionDate = chrono::NaiveDateTime;
+
+/// A naive local date and time, as stored in `.trashinfo` files
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub type DeletionDate = time::PrimitiveDateTime;
+
+/// A calendar day in local time, the day part of a `DeletionDate`
+#[cfg(feature = "chrono")]
+pub type DeletionDay = chrono::NaiveDate;
+
+/// A calendar day in local time, the day part of a `DeletionDate`
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub type DeletionDay = time::Date;
+
+/// The current local time
+#[cfg(feature = "chrono")]
+pub(crate) fn now() -> DeletionDate {
+    chrono::Local::now().naive_local()
+}
+
+/// The current local time
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn now() -> DeletionDate {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let utc = time::OffsetDateTime::from_unix_timestamp(secs as i64)
+        .unwrap_or(time::OffsetDateTime::UNIX_EPOCH);
+    utc_to_local(time::PrimitiveDateTime::new(utc.date(), utc.time()))
+}
+
+/// Offset of local time from UTC at the given time, from the C library
+/// as `time` refuses to read it once several threads are running
+#[cfg(all(unix, feature = "time", not(feature = "chrono")))]
+fn local_offset_secs(at: i64) -> i32 {
+    let at = at as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::localtime_r(&at, &mut tm) };
+    if res.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i32
+    }
+}
+
+/// Without a C library to ask, dates are shown in UTC
+#[cfg(all(not(unix), feature = "time", not(feature = "chrono")))]
+fn local_offset_secs(_at: i64) -> i32 {
+    0
+}
+
+/// Formats a date the way the spec stores it
+#[cfg(feature = "chrono")]
+pub(crate) fn format(date: &DeletionDate) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Formats a date the way the spec stores it
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn format(date: &DeletionDate) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        date.year(),
+        u8::from(date.month()),
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second()
+    )
+}
+
+/// Parses a date in the spec's format, also accepting what other trash
+/// tools write: fractional seconds, a space instead of the `T`,
+/// and a trailing `Z` marking the time as UTC rather than local
+pub(crate) fn parse(text: &str) -> Option<DeletionDate> {
+    let text = text.trim();
+    let (text, is_utc) = match text.strip_suffix(|c| c == 'Z' || c == 'z') {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let text = match text.find('.') {
+        Some(dot) if text[dot + 1..].bytes().all(|b| b.is_ascii_digit()) => &text[..dot],
+        _ => text,
+    };
+    let text = match text.as_bytes().get(10) {
+        Some(b' ') => format!("{}T{}", &text[..10], text[11..].trim_start()),
+        _ => text.to_owned(),
+    };
+    let date = parse_strict(&text)?;
+    if is_utc {
+        Some(utc_to_local(date))
+    } else {
+        Some(date)
+    }
+}
+
+#[cfg(feature = "chrono")]
+fn parse_strict(text: &str) -> Option<DeletionDate> {
+    chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").ok()
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+fn parse_strict(text: &str) -> Option<DeletionDate> {
+    let format = time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+    time::PrimitiveDateTime::parse(text, format).ok()
+}
+
+#[cfg(feature = "chrono")]
+fn utc_to_local(date: DeletionDate) -> DeletionDate {
+    use chrono::TimeZone;
+
+    chrono::Local.from_utc_datetime(&date).naive_local()
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+fn utc_to_local(date: DeletionDate) -> DeletionDate {
+    let utc = date.assume_utc();
+    let offset = time::UtcOffset::from_whole_seconds(local_offset_secs(utc.unix_timestamp()))
+        .unwrap_or(time::UtcOffset::UTC);
+    let local = utc.to_offset(offset);
+    time::PrimitiveDateTime::new(local.date(), local.time())
+}
+
+/// The date `age` before `date`, or the earliest representable date
+#[cfg(feature = "chrono")]
+pub(crate) fn saturating_sub(date: DeletionDate, age: Duration) -> DeletionDate {
+    chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| date.checked_sub_signed(age))
+        .unwrap_or(chrono::NaiveDateTime::MIN)
+}
+
+/// The date `age` before `date`, or the earliest representable date
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn saturating_sub(date: DeletionDate, age: Duration) -> DeletionDate {
+    use std::convert::TryFrom;
+
+    time::Duration::try_from(age)
+        .ok()
+        .and_then(|age| date.checked_sub(age))
+        .unwrap_or(time::PrimitiveDateTime::MIN)
+}
+
+/// The date without its fraction of a second, as the spec stores it
+#[cfg(feature = "chrono")]
+pub(crate) fn whole_seconds(date: DeletionDate) -> DeletionDate {
+    use chrono::Timelike;
+
+    date.with_nanosecond(0).unwrap_or(date)
+}
+
+/// The date without its fraction of a second, as the spec stores it
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn whole_seconds(date: DeletionDate) -> DeletionDate {
+    date.replace_nanosecond(0).unwrap_or(date)
+}
+
+/// How long after `earlier` `later` is, zero if it is not after it
+#[cfg(feature = "chrono")]
+pub(crate) fn elapsed(earlier: DeletionDate, later: DeletionDate) -> Duration {
+    later
+        .signed_duration_since(earlier)
+        .to_std()
+        .unwrap_or_default()
+}
+
+/// How long after `earlier` `later` is, zero if it is not after it
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn elapsed(earlier: DeletionDate, later: DeletionDate) -> Duration {
+    use std::convert::TryFrom;
+
+    Duration::try_from(later - earlier).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{elapsed, format, parse, saturating_sub};
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_round_trip() {
+        let date = parse("2019-03-05T07:08:09").unwrap();
+        assert_eq!(format(&date), "2019-03-05T07:08:09");
+        let earlier = saturating_sub(date, Duration::from_secs(24 * 60 * 60));
+        assert_eq!(format(&earlier), "2019-03-04T07:08:09");
+        assert!(parse("yesterday").is_none());
+        assert_eq!(elapsed(earlier, date), Duration::from_secs(24 * 60 * 60));
+        assert_eq!(elapsed(date, earlier), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_lenient_parsing() {
+        let expected = "2019-03-05T07:08:09";
+        assert_eq!(
+            format(&parse("2019-03-05T07:08:09.123456").unwrap()),
+            expected
+        );
+        assert_eq!(format(&parse("2019-03-05 07:08:09").unwrap()), expected);
+        assert_eq!(format(&parse(" 2019-03-05 07:08:09.5 ").unwrap()), expected);
+        assert!(parse("2019-03-05T07:08:09Z").is_some());
+        assert!(parse("2019-03-05T07:08").is_none());
+        assert!(parse("2019-03-05T07:08:09.x").is_none());
+    }
+}
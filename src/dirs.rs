@@ -0,0 +1,247 @@
+//! Handles on the `files` and `info` directories of a trash
+//!
+//! Once opened, names are created, linked and renamed relative to the
+//! directories themselves rather than to paths, so swapping the trash or
+//! one of its directories for a symlink halfway through a move cannot
+//! redirect what is written. Copies to another filesystem still go by
+//! path, as do platforms other than unix.
+
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// The open `files` and `info` directories of a trash, created if missing
+#[derive(Debug)]
+pub(crate) struct TrashDirs {
+    trash_dir: PathBuf,
+    #[cfg(unix)]
+    files: std::os::fd::OwnedFd,
+    #[cfg(unix)]
+    info: std::os::fd::OwnedFd,
+}
+
+impl TrashDirs {
+    /// The path of an entry of the info directory
+    pub(crate) fn info_path(&self, name: &OsStr) -> PathBuf {
+        self.trash_dir.join("info").join(name)
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::TrashDirs;
+    use crate::platform::OsStrExt;
+    use std::ffi::{CString, OsStr};
+    use std::fs::File;
+    use std::io::{Error, ErrorKind};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::path::Path;
+
+    fn c_name(name: &[u8]) -> std::io::Result<CString> {
+        CString::new(name).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+    }
+
+    /// A single entry of a directory, so that no path can lead elsewhere
+    fn entry_name(name: &OsStr) -> std::io::Result<CString> {
+        if name.is_empty() || name.as_bytes().contains(&b'/') || name == ".." || name == "." {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("not a file name: {:?}", name),
+            ));
+        }
+        c_name(name.as_bytes())
+    }
+
+    fn check(ret: libc::c_int) -> std::io::Result<libc::c_int> {
+        if ret < 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(ret)
+        }
+    }
+
+    /// Opens a directory of `parent`, creating it first if missing
+    /// The directory itself must not be a symlink, while `trash` may be
+    fn open_subdir(parent: RawFd, name: &str) -> std::io::Result<OwnedFd> {
+        let name = c_name(name.as_bytes())?;
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        match check(unsafe { libc::openat(parent, name.as_ptr(), flags) }) {
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                match check(unsafe { libc::mkdirat(parent, name.as_ptr(), 0o777) }) {
+                    Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
+                    res => {
+                        res?;
+                    }
+                }
+                let fd = check(unsafe { libc::openat(parent, name.as_ptr(), flags) })?;
+                Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+            }
+            res => Ok(unsafe { OwnedFd::from_raw_fd(res?) }),
+        }
+    }
+
+    impl TrashDirs {
+        /// Opens the trash at `trash_dir`, creating what is missing of it
+        pub(crate) fn open(trash_dir: &Path) -> std::io::Result<TrashDirs> {
+            let path = c_name(trash_dir.as_os_str().as_bytes())?;
+            let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+            let trash = match check(unsafe { libc::open(path.as_ptr(), flags) }) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                    std::fs::create_dir_all(trash_dir)?;
+                    check(unsafe { libc::open(path.as_ptr(), flags) })?
+                }
+                res => res?,
+            };
+            let trash = unsafe { OwnedFd::from_raw_fd(trash) };
+            Ok(TrashDirs {
+                trash_dir: trash_dir.to_path_buf(),
+                files: open_subdir(trash.as_raw_fd(), "files")?,
+                info: open_subdir(trash.as_raw_fd(), "info")?,
+            })
+        }
+
+        /// Creates a file in the info directory which must not exist yet
+        pub(crate) fn create_info(&self, name: &OsStr) -> std::io::Result<File> {
+            self.open_info(name, libc::O_EXCL)
+        }
+
+        /// Creates or truncates a file in the info directory, never
+        /// following a symlink found in its place
+        pub(crate) fn create_info_temp(&self, name: &OsStr) -> std::io::Result<File> {
+            self.open_info(name, libc::O_TRUNC | libc::O_NOFOLLOW)
+        }
+
+        fn open_info(&self, name: &OsStr, flags: libc::c_int) -> std::io::Result<File> {
+            let name = entry_name(name)?;
+            let flags = flags | libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC;
+            let fd = check(unsafe {
+                libc::openat(
+                    self.info.as_raw_fd(),
+                    name.as_ptr(),
+                    flags,
+                    0o666 as libc::c_uint,
+                )
+            })?;
+            Ok(unsafe { File::from_raw_fd(fd) })
+        }
+
+        /// Gives an entry of the info directory a second name
+        pub(crate) fn link_info(&self, from: &OsStr, to: &OsStr) -> std::io::Result<()> {
+            let (from, to) = (entry_name(from)?, entry_name(to)?);
+            let info = self.info.as_raw_fd();
+            check(unsafe { libc::linkat(info, from.as_ptr(), info, to.as_ptr(), 0) })?;
+            Ok(())
+        }
+
+        /// Renames an entry of the info directory, replacing `to`
+        pub(crate) fn rename_info(&self, from: &OsStr, to: &OsStr) -> std::io::Result<()> {
+            let (from, to) = (entry_name(from)?, entry_name(to)?);
+            let info = self.info.as_raw_fd();
+            check(unsafe { libc::renameat(info, from.as_ptr(), info, to.as_ptr()) })?;
+            Ok(())
+        }
+
+        /// Removes a file from the info directory
+        pub(crate) fn remove_info(&self, name: &OsStr) -> std::io::Result<()> {
+            let name = entry_name(name)?;
+            check(unsafe { libc::unlinkat(self.info.as_raw_fd(), name.as_ptr(), 0) })?;
+            Ok(())
+        }
+
+        /// Renames `src` into the files directory as `name`
+        pub(crate) fn rename_into_files(&self, src: &Path, name: &OsStr) -> std::io::Result<()> {
+            let (src, name) = (c_name(src.as_os_str().as_bytes())?, entry_name(name)?);
+            check(unsafe {
+                libc::renameat(
+                    libc::AT_FDCWD,
+                    src.as_ptr(),
+                    self.files.as_raw_fd(),
+                    name.as_ptr(),
+                )
+            })?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+use std::fs::File;
+#[cfg(not(unix))]
+use std::path::Path;
+
+#[cfg(not(unix))]
+impl TrashDirs {
+    pub(crate) fn open(trash_dir: &Path) -> std::io::Result<TrashDirs> {
+        std::fs::create_dir_all(trash_dir.join("files"))?;
+        std::fs::create_dir_all(trash_dir.join("info"))?;
+        Ok(TrashDirs {
+            trash_dir: trash_dir.to_path_buf(),
+        })
+    }
+
+    pub(crate) fn create_info(&self, name: &OsStr) -> std::io::Result<File> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.info_path(name))
+    }
+
+    pub(crate) fn create_info_temp(&self, name: &OsStr) -> std::io::Result<File> {
+        File::create(self.info_path(name))
+    }
+
+    pub(crate) fn link_info(&self, from: &OsStr, to: &OsStr) -> std::io::Result<()> {
+        std::fs::hard_link(self.info_path(from), self.info_path(to))
+    }
+
+    pub(crate) fn rename_info(&self, from: &OsStr, to: &OsStr) -> std::io::Result<()> {
+        std::fs::rename(self.info_path(from), self.info_path(to))
+    }
+
+    pub(crate) fn remove_info(&self, name: &OsStr) -> std::io::Result<()> {
+        std::fs::remove_file(self.info_path(name))
+    }
+
+    pub(crate) fn rename_into_files(&self, src: &Path, name: &OsStr) -> std::io::Result<()> {
+        std::fs::rename(src, self.trash_dir.join("files").join(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrashDirs;
+    use std::ffi::OsStr;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_trash_dirs() {
+        let dir = tempdir().expect("temp dir creation failed");
+        let trash_dir = dir.path().join("trash");
+        let dirs = TrashDirs::open(&trash_dir).unwrap();
+        assert!(trash_dir.join("files").is_dir());
+        assert!(trash_dir.join("info").is_dir());
+
+        dirs.create_info(OsStr::new("a.trashinfo")).unwrap();
+        assert!(dirs.create_info(OsStr::new("a.trashinfo")).is_err());
+        assert!(dirs.create_info(OsStr::new("../escape")).is_err());
+        dirs.link_info(OsStr::new("a.trashinfo"), OsStr::new("b.trashinfo"))
+            .unwrap();
+        dirs.remove_info(OsStr::new("a.trashinfo")).unwrap();
+        assert!(dirs.info_path(OsStr::new("b.trashinfo")).is_file());
+
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"x").unwrap();
+        dirs.rename_into_files(&file, OsStr::new("file")).unwrap();
+        assert!(trash_dir.join("files/file").is_file());
+
+        // Whatever is put in place of the trash once opened goes untouched
+        #[cfg(unix)]
+        {
+            let moved = dir.path().join("moved");
+            std::fs::rename(&trash_dir, &moved).unwrap();
+            std::fs::create_dir_all(trash_dir.join("info")).unwrap();
+            dirs.create_info(OsStr::new("c.trashinfo")).unwrap();
+            assert!(moved.join("info/c.trashinfo").is_file());
+            assert!(!trash_dir.join("info/c.trashinfo").exists());
+        }
+    }
+}
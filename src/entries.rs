@@ -0,0 +1,215 @@
+use crate::platform::OsStrExt;
+use crate::{
+    read_info_file, EscapePolicy, Journal, TrashError, TrashInfo, TrashLocation, TrashedItem,
+};
+use std::ffi::{OsStr, OsString};
+use std::fs::ReadDir;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The items of a trash in directory order, read one at a time
+/// Skipping items with `nth` or `skip` does not read their info files
+#[derive(Debug)]
+pub struct TrashEntries {
+    location: TrashLocation,
+    trash_dir: PathBuf,
+    /// `None` when the trash has no info directory yet
+    entries: Option<ReadDir>,
+    /// Only items whose internal name starts with this are read
+    prefix: Option<Vec<u8>>,
+    /// How malformed escapes in the `Path` of info files are treated
+    policy: EscapePolicy,
+    /// Where restoring or purging the items read is recorded
+    journal: Option<Journal>,
+}
+
+impl TrashEntries {
+    pub(crate) fn new(location: &TrashLocation, trash_dir: PathBuf) -> Result<Self, TrashError> {
+        let entries = match std::fs::read_dir(trash_dir.join("info")) {
+            Ok(entries) => Some(entries),
+            // Nothing has been trashed yet
+            Err(ref e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            location: location.clone(),
+            trash_dir,
+            entries,
+            prefix: None,
+            policy: EscapePolicy::Strict,
+            journal: None,
+        })
+    }
+
+    /// Treat malformed escapes in the `Path` of the info files read as
+    /// `policy` says, `EscapePolicy::Strict` by default
+    pub fn escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Record restoring or purging the items read in `journal`, see
+    /// `TrashedItem::with_journal`
+    pub fn journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Items which may have been trashed from `original`, judging by
+    /// their internal name starting with its name before the extension,
+    /// as the names of all items trashed by us and other common tools do
+    pub(crate) fn named_after(
+        location: &TrashLocation,
+        trash_dir: PathBuf,
+        original: &Path,
+    ) -> Result<Self, TrashError> {
+        let mut entries = Self::new(location, trash_dir)?;
+        let stem = original.file_stem().unwrap_or_default();
+        entries.prefix = Some(stem.as_bytes().to_vec());
+        Ok(entries)
+    }
+
+    /// Path of the next info file, without reading it
+    fn next_info_path(&mut self) -> Option<Result<(OsString, PathBuf), TrashError>> {
+        let entries = self.entries.as_mut()?;
+        for entry in entries {
+            let info_path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            if let Some(internal_filename) = info_name(&info_path) {
+                if let Some(prefix) = &self.prefix {
+                    if !internal_filename.as_bytes().starts_with(prefix) {
+                        continue;
+                    }
+                }
+                return Some(Ok((internal_filename.to_os_string(), info_path)));
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for TrashEntries {
+    type Item = Result<TrashedItem, TrashError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (internal_filename, info_path) = match self.next_info_path()? {
+            Ok(found) => found,
+            Err(e) => return Some(Err(e)),
+        };
+        let item = read_info_file(&info_path, &internal_filename)
+            .and_then(|content| {
+                Ok(TrashInfo::from_filename_and_content_with(
+                    internal_filename,
+                    &content,
+                    self.policy,
+                )?)
+            })
+            .map(|info| {
+                TrashedItem::new(info, self.location.clone(), self.trash_dir.clone())
+                    .with_journal(self.journal.clone())
+            });
+        Some(item)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        for _ in 0..n {
+            if let Err(e) = self.next_info_path()? {
+                return Some(Err(e));
+            }
+        }
+        self.next()
+    }
+}
+
+/// Name of the item an info file describes, if it is one
+pub(crate) fn info_name(info_path: &Path) -> Option<&OsStr> {
+    if info_path.extension() != Some(OsStr::new("trashinfo")) {
+        return None;
+    }
+    info_path.file_stem()
+}
+
+/// A slice of a trash listing
+#[derive(Debug, Clone)]
+pub struct TrashPage {
+    /// The items on this page, in directory order
+    pub items: Vec<TrashedItem>,
+    /// Offset of the next page, or `None` if this was the last one
+    pub next_offset: Option<usize>,
+}
+
+/// Iterates over the items in a trash, in directory order, without
+/// reading all of them up front
+pub fn iter_trash_in(location: &TrashLocation) -> Result<TrashEntries, TrashError> {
+    TrashEntries::new(location, location.trash_dir()?)
+}
+
+/// Lists up to `limit` items of a trash, after skipping `offset` of them,
+/// for showing a large trash a page at a time
+/// Items are in directory order, which stays the same as long as
+/// nothing is trashed or removed in between
+pub fn list_trash_page(
+    location: &TrashLocation,
+    offset: usize,
+    limit: usize,
+) -> Result<TrashPage, TrashError> {
+    let mut entries = iter_trash_in(location)?;
+    let mut items = Vec::with_capacity(limit);
+    if limit > 0 {
+        if let Some(item) = entries.nth(offset) {
+            items.push(item?);
+        }
+        for item in entries.by_ref().take(limit - 1) {
+            items.push(item?);
+        }
+    }
+    let read = offset + items.len();
+    let next_offset = match entries.next_info_path() {
+        Some(Err(e)) => return Err(e),
+        Some(Ok(_)) if items.len() == limit => Some(read),
+        _ => None,
+    };
+    Ok(TrashPage { items, next_offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{iter_trash_in, list_trash_page, move_to_trash_in};
+
+    #[test]
+    fn test_pages() {
+        let (file_dir, _trash_dir, location) = fixture();
+        assert_eq!(iter_trash_in(&location).unwrap().count(), 0);
+
+        for name in &["a", "b", "c", "d", "e"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"x").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+        let mut seen = Vec::new();
+        let mut offset = Some(0);
+        while let Some(current) = offset {
+            let page = list_trash_page(&location, current, 2).unwrap();
+            assert!(page.items.len() <= 2);
+            seen.extend(
+                page.items
+                    .into_iter()
+                    .map(|item| item.original_path().to_owned()),
+            );
+            offset = page.next_offset;
+        }
+        seen.sort();
+        let expected: Vec<_> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| file_dir.path().join(name))
+            .collect();
+        assert_eq!(seen, expected);
+
+        let page = list_trash_page(&location, 4, 1).unwrap();
+        assert_eq!((page.items.len(), page.next_offset), (1, None));
+        assert!(list_trash_page(&location, 9, 2).unwrap().items.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! Percent-encoding of the paths the crate records, in info files as
+//! well as in its own journal and records of moves under way
+
+use crate::platform::{OsStrExt, OsStringExt};
+use percent_encoding::{define_encode_set, percent_decode, percent_encode, DEFAULT_ENCODE_SET};
+use std::ffi::{OsStr, OsString};
+
+define_encode_set! {
+    /// `DEFAULT_ENCODE_SET` leaves `%` as it is, which would then be taken
+    /// for the start of an escape when read back
+    pub PATH_ENCODE_SET = [DEFAULT_ENCODE_SET] | {'%'}
+}
+
+/// Escapes a path for recording it
+pub(crate) fn encode_path(bytes: &[u8]) -> String {
+    percent_encode(bytes, PATH_ENCODE_SET).to_string()
+}
+
+/// Escapes a path or name for the crate's own records
+pub(crate) fn encode_os(text: &OsStr) -> String {
+    encode_path(text.as_bytes())
+}
+
+/// Reads back what `encode_os` wrote
+pub(crate) fn decode_os(field: &str) -> OsString {
+    OsString::from_vec(percent_decode(field.as_bytes()).collect())
+}
+
+/// What to do with a `%` in a recorded path which is not followed by
+/// two hex digits, as some tools write a literal `%` unescaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapePolicy {
+    /// Refuse the info file with `ParseTrashInfoError::InvalidEscape`
+    Strict,
+    /// Keep the `%` and what follows as they are
+    Lossy,
+    /// Put U+FFFD REPLACEMENT CHARACTER in place of the `%`
+    Replace,
+}
+
+/// Undoes `%XX` escapes, treating malformed ones as `policy` says
+/// Returns the bytes and whether any escape was malformed, or `None` if
+/// one was and the policy is strict
+pub(crate) fn decode_path(text: &str, policy: EscapePolicy) -> Option<(Vec<u8>, bool)> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut malformed = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(|b| b.is_ascii_hexdigit()));
+        match hex {
+            Some(hex) => {
+                let hex = std::str::from_utf8(hex).unwrap_or_default();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 3;
+            }
+            None => {
+                malformed = true;
+                match policy {
+                    EscapePolicy::Strict => return None,
+                    EscapePolicy::Lossy => decoded.push(b'%'),
+                    EscapePolicy::Replace => decoded.extend_from_slice("\u{FFFD}".as_bytes()),
+                }
+                i += 1;
+            }
+        }
+    }
+    Some((decoded, malformed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_path, encode_path, EscapePolicy};
+
+    #[test]
+    fn test_escape_round_trip() {
+        let path = b"/home/me/100% \xff#1.txt";
+        let encoded = encode_path(path);
+        assert_eq!(encoded, "/home/me/100%25%20%FF%231.txt");
+        assert_eq!(
+            decode_path(&encoded, EscapePolicy::Strict).unwrap(),
+            (path.to_vec(), false)
+        );
+
+        for invalid in &["100%", "100%2", "%zz", "%+1"] {
+            assert_eq!(
+                decode_path(invalid, EscapePolicy::Strict),
+                None,
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_escape_policies() {
+        let decode = |policy| decode_path("/a%zz%20b%", policy);
+        assert_eq!(decode(EscapePolicy::Strict), None);
+        assert_eq!(
+            decode(EscapePolicy::Lossy).unwrap(),
+            (b"/a%zz b%".to_vec(), true)
+        );
+        assert_eq!(
+            decode(EscapePolicy::Replace).unwrap(),
+            ("/a\u{FFFD}zz b\u{FFFD}".as_bytes().to_vec(), true)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// What sort of entry a trashed item is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    /// Sockets, fifos and devices
+    Other,
+}
+
+/// The detected type of a trashed item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileType {
+    pub kind: FileKind,
+    /// MIME type such as `image/png`, if it could be worked out
+    /// Directories and symlinks are `inode/directory` and `inode/symlink`
+    pub mime: Option<&'static str>,
+}
+
+impl FileType {
+    /// Detects the type of the file at `path`, without following symlinks
+    /// Files are typed by the extension of `name`, their original name,
+    /// and with the `sniff` feature by their first bytes as well
+    pub(crate) fn detect(path: &Path, name: &OsStr) -> std::io::Result<Self> {
+        let meta = path.symlink_metadata()?;
+        let file_type = meta.file_type();
+        Ok(if file_type.is_dir() {
+            Self {
+                kind: FileKind::Directory,
+                mime: Some("inode/directory"),
+            }
+        } else if file_type.is_symlink() {
+            Self {
+                kind: FileKind::Symlink,
+                mime: Some("inode/symlink"),
+            }
+        } else if file_type.is_file() {
+            Self {
+                kind: FileKind::File,
+                mime: sniff(path)?.or_else(|| mime_from_name(name)),
+            }
+        } else {
+            Self {
+                kind: FileKind::Other,
+                mime: None,
+            }
+        })
+    }
+
+    /// The part of the MIME type before the `/`, such as `image`
+    pub fn media_type(&self) -> Option<&'static str> {
+        self.mime.and_then(|mime| mime.split('/').next())
+    }
+
+    /// Whether the item is a picture
+    pub fn is_image(&self) -> bool {
+        self.media_type() == Some("image")
+    }
+}
+
+/// MIME type matching the extension of a file name
+fn mime_from_name(name: &OsStr) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "js" => "application/javascript",
+        "rs" => "text/rust",
+        "py" => "text/x-python",
+        "sh" => "application/x-shellscript",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/vnd.microsoft.icon",
+        "tif" | "tiff" => "image/tiff",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "flac" => "audio/flac",
+        "wav" => "audio/x-wav",
+        "mp4" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "xz" => "application/x-xz",
+        "bz2" => "application/x-bzip2",
+        "7z" => "application/x-7z-compressed",
+        "tar" => "application/x-tar",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        _ => return None,
+    })
+}
+
+/// MIME type recognized from the first bytes of a file
+#[cfg(feature = "sniff")]
+fn sniff(path: &Path) -> std::io::Result<Option<&'static str>> {
+    use std::io::Read;
+
+    let mut head = [0u8; 16];
+    let mut len = 0;
+    let mut file = std::fs::File::open(path)?;
+    while len < head.len() {
+        match file.read(&mut head[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    let head = &head[..len];
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"BZh", "application/x-bzip2"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+    ];
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return Ok(Some("image/webp"));
+    }
+    Ok(SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|(_, mime)| *mime))
+}
+
+/// Without the `sniff` feature files are only typed by name
+#[cfg(not(feature = "sniff"))]
+fn sniff(_path: &Path) -> std::io::Result<Option<&'static str>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileKind, FileType};
+    use std::ffi::OsStr;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detect() {
+        let dir = tempdir().unwrap();
+        let photo = dir.path().join("photo");
+        std::fs::write(&photo, b"\x89PNG\r\n\x1a\n").unwrap();
+        let detected = FileType::detect(&photo, OsStr::new("Photo.PNG")).unwrap();
+        assert_eq!(detected.kind, FileKind::File);
+        assert_eq!(detected.mime, Some("image/png"));
+        assert!(detected.is_image());
+
+        let unknown = FileType::detect(&photo, OsStr::new("photo")).unwrap();
+        assert_eq!(unknown.mime.is_some(), cfg!(feature = "sniff"));
+
+        let detected = FileType::detect(dir.path(), OsStr::new("dir.png")).unwrap();
+        assert_eq!(detected.kind, FileKind::Directory);
+        assert_eq!(detected.media_type(), Some("inode"));
+    }
+}
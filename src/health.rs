@@ -0,0 +1,181 @@
+use crate::date::now;
+use crate::location::{current_uid, is_writable};
+use crate::platform::{disk_space, MetadataExt, PermissionsExt};
+use crate::{DeletionDate, TrashEntries, TrashError, TrashLocation};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Something about a trash which makes, or will soon make, operations on
+/// it fail or leak what is in it, found by `diagnose`
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthFinding {
+    /// The trash, or its `files` or `info` directory, lets other users in,
+    /// where it should only be accessible to its owner
+    BadPermissions { path: PathBuf, mode: u32 },
+    /// The trash, or its `files` or `info` directory, belongs to another user
+    WrongOwner { path: PathBuf, uid: u32 },
+    /// Nothing can be trashed as the directory cannot be written to
+    NotWritable(PathBuf),
+    /// The trash, or its `files` or `info` directory, is a symlink, which
+    /// the spec does not allow as it may lead anywhere
+    Symlinked(PathBuf),
+    /// An item claims to have been deleted in the future, most likely by a
+    /// machine with its clock set wrong, which confuses age based cleanups
+    FutureDeletionDate {
+        internal_filename: OsString,
+        deleted_at: DeletionDate,
+    },
+    /// The filesystem holding the trash has less than 5% of its space left
+    LowSpace {
+        path: PathBuf,
+        available: u64,
+        total: u64,
+    },
+    /// An info file could not be read or parsed
+    UnreadableItem(String),
+}
+
+/// Looks for problems with the home trash, so that tools can warn about
+/// them before operations start failing
+/// A trash which does not exist yet only has problems if it cannot be created
+pub fn diagnose() -> Result<Vec<HealthFinding>, TrashError> {
+    diagnose_in(&TrashLocation::Home)
+}
+
+/// Looks for problems with the given trash
+pub fn diagnose_in(location: &TrashLocation) -> Result<Vec<HealthFinding>, TrashError> {
+    let trash_dir = location.trash_dir()?;
+    let mut findings = Vec::new();
+    if trash_dir.symlink_metadata().is_err() {
+        if let Some(parent) = trash_dir.ancestors().skip(1).find(|dir| dir.is_dir()) {
+            if !is_writable(parent) {
+                findings.push(HealthFinding::NotWritable(parent.to_path_buf()));
+            }
+        }
+        return Ok(findings);
+    }
+
+    for dir in &[
+        trash_dir.clone(),
+        trash_dir.join("files"),
+        trash_dir.join("info"),
+    ] {
+        check_dir(dir, &mut findings)?;
+    }
+    if let Ok((available, total)) = disk_space(&trash_dir) {
+        if available.saturating_mul(20) < total {
+            findings.push(HealthFinding::LowSpace {
+                path: trash_dir.clone(),
+                available,
+                total,
+            });
+        }
+    }
+
+    let now = now();
+    for item in TrashEntries::new(location, trash_dir)? {
+        match item {
+            Ok(item) if item.deleted_at() > now => {
+                findings.push(HealthFinding::FutureDeletionDate {
+                    internal_filename: item.internal_filename().to_os_string(),
+                    deleted_at: item.deleted_at(),
+                })
+            }
+            Ok(_) => {}
+            Err(e) => findings.push(HealthFinding::UnreadableItem(e.to_string())),
+        }
+    }
+    Ok(findings)
+}
+
+/// Checks one of the directories making up a trash, if it exists yet
+fn check_dir(dir: &Path, findings: &mut Vec<HealthFinding>) -> Result<(), TrashError> {
+    let meta = match dir.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if meta.file_type().is_symlink() {
+        findings.push(HealthFinding::Symlinked(dir.to_path_buf()));
+        return Ok(());
+    }
+    let mode = meta.permissions().mode() & 0o7777;
+    if mode & 0o077 != 0 {
+        findings.push(HealthFinding::BadPermissions {
+            path: dir.to_path_buf(),
+            mode,
+        });
+    }
+    if meta.uid() != current_uid() {
+        findings.push(HealthFinding::WrongOwner {
+            path: dir.to_path_buf(),
+            uid: meta.uid(),
+        });
+    }
+    if !is_writable(dir) {
+        findings.push(HealthFinding::NotWritable(dir.to_path_buf()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::platform::{symlink, PermissionsExt};
+    use crate::{diagnose_in, move_to_trash_in, HealthFinding, TrashLocation};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_diagnose() {
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().join("Trash"));
+        assert_eq!(diagnose_in(&location).unwrap(), Vec::<HealthFinding>::new());
+
+        for name in &["now", "later"] {
+            std::fs::write(file_dir.path().join(name), b"x").unwrap();
+            move_to_trash_in(file_dir.path().join(name), &location).unwrap();
+        }
+        let trash = trash_dir.path().join("Trash");
+        std::fs::set_permissions(trash.join("files"), PermissionsExt::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(trash.join("info"), PermissionsExt::from_mode(0o700)).unwrap();
+        std::fs::set_permissions(&trash, PermissionsExt::from_mode(0o700)).unwrap();
+        let info_file = trash.join("info/later.trashinfo");
+        let info = std::fs::read_to_string(&info_file).unwrap();
+        let dated: Vec<&str> = info
+            .lines()
+            .map(|line| {
+                if line.starts_with("DeletionDate=") {
+                    "DeletionDate=2999-01-01T00:00:00"
+                } else {
+                    line
+                }
+            })
+            .collect();
+        std::fs::write(&info_file, dated.join("\n")).unwrap();
+
+        let findings: Vec<_> = diagnose_in(&location)
+            .unwrap()
+            .into_iter()
+            // Depends on the disk the tests are run on
+            .filter(|finding| !matches!(finding, HealthFinding::LowSpace { .. }))
+            .collect();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0],
+            HealthFinding::BadPermissions {
+                path: trash.join("files"),
+                mode: 0o755
+            }
+        );
+        assert!(matches!(
+            &findings[1],
+            HealthFinding::FutureDeletionDate { internal_filename, .. } if internal_filename == "later"
+        ));
+
+        // The trash itself being a symlink is reported too
+        let link = trash_dir.path().join("Linked");
+        symlink(&trash, &link).unwrap();
+        let findings = diagnose_in(&TrashLocation::Custom(link.clone())).unwrap();
+        assert!(findings.contains(&HealthFinding::Symlinked(link)));
+    }
+}
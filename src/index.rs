@@ -0,0 +1,309 @@
+//! An SQLite index of a trash, behind the `sqlite` feature
+//! The info files stay the source of truth: the index only caches what
+//! they say, and `refresh` brings it up to date with them
+
+use crate::entries::info_name;
+use crate::location::expected_original;
+use crate::platform::OsStrExt;
+use crate::query::size_or_zero;
+use crate::{read_info_file, TrashError, TrashInfo, TrashLocation, TrashedItem};
+use rusqlite::{params, Connection, OpenFlags, Params};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Name of the index database inside of a trash directory
+const INDEX_FILE: &str = "trash-index.sqlite";
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS items (
+    name BLOB PRIMARY KEY,
+    info_mtime INTEGER NOT NULL,
+    file_name TEXT NOT NULL,
+    deleted TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    tags TEXT NOT NULL,
+    info TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS items_deleted ON items (deleted);
+CREATE INDEX IF NOT EXISTS items_size ON items (size);";
+
+/// How `TrashIndex::sorted` orders items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexOrder {
+    /// Most recently deleted first
+    Newest,
+    /// Least recently deleted first
+    Oldest,
+    /// Largest first
+    Largest,
+}
+
+/// Counts over a whole trash, answered from the index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStats {
+    /// Number of items
+    pub count: u64,
+    /// Combined size of the items in bytes
+    pub size: u64,
+}
+
+/// A database of the items in a trash, kept in the trash directory, for
+/// answering searches and sorts over hundreds of thousands of items
+/// without reading every info file
+pub struct TrashIndex {
+    db: Connection,
+    location: TrashLocation,
+    trash_dir: PathBuf,
+}
+
+impl std::fmt::Debug for TrashIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TrashIndex")
+            .field("location", &self.location)
+            .field("trash_dir", &self.trash_dir)
+            .finish()
+    }
+}
+
+impl TrashIndex {
+    /// Opens the index of a trash, creating it if there is none yet
+    /// Call `refresh` to bring it up to date with the info files
+    pub fn open(location: &TrashLocation) -> Result<Self, TrashError> {
+        let trash_dir = location.trash_dir()?;
+        std::fs::create_dir_all(&trash_dir)?;
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let db = Connection::open_with_flags(trash_dir.join(INDEX_FILE), flags)?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Self {
+            db,
+            location: location.clone(),
+            trash_dir,
+        })
+    }
+
+    /// Reads info files which are new or changed since the last refresh,
+    /// and forgets items whose info files are gone
+    /// Returns how many items were added, updated or removed
+    pub fn refresh(&mut self) -> Result<usize, TrashError> {
+        let mut known = self
+            .db
+            .prepare("SELECT name, info_mtime FROM items")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<Vec<u8>, i64>, _>>()?;
+
+        // Rolled back when dropped before the commit
+        let transaction = self.db.unchecked_transaction()?;
+        let mut changes = self.update_from_info_dir(&mut known)?;
+        let mut delete = transaction.prepare("DELETE FROM items WHERE name = ?1")?;
+        for name in known.keys() {
+            delete.execute([name])?;
+            changes += 1;
+        }
+        drop(delete);
+        transaction.commit()?;
+        Ok(changes)
+    }
+
+    /// Indexes info files which changed, taking the ones seen out of `known`
+    /// Returns how many were indexed
+    fn update_from_info_dir(&self, known: &mut HashMap<Vec<u8>, i64>) -> Result<usize, TrashError> {
+        let entries = match std::fs::read_dir(self.trash_dir.join("info")) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut upsert = self.db.prepare(
+            "INSERT OR REPLACE INTO items (name, info_mtime, file_name, deleted, size, tags, info)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        let mut changes = 0;
+        for entry in entries {
+            let info_path = entry?.path();
+            let name = match info_name(&info_path) {
+                Some(name) => name.as_bytes().to_vec(),
+                None => continue,
+            };
+            let meta = match info_path.metadata() {
+                Ok(meta) => meta,
+                // Purged while we were looking
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let mtime = modified_nanos(&meta);
+            if known.remove(&name) == Some(mtime) {
+                continue;
+            }
+            let content = read_info_file(&info_path, OsStr::from_bytes(&name))?;
+            let item = self.item(&name, &content)?;
+            let file_name = item.original_path().file_name().unwrap_or_default();
+            upsert.execute(params![
+                name,
+                mtime,
+                file_name.to_string_lossy(),
+                item.info().deletion_date_string(),
+                size_or_zero(&item)? as i64,
+                item.tags().join(";"),
+                content,
+            ])?;
+            changes += 1;
+        }
+        Ok(changes)
+    }
+
+    /// Number and combined size of the items, as of the last refresh
+    pub fn stats(&self) -> Result<IndexStats, TrashError> {
+        let (count, size): (i64, i64) = self.db.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM items",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(IndexStats {
+            count: count as u64,
+            size: size as u64,
+        })
+    }
+
+    /// Up to `limit` items in the given order
+    pub fn sorted(&self, order: IndexOrder, limit: usize) -> Result<Vec<TrashedItem>, TrashError> {
+        let order = match order {
+            IndexOrder::Newest => "deleted DESC",
+            IndexOrder::Oldest => "deleted ASC",
+            IndexOrder::Largest => "size DESC",
+        };
+        self.items(
+            &format!("SELECT name, info FROM items ORDER BY {} LIMIT ?1", order),
+            [limit.min(i64::MAX as usize) as i64],
+        )
+    }
+
+    /// Items whose original file name contains `text`, most recently
+    /// deleted first
+    pub fn search(&self, text: &str) -> Result<Vec<TrashedItem>, TrashError> {
+        self.items(
+            "SELECT name, info FROM items WHERE instr(file_name, ?1) > 0 ORDER BY deleted DESC",
+            [text],
+        )
+    }
+
+    /// Whether anything trashed from `path` is in the trash, as of the
+    /// last refresh
+    pub fn contains_original<P: AsRef<Path>>(&self, path: P) -> Result<bool, TrashError> {
+        let original = expected_original(path.as_ref());
+        let file_name = original.file_name().unwrap_or_default();
+        Ok(self
+            .items(
+                "SELECT name, info FROM items WHERE file_name = ?1",
+                [file_name.to_string_lossy()],
+            )?
+            .iter()
+            .any(|item| item.original_path() == original))
+    }
+
+    /// Items carrying a tag, most recently deleted first
+    pub fn tagged(&self, tag: &str) -> Result<Vec<TrashedItem>, TrashError> {
+        self.items(
+            "SELECT name, info FROM items WHERE instr(';' || tags || ';', ';' || ?1 || ';') > 0
+             ORDER BY deleted DESC",
+            [tag],
+        )
+    }
+
+    /// The items selected by a query for their name and info
+    fn items<P: Params>(&self, sql: &str, params: P) -> Result<Vec<TrashedItem>, TrashError> {
+        let mut select = self.db.prepare(sql)?;
+        let rows = select.query_map(params, |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut items = Vec::new();
+        for row in rows {
+            let (name, content) = row?;
+            items.push(self.item(&name, &content)?);
+        }
+        Ok(items)
+    }
+
+    fn item(&self, name: &[u8], content: &str) -> Result<TrashedItem, TrashError> {
+        let name = OsStr::from_bytes(name).to_os_string();
+        let info = TrashInfo::from_filename_and_content(name, content)?;
+        Ok(TrashedItem::new(
+            info,
+            self.location.clone(),
+            self.trash_dir.clone(),
+        ))
+    }
+}
+
+/// Modification time of an info file in nanoseconds since the epoch,
+/// which changes whenever the info file is rewritten
+fn modified_nanos(meta: &std::fs::Metadata) -> i64 {
+    meta.modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as i64)
+}
+
+impl From<rusqlite::Error> for TrashError {
+    fn from(e: rusqlite::Error) -> Self {
+        TrashError::Index(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndexOrder, IndexStats, TrashIndex};
+    use crate::tests::fixture;
+    use crate::{list_trash_in, move_to_trash_in};
+
+    #[test]
+    fn test_index() {
+        let (file_dir, trash_dir, location) = fixture();
+        for (name, content) in &[
+            ("small.txt", "1"),
+            ("large.iso", "123456"),
+            ("mid.txt", "123"),
+        ] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+
+        let mut index = TrashIndex::open(&location).unwrap();
+        assert_eq!(index.refresh().unwrap(), 3);
+        assert_eq!(index.refresh().unwrap(), 0);
+        assert_eq!(index.stats().unwrap(), IndexStats { count: 3, size: 10 });
+
+        let largest = index.sorted(IndexOrder::Largest, 2).unwrap();
+        let names: Vec<_> = largest
+            .iter()
+            .map(|item| item.internal_filename())
+            .collect();
+        assert_eq!(names, ["large.iso", "mid.txt"]);
+        assert_eq!(index.search(".txt").unwrap().len(), 2);
+        assert!(index
+            .contains_original(file_dir.path().join("mid.txt"))
+            .unwrap());
+        assert!(!index
+            .contains_original(trash_dir.path().join("mid.txt"))
+            .unwrap());
+
+        let mut item = index.search("mid").unwrap().remove(0);
+        item.add_tag("keep").unwrap();
+        list_trash_in(&location)
+            .unwrap()
+            .into_iter()
+            .find(|item| item.internal_filename() == "small.txt")
+            .unwrap()
+            .purge()
+            .unwrap();
+        // Sub-second timestamps tell the rewritten info file apart
+        assert_eq!(index.refresh().unwrap(), 2);
+        assert_eq!(
+            index.tagged("keep").unwrap()[0].internal_filename(),
+            "mid.txt"
+        );
+        assert!(index.tagged("kee").unwrap().is_empty());
+        assert_eq!(index.stats().unwrap(), IndexStats { count: 2, size: 9 });
+    }
+}
@@ -0,0 +1,1011 @@
+use crate::journal::{self, Journal, JournalOperation};
+use crate::location::{current_uid, is_writable, mount_root};
+use crate::platform::{self, MetadataExt, OsStrExt};
+use crate::{
+    copy_tree, count_tree, date, move_path, read_info_file, remove_path, rewrite_info_file,
+    CollisionStrategy, DeletionDate, EscapePolicy, FileType, RecordedPath, TransferStats,
+    TrashError, TrashInfo, TrashLocation, TrashOptions, DIR_KEY, OWNER_KEY, SIZE_KEY, TAGS_KEY,
+};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Local, Utc};
+use percent_encoding::percent_decode;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// What to do when something already exists where an item is restored to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreStrategy {
+    /// Fail with `TrashError::RestoreConflict`, leaving everything as is
+    FailOnConflict,
+    /// Permanently delete whatever is in the way
+    Overwrite,
+    /// Restore next to it under a free name such as `name.2`
+    Rename,
+}
+
+/// What to do about something already existing where an item is
+/// restored to, as decided for each item by the resolver given to
+/// `TrashedItem::restore_resolving`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreDecision {
+    /// Permanently delete whatever is in the way
+    Overwrite,
+    /// Restore to this path instead, asking again should it be taken too
+    RenameTo(PathBuf),
+    /// Leave the item in the trash
+    Skip,
+    /// Leave the item in the trash, failing with
+    /// `TrashError::RestoreAborted` so no more items are restored
+    Abort,
+}
+
+/// Whether an item can be put back where it was deleted from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restorability {
+    /// Restoring the item will work as is
+    Restorable,
+    /// Something else now exists at the original path
+    Conflict,
+    /// The directory the item was deleted from is gone
+    MissingParent,
+    /// The directory the item was deleted from may not be written to
+    ParentNotWritable,
+}
+
+/// A file or directory in a trash, along with its trash info
+#[derive(Debug, Clone)]
+pub struct TrashedItem {
+    info: TrashInfo,
+    location: TrashLocation,
+    trash_dir: PathBuf,
+    /// The info file's path, made absolute
+    original_path: PathBuf,
+    /// Where restoring or purging the item is recorded
+    journal: Option<Journal>,
+}
+
+impl TrashedItem {
+    pub(crate) fn new(info: TrashInfo, location: TrashLocation, trash_dir: PathBuf) -> Self {
+        let original_path = match info.recorded_path() {
+            RecordedPath::Absolute(path) => path.to_path_buf(),
+            RecordedPath::Relative(path) => topdir_of(&location, &trash_dir).join(path),
+        };
+        Self {
+            info,
+            location,
+            trash_dir,
+            original_path,
+            journal: None,
+        }
+    }
+
+    /// Records restoring or purging the item in `journal`, none by
+    /// default, see `TrashOptions::journal`
+    pub fn with_journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Reads the info of the item named `internal_filename` in a trash
+    pub(crate) fn load(
+        internal_filename: &OsStr,
+        location: &TrashLocation,
+    ) -> Result<Self, TrashError> {
+        Self::load_from(
+            internal_filename,
+            location,
+            location.trash_dir()?,
+            EscapePolicy::Strict,
+        )
+    }
+
+    /// Like `load`, for a trash whose directory is already known
+    pub(crate) fn load_from(
+        internal_filename: &OsStr,
+        location: &TrashLocation,
+        trash_dir: PathBuf,
+        policy: EscapePolicy,
+    ) -> Result<Self, TrashError> {
+        let mut info_filename = internal_filename.to_os_string();
+        info_filename.push(".trashinfo");
+        let info_path = trash_dir.join("info").join(info_filename);
+
+        let content = match read_info_file(&info_path, internal_filename) {
+            Ok(content) => content,
+            Err(TrashError::Io(ref e)) if e.kind() == ErrorKind::NotFound => {
+                return Err(TrashError::ItemNotFound(internal_filename.to_os_string()))
+            }
+            Err(e) => return Err(e),
+        };
+        let info = TrashInfo::from_filename_and_content_with(
+            internal_filename.to_os_string(),
+            &content,
+            policy,
+        )?;
+        Ok(Self::new(info, location.clone(), trash_dir))
+    }
+
+    /// The trash info as read from the info file
+    pub fn info(&self) -> &TrashInfo {
+        &self.info
+    }
+
+    /// Application specific value recorded when the item was trashed,
+    /// see `TrashOptions::metadata`
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.info.metadata(key)
+    }
+
+    /// Name of the item inside of the trash
+    pub fn internal_filename(&self) -> &OsStr {
+        &self.info.internal_filename
+    }
+
+    /// Where the item was deleted from
+    /// A path relative to the top directory of a device's trash, as the
+    /// spec allows there, is joined with that top directory
+    pub fn original_path(&self) -> &Path {
+        &self.original_path
+    }
+
+    /// When the item was deleted, in local time
+    pub fn deleted_at(&self) -> DeletionDate {
+        self.info.deletion_date
+    }
+
+    /// When the item was deleted, with the local offset attached
+    #[cfg(feature = "chrono")]
+    pub fn deleted_at_local(&self) -> DateTime<Local> {
+        self.info.deletion_date_local()
+    }
+
+    /// When the item was deleted, in UTC
+    #[cfg(feature = "chrono")]
+    pub fn deleted_at_utc(&self) -> DateTime<Utc> {
+        self.info.deletion_date_utc()
+    }
+
+    /// How long ago the item was deleted, zero if its date is ahead of
+    /// the clock
+    pub fn age(&self) -> Duration {
+        date::elapsed(self.info.deletion_date, date::now())
+    }
+
+    /// Which trash holds the item
+    pub fn location(&self) -> &TrashLocation {
+        &self.location
+    }
+
+    /// Root of the trash holding the item
+    pub fn trash_dir(&self) -> &Path {
+        &self.trash_dir
+    }
+
+    /// Current location of the item's data inside of the trash
+    pub fn trash_file(&self) -> PathBuf {
+        let mut trash_file = self.trash_dir.join("files");
+        if let Some(dir) = self.stored_dir() {
+            trash_file.push(dir);
+        }
+        trash_file.push(self.internal_filename());
+        trash_file
+    }
+
+    /// The directory inside of `files` the item is stored in when it was
+    /// trashed with `TrashLayout::Hierarchical`
+    /// Anything leading outside of `files` is ignored
+    fn stored_dir(&self) -> Option<PathBuf> {
+        let encoded = self.metadata(DIR_KEY)?;
+        let decoded: Vec<u8> = percent_decode(encoded.as_bytes()).collect();
+        let dir = PathBuf::from(OsStr::from_bytes(&decoded));
+        dir.components()
+            .all(|component| matches!(component, Component::Normal(_)))
+            .then_some(dir)
+    }
+
+    /// Removes the directories of a hierarchical layout left empty after
+    /// the item was taken out of them
+    fn prune_stored_dirs(&self) {
+        let files_dir = self.trash_dir.join("files");
+        let trash_file = self.trash_file();
+        let mut dir = trash_file.parent();
+        while let Some(current) = dir {
+            if current == files_dir || std::fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    /// Location of the item's info file
+    pub fn info_file(&self) -> PathBuf {
+        let mut info_filename = self.internal_filename().to_os_string();
+        info_filename.push(".trashinfo");
+        self.trash_dir.join("info").join(info_filename)
+    }
+
+    /// Size in bytes of the item, counting the data of every file in a
+    /// directory, as `TrashOptions::max_size` does
+    /// The size recorded when it was trashed is used if there is one
+    pub fn size(&self) -> Result<u64, TrashError> {
+        let trash_file = self.trash_file();
+        let measured = match self.recorded_size() {
+            Some(size) => trash_file.symlink_metadata().map(|_| size),
+            None => count_tree(&trash_file).map(|(bytes, _)| bytes),
+        };
+        match measured {
+            Ok(size) => Ok(size),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Size in bytes of the item as recorded when it was trashed, if it was,
+    /// see `TrashOptions::record_size`
+    pub fn recorded_size(&self) -> Option<u64> {
+        self.metadata(SIZE_KEY)?.parse().ok()
+    }
+
+    /// User and group which owned the item before it was trashed, as
+    /// recorded when root trashed it, or else those owning it in the trash
+    pub fn original_owner(&self) -> Option<(u32, u32)> {
+        let recorded = self
+            .metadata(OWNER_KEY)
+            .and_then(|owner| owner.split_once(':'))
+            .and_then(|(uid, gid)| Some((uid.parse().ok()?, gid.parse().ok()?)));
+        if recorded.is_some() {
+            return recorded;
+        }
+        let meta = self.trash_file().symlink_metadata().ok()?;
+        Some((meta.uid(), meta.gid()))
+    }
+
+    /// Labels attached to the item with `add_tag`, in the order they were added
+    pub fn tags(&self) -> Vec<&str> {
+        match self.metadata(TAGS_KEY) {
+            Some(tags) => tags.split(';').filter(|tag| !tag.is_empty()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether the item carries a tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+
+    /// Attaches a label such as `project-x` to the item, saved in its info file
+    /// Tags cannot be empty or hold `;` or line breaks
+    pub fn add_tag(&mut self, tag: &str) -> Result<(), TrashError> {
+        if tag.is_empty() || tag.contains([';', '\n', '\r']) {
+            return Err(TrashError::InvalidTag(tag.to_owned()));
+        }
+        if self.has_tag(tag) {
+            return Ok(());
+        }
+        let mut tags = self.tags();
+        tags.push(tag);
+        let tags = tags.join(";");
+        self.save_tags(tags)
+    }
+
+    /// Takes a label off the item, returning whether it had it
+    pub fn remove_tag(&mut self, tag: &str) -> Result<bool, TrashError> {
+        if !self.has_tag(tag) {
+            return Ok(false);
+        }
+        let tags: Vec<&str> = self
+            .tags()
+            .into_iter()
+            .filter(|kept| *kept != tag)
+            .collect();
+        let tags = tags.join(";");
+        self.save_tags(tags)?;
+        Ok(true)
+    }
+
+    /// Writes a new list of tags to the info file, then updates this item
+    fn save_tags(&mut self, tags: String) -> Result<(), TrashError> {
+        let mut info = self.info.clone();
+        info.extra.retain(|(key, _)| key != TAGS_KEY);
+        if !tags.is_empty() {
+            info.extra.push((TAGS_KEY.to_owned(), tags));
+        }
+        match rewrite_info_file(&self.info_file(), &info) {
+            Ok(()) => {
+                self.info = info;
+                Ok(())
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Detects what sort of file the item is, for showing icons or
+    /// filtering, from its original name and with the `sniff` feature
+    /// from its content
+    pub fn file_type(&self) -> Result<FileType, TrashError> {
+        let name = self.original_path().file_name().unwrap_or_default();
+        match FileType::detect(&self.trash_file(), name) {
+            Ok(file_type) => Ok(file_type),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Checks whether the item could be restored to its original path,
+    /// so items needing another destination can be told apart
+    pub fn restorability(&self) -> Restorability {
+        let original_path = self.original_path();
+        if original_path.symlink_metadata().is_ok() {
+            return Restorability::Conflict;
+        }
+        let parent = match original_path.parent() {
+            Some(parent) => parent,
+            None => return Restorability::MissingParent,
+        };
+        match parent.metadata() {
+            Ok(meta) if meta.is_dir() && is_writable(parent) => Restorability::Restorable,
+            Ok(meta) if meta.is_dir() => Restorability::ParentNotWritable,
+            Ok(_) => Restorability::MissingParent,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Restorability::MissingParent,
+            // Unreadable or out of reach for now, such as on a hung mount,
+            // which does not make it gone
+            Err(_) => Restorability::ParentNotWritable,
+        }
+    }
+
+    /// Whether the directory the item was deleted from is known to be
+    /// gone, rather than unreadable or out of reach for now, whose errors
+    /// are returned
+    pub(crate) fn origin_missing(&self) -> std::io::Result<bool> {
+        let parent = match self.original_path().parent() {
+            Some(parent) => parent,
+            None => return Ok(false),
+        };
+        match parent.symlink_metadata() {
+            Ok(_) => Ok(false),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the item would restore cleanly to its original path
+    pub fn is_restorable(&self) -> bool {
+        self.restorability() == Restorability::Restorable
+    }
+
+    /// Opens the item's content for reading, without restoring it
+    /// Only regular files can be previewed
+    pub fn open_preview(&self) -> Result<impl Read, TrashError> {
+        let trash_file = self.trash_file();
+        match trash_file.symlink_metadata() {
+            Ok(meta) if meta.is_file() => Ok(File::open(trash_file)?),
+            Ok(_) => Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "only regular files can be previewed",
+            )
+            .into()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads up to `max_bytes` from the start of the item's content
+    pub fn read_preview(&self, max_bytes: usize) -> Result<Vec<u8>, TrashError> {
+        let mut content = Vec::new();
+        self.open_preview()?
+            .take(max_bytes as u64)
+            .read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    /// Reads up to `max_bytes` from the start of the item's content as text
+    /// Invalid UTF-8, including a character cut off at the end, is replaced
+    pub fn read_preview_string(&self, max_bytes: usize) -> Result<String, TrashError> {
+        let content = self.read_preview(max_bytes)?;
+        Ok(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    /// Moves the item back to where it was deleted from
+    /// Returns the path the item was restored to
+    pub fn restore(self, strategy: RestoreStrategy) -> Result<PathBuf, TrashError> {
+        self.restore_with_stats(strategy).map(|(path, _)| path)
+    }
+
+    /// Like `restore`, also returning what it took to move the item back
+    pub fn restore_with_stats(
+        self,
+        strategy: RestoreStrategy,
+    ) -> Result<(PathBuf, TransferStats), TrashError> {
+        let started = std::time::Instant::now();
+        let (destination, aside) = clear_destination(self.original_path().to_path_buf(), strategy)?;
+        finish_aside(aside, self.restore_to(destination, started))
+    }
+
+    /// Like `restore`, asking `resolve` what to do when something already
+    /// exists where the item would go, given that path and the item
+    /// Returns `None` if the item was skipped and left in the trash
+    ///
+    /// ```no_run
+    /// use trash::{list_trash, RestoreDecision};
+    ///
+    /// for item in list_trash()? {
+    ///     let restored = item.restore_resolving(|existing, item| {
+    ///         println!("{} is in the way of {:?}", existing.display(), item.internal_filename());
+    ///         RestoreDecision::Skip
+    ///     })?;
+    ///     println!("restored to {:?}", restored);
+    /// }
+    /// # Ok::<(), trash::TrashError>(())
+    /// ```
+    pub fn restore_resolving<F>(self, mut resolve: F) -> Result<Option<PathBuf>, TrashError>
+    where
+        F: FnMut(&Path, &TrashedItem) -> RestoreDecision,
+    {
+        let started = std::time::Instant::now();
+        let mut destination = self.original_path().to_path_buf();
+        let mut aside = None;
+        loop {
+            match destination.symlink_metadata() {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == ErrorKind::NotFound => break,
+                Err(e) => return Err(e.into()),
+            }
+            match resolve(&destination, &self) {
+                RestoreDecision::Overwrite => {
+                    aside = Some(SetAside::new(&destination)?);
+                    break;
+                }
+                RestoreDecision::RenameTo(path) => destination = path,
+                RestoreDecision::Skip => return Ok(None),
+                RestoreDecision::Abort => return Err(TrashError::RestoreAborted(destination)),
+            }
+        }
+        finish_aside(aside, self.restore_to(destination, started)).map(|(path, _)| Some(path))
+    }
+
+    /// Moves the item to `destination`, which is free
+    fn restore_to(
+        self,
+        destination: PathBuf,
+        started: std::time::Instant,
+    ) -> Result<(PathBuf, TransferStats), TrashError> {
+        let owner = self.owner_to_restore();
+        let mut stats = move_path(
+            &self.trash_file(),
+            &destination,
+            &TrashOptions::default(),
+            true,
+            None,
+            None,
+            None,
+        )?;
+        std::fs::remove_file(self.info_file())?;
+        self.prune_stored_dirs();
+        if let Some((uid, gid)) = owner {
+            give_back(&destination, uid, gid)?;
+        }
+        journal::record(
+            self.journal.as_ref(),
+            JournalOperation::Restore,
+            &self.trash_dir,
+            self.original_path(),
+            &destination,
+        );
+        stats.elapsed = started.elapsed();
+        Ok((destination, stats))
+    }
+
+    /// Copies the item back to where it was deleted from, or to `destination`
+    /// when given, leaving it in the trash as well
+    /// Returns the path the copy was made at
+    pub fn restore_copy(
+        &self,
+        destination: Option<&Path>,
+        strategy: RestoreStrategy,
+    ) -> Result<PathBuf, TrashError> {
+        let trash_file = self.trash_file();
+        if trash_file.symlink_metadata().is_err() {
+            return Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            ));
+        }
+        let destination = destination.unwrap_or_else(|| self.original_path());
+        let (destination, aside) = clear_destination(destination.to_path_buf(), strategy)?;
+        let copied = copy_tree(&trash_file, &destination, &TrashOptions::default(), None);
+        finish_aside(aside, copied.map_err(TrashError::from))?;
+        if let Some((uid, gid)) = self.owner_to_restore() {
+            give_back(&destination, uid, gid)?;
+        }
+        journal::record(
+            self.journal.as_ref(),
+            JournalOperation::RestoreCopy,
+            &self.trash_dir,
+            self.original_path(),
+            &destination,
+        );
+        Ok(destination)
+    }
+
+    /// Who to hand the item back to when root restores it, as what root
+    /// copies belongs to root
+    fn owner_to_restore(&self) -> Option<(u32, u32)> {
+        owner_to_restore_as(current_uid(), self.original_owner())
+    }
+
+    /// Permanently deletes the item
+    pub fn purge(self) -> Result<(), TrashError> {
+        // Remove the file first so a failure never leaves an orphan without info
+        let trash_file = self.trash_file();
+        match trash_file.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(&trash_file)?,
+            Ok(_) => std::fs::remove_file(&trash_file)?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        match std::fs::remove_file(self.info_file()) {
+            Ok(()) => {
+                self.prune_stored_dirs();
+                journal::record(
+                    self.journal.as_ref(),
+                    JournalOperation::Purge,
+                    &self.trash_dir,
+                    self.original_path(),
+                    &trash_file,
+                );
+                Ok(())
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Err(TrashError::ItemNotFound(
+                self.internal_filename().to_os_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Where to restore to, after making room there as `strategy` says,
+/// along with what was in the way when it was set aside to overwrite it
+fn clear_destination(
+    path: PathBuf,
+    strategy: RestoreStrategy,
+) -> Result<(PathBuf, Option<SetAside>), TrashError> {
+    match path.symlink_metadata() {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok((path, None)),
+        Err(e) => Err(e.into()),
+        Ok(_) => match strategy {
+            RestoreStrategy::FailOnConflict => Err(TrashError::RestoreConflict(path)),
+            RestoreStrategy::Overwrite => {
+                let aside = SetAside::new(&path)?;
+                Ok((path, Some(aside)))
+            }
+            RestoreStrategy::Rename => Ok((free_name(&path), None)),
+        },
+    }
+}
+
+/// What is in the way of a restore which overwrites it, renamed to a
+/// hidden name next to it until the item is in its place, so that it is
+/// only lost once the item has made it there
+struct SetAside {
+    path: PathBuf,
+    aside: PathBuf,
+}
+
+impl SetAside {
+    fn new(path: &Path) -> std::io::Result<SetAside> {
+        let mut base = OsString::from(".");
+        base.push(path.file_name().unwrap_or_default());
+        base.push(format!(".{}.overwritten", std::process::id()));
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let aside =
+                path.with_file_name(CollisionStrategy::Counter.internal_name(&base, attempt));
+            if aside.symlink_metadata().is_err() {
+                std::fs::rename(path, &aside)?;
+                return Ok(SetAside {
+                    path: path.to_path_buf(),
+                    aside,
+                });
+            }
+        }
+    }
+}
+
+/// Removes what was set aside once `result` tells the item is in place,
+/// or else puts it back where it was, unless the item got there after all
+/// and only what follows the move failed
+fn finish_aside<T>(
+    aside: Option<SetAside>,
+    result: Result<T, TrashError>,
+) -> Result<T, TrashError> {
+    let aside = match aside {
+        Some(aside) => aside,
+        None => return result,
+    };
+    match result {
+        Ok(value) => {
+            remove_path(&aside.aside)?;
+            Ok(value)
+        }
+        Err(e) => {
+            if aside.path.symlink_metadata().is_err() {
+                let _ = std::fs::rename(&aside.aside, &aside.path);
+            } else {
+                let _ = remove_path(&aside.aside);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Who to hand an item owned by `original` back to when `uid` restores
+/// it: only root can, and only to someone else
+fn owner_to_restore_as(uid: u32, original: Option<(u32, u32)>) -> Option<(u32, u32)> {
+    if uid != 0 {
+        return None;
+    }
+    original.filter(|&(uid, _)| uid != 0)
+}
+
+/// Hands what root restored back to the user who owned it, leaving alone
+/// anything inside of it which belongs to someone other than root
+fn give_back(path: &Path, uid: u32, gid: u32) -> std::io::Result<()> {
+    let meta = path.symlink_metadata()?;
+    if meta.uid() == 0 {
+        platform::lchown(path, uid, gid)?;
+    }
+    if meta.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            give_back(&entry?.path(), uid, gid)?;
+        }
+    }
+    Ok(())
+}
+
+/// First of `path.2`, `path.3`, ... which does not exist yet,
+/// numbered the same way as names inside of the trash
+fn free_name(path: &Path) -> PathBuf {
+    let base = path.file_name().unwrap_or_default();
+    let mut attempt = 1u32;
+    loop {
+        attempt += 1;
+        let candidate =
+            path.with_file_name(CollisionStrategy::Counter.internal_name(base, attempt));
+        if candidate.symlink_metadata().is_err() {
+            return candidate;
+        }
+    }
+}
+
+/// The top directory relative paths in a trash's info files are relative to
+fn topdir_of(location: &TrashLocation, trash_dir: &Path) -> PathBuf {
+    match location {
+        TrashLocation::TopDir(topdir) | TrashLocation::TopDirUser(topdir) => topdir.clone(),
+        // Recognize `$topdir/.Trash-$uid` and `$topdir/.Trash/$uid` by name
+        _ => {
+            let name = trash_dir.file_name().unwrap_or_default().as_bytes();
+            let parent = trash_dir.parent().unwrap_or(trash_dir);
+            if name.starts_with(b".Trash-") {
+                parent.to_path_buf()
+            } else if parent.file_name() == Some(OsStr::new(".Trash")) {
+                parent.parent().unwrap_or(parent).to_path_buf()
+            } else {
+                mount_root(trash_dir).unwrap_or_else(|_| PathBuf::from("/"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        list_trash_in, move_to_trash_in, move_to_trash_with, Restorability, RestoreDecision,
+        RestoreStrategy, TrashError, TrashLocation, TrashOptions,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_restore_strategies() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let file_path = file_dir.path().join("notes.txt");
+        std::fs::write(&file_path, b"old").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+        std::fs::write(&file_path, b"new").unwrap();
+
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.original_path(), file_path);
+        assert_eq!(item.size().unwrap(), 3);
+        match item.clone().restore(RestoreStrategy::FailOnConflict) {
+            Err(TrashError::RestoreConflict(path)) => assert_eq!(path, file_path),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        let (restored, stats) = item.restore_with_stats(RestoreStrategy::Rename).unwrap();
+        assert_eq!(restored, file_dir.path().join("notes.txt.2"));
+        assert_eq!((stats.bytes, stats.files, stats.copied), (3, 1, false));
+        assert_eq!(std::fs::read(&restored).unwrap(), b"old");
+
+        move_to_trash_in(&restored, &location).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        std::fs::write(&restored, b"newer").unwrap();
+        item.restore(RestoreStrategy::Overwrite).unwrap();
+        assert_eq!(std::fs::read(&restored).unwrap(), b"old");
+
+        move_to_trash_in(&restored, &location).unwrap();
+        list_trash_in(&location).unwrap().remove(0).purge().unwrap();
+        assert!(list_trash_in(&location).unwrap().is_empty());
+        assert!(!restored.exists());
+    }
+
+    #[test]
+    fn test_failed_overwrite_keeps_existing() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let file_path = file_dir.path().join("notes.txt");
+        std::fs::write(&file_path, b"old").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+        std::fs::write(&file_path, b"new").unwrap();
+
+        // With its data gone from the trash, the move back fails
+        let item = list_trash_in(&location).unwrap().remove(0);
+        std::fs::remove_file(item.trash_file()).unwrap();
+        assert!(item.restore_copy(None, RestoreStrategy::Overwrite).is_err());
+        assert!(item.clone().restore(RestoreStrategy::Overwrite).is_err());
+        assert!(item
+            .restore_resolving(|_, _| RestoreDecision::Overwrite)
+            .is_err());
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(file_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_restore_resolving() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let file_path = file_dir.path().join("notes.txt");
+        let taken = file_dir.path().join("taken.txt");
+        let free = file_dir.path().join("free.txt");
+        std::fs::write(&file_path, b"old").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+        std::fs::write(&file_path, b"new").unwrap();
+        std::fs::write(&taken, b"taken").unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+
+        let skipped = item.clone().restore_resolving(|existing, conflicting| {
+            assert_eq!(existing, file_path);
+            assert_eq!(conflicting.original_path(), file_path);
+            RestoreDecision::Skip
+        });
+        assert_eq!(skipped.unwrap(), None);
+        match item
+            .clone()
+            .restore_resolving(|_, _| RestoreDecision::Abort)
+        {
+            Err(TrashError::RestoreAborted(path)) => assert_eq!(path, file_path),
+            other => panic!("expected an abort, got {:?}", other),
+        }
+
+        let mut asked = Vec::new();
+        let restored = item
+            .restore_resolving(|existing, _| {
+                asked.push(existing.to_path_buf());
+                if existing == file_path {
+                    RestoreDecision::RenameTo(taken.clone())
+                } else {
+                    RestoreDecision::RenameTo(free.clone())
+                }
+            })
+            .unwrap();
+        assert_eq!(restored, Some(free.clone()));
+        assert_eq!(asked, [file_path.clone(), taken.clone()]);
+        assert_eq!(std::fs::read(&free).unwrap(), b"old");
+        assert_eq!(std::fs::read(&taken).unwrap(), b"taken");
+
+        move_to_trash_in(&free, &location).unwrap();
+        std::fs::write(&free, b"newer").unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        let restored = item
+            .restore_resolving(|_, _| RestoreDecision::Overwrite)
+            .unwrap();
+        assert_eq!(restored, Some(free.clone()));
+        assert_eq!(std::fs::read(&free).unwrap(), b"old");
+        assert!(list_trash_in(&location).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_owner_to_restore() {
+        use super::owner_to_restore_as;
+
+        assert_eq!(
+            owner_to_restore_as(0, Some((1234, 2345))),
+            Some((1234, 2345))
+        );
+        assert_eq!(owner_to_restore_as(0, Some((0, 0))), None);
+        assert_eq!(owner_to_restore_as(0, None), None);
+        assert_eq!(owner_to_restore_as(1000, Some((1234, 2345))), None);
+
+        // As root records it when trashing another user's file
+        let (_file_dir, trash_dir, location) = fixture();
+        std::fs::create_dir(trash_dir.path().join("info")).unwrap();
+        std::fs::create_dir(trash_dir.path().join("files")).unwrap();
+        std::fs::write(trash_dir.path().join("files/file"), b"x").unwrap();
+        std::fs::write(
+            trash_dir.path().join("info/file.trashinfo"),
+            "[Trash Info]\nPath=/tmp/file\nDeletionDate=2020-01-02T03:04:05\n\
+             X-Trash-Owner=1234:2345\n",
+        )
+        .unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.original_owner(), Some((1234, 2345)));
+    }
+
+    #[test]
+    #[ignore = "changes file owners, which needs root"]
+    fn test_restore_gives_back_to_owner() {
+        use crate::platform::{lchown, MetadataExt};
+
+        let (file_dir, _trash_dir, location) = fixture();
+        let dir_path = file_dir.path().join("project");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("main.rs"), b"fn main() {}").unwrap();
+        lchown(&dir_path, 1234, 2345).unwrap();
+        lchown(&dir_path.join("main.rs"), 1234, 2345).unwrap();
+        move_to_trash_in(&dir_path, &location).unwrap();
+
+        // As if copied into the trash by root
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.metadata("X-Trash-Owner"), Some("1234:2345"));
+        lchown(&item.trash_file(), 0, 0).unwrap();
+        lchown(&item.trash_file().join("main.rs"), 0, 0).unwrap();
+        assert_eq!(item.original_owner(), Some((1234, 2345)));
+
+        let restored = item.restore(RestoreStrategy::FailOnConflict).unwrap();
+        for path in &[restored.clone(), restored.join("main.rs")] {
+            let meta = path.symlink_metadata().unwrap();
+            assert_eq!((meta.uid(), meta.gid()), (1234, 2345));
+        }
+    }
+
+    #[test]
+    fn test_restore_copy() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let dir_path = file_dir.path().join("project");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("main.rs"), b"fn main() {}").unwrap();
+        move_to_trash_in(&dir_path, &location).unwrap();
+
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(
+            item.restore_copy(None, RestoreStrategy::FailOnConflict)
+                .unwrap(),
+            dir_path
+        );
+        let elsewhere = file_dir.path().join("inspect");
+        let copied = item
+            .restore_copy(Some(&elsewhere), RestoreStrategy::FailOnConflict)
+            .unwrap();
+        assert_eq!(copied, elsewhere);
+        assert!(matches!(
+            item.restore_copy(None, RestoreStrategy::FailOnConflict),
+            Err(TrashError::RestoreConflict(_))
+        ));
+        let renamed = item.restore_copy(None, RestoreStrategy::Rename).unwrap();
+        assert_eq!(renamed, file_dir.path().join("project.2"));
+        for restored in &[&dir_path, &elsewhere, &renamed] {
+            assert_eq!(
+                std::fs::read(restored.join("main.rs")).unwrap(),
+                b"fn main() {}"
+            );
+        }
+        assert!(item.trash_file().join("main.rs").exists());
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_recorded_size() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let dir_path = file_dir.path().join("photos");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("a.jpg"), b"12345").unwrap();
+        let (dir_size, _) = crate::count_tree(&dir_path).unwrap();
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .record_size(true);
+        move_to_trash_with(&dir_path, &options).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.recorded_size(), Some(dir_size));
+        // Adding to the trashed copy goes unnoticed, as the walk is skipped
+        std::fs::write(item.trash_file().join("b.jpg"), b"67").unwrap();
+        assert_eq!(item.size().unwrap(), dir_size);
+        item.purge().unwrap();
+
+        std::fs::write(&dir_path, b"123").unwrap();
+        move_to_trash_in(&dir_path, &location).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.recorded_size(), None);
+        assert_eq!(item.size().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_relative_original_path() {
+        let topdir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::TopDir(topdir.path().to_path_buf());
+        let trash_dir = location.trash_dir().unwrap();
+        std::fs::create_dir_all(trash_dir.join("info")).unwrap();
+        std::fs::create_dir_all(trash_dir.join("files")).unwrap();
+        std::fs::create_dir(topdir.path().join("photos")).unwrap();
+        std::fs::write(trash_dir.join("files/cat.jpg"), b"meow").unwrap();
+        std::fs::write(
+            trash_dir.join("info/cat.jpg.trashinfo"),
+            "[Trash Info]\nPath=photos/cat.jpg\nDeletionDate=2020-01-02T03:04:05\n",
+        )
+        .unwrap();
+
+        let expected = topdir.path().join("photos/cat.jpg");
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.original_path(), expected);
+        // Found the same way when the trash is given by its directory
+        let custom = TrashLocation::Custom(trash_dir.clone());
+        let item = list_trash_in(&custom).unwrap().remove(0);
+        assert_eq!(item.original_path(), expected);
+
+        assert_eq!(
+            item.restore(RestoreStrategy::FailOnConflict).unwrap(),
+            expected
+        );
+        assert_eq!(std::fs::read(&expected).unwrap(), b"meow");
+    }
+
+    #[test]
+    fn test_preview() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let file_path = file_dir.path().join("caf\u{e9}.txt");
+        std::fs::write(&file_path, "caf\u{e9} au lait").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+        std::fs::create_dir(&file_path).unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+
+        let items = list_trash_in(&location).unwrap();
+        let (dirs, files): (Vec<_>, Vec<_>) =
+            items.iter().partition(|item| item.trash_file().is_dir());
+        assert_eq!(files[0].read_preview(3).unwrap(), b"caf");
+        assert_eq!(files[0].read_preview_string(4).unwrap(), "caf\u{fffd}");
+        assert_eq!(
+            files[0].read_preview_string(100).unwrap(),
+            "caf\u{e9} au lait"
+        );
+        assert!(dirs[0].open_preview().is_err());
+    }
+
+    #[test]
+    fn test_restorability() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let sub_dir = file_dir.path().join("sub");
+        std::fs::create_dir(&sub_dir).unwrap();
+        let file_path = sub_dir.join("file");
+        std::fs::write(&file_path, b"x").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert!(item.is_restorable());
+        std::fs::write(&file_path, b"y").unwrap();
+        assert_eq!(item.restorability(), Restorability::Conflict);
+        std::fs::remove_dir_all(&sub_dir).unwrap();
+        assert_eq!(item.restorability(), Restorability::MissingParent);
+    }
+}
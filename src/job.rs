@@ -0,0 +1,139 @@
+use crate::{move_to_trash_with, TrashError, TrashFiles, TrashOptions, TrashProgress};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// A file being trashed on a thread of its own, by `move_to_trash_background`
+///
+/// ```no_run
+/// let mut job = trash::move_to_trash_background("huge-directory");
+/// while job.poll().is_none() {
+///     let progress = job.progress();
+///     println!("{} of {} bytes", progress.copied_bytes, progress.total_bytes);
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// let files = job.wait()?;
+/// # Ok::<(), trash::TrashError>(())
+/// ```
+#[derive(Debug)]
+pub struct TrashJob {
+    path: PathBuf,
+    worker: Option<JoinHandle<Result<TrashFiles, TrashError>>>,
+    result: Option<Result<TrashFiles, TrashError>>,
+    copied_bytes: Arc<AtomicU64>,
+    total_bytes: Arc<AtomicU64>,
+}
+
+/// Starts moving a file or directory to the trash without waiting for it,
+/// for apps which must stay responsive while a huge directory is copied
+/// to the trash of another filesystem
+pub fn move_to_trash_background<P: Into<PathBuf>>(path: P) -> TrashJob {
+    move_to_trash_background_with(path, TrashOptions::default())
+}
+
+/// Starts moving a file or directory to the trash as configured by
+/// `options`, without waiting for it
+/// A callback given to `TrashOptions::on_progress` is called on the
+/// job's thread
+pub fn move_to_trash_background_with<P: Into<PathBuf>>(path: P, options: TrashOptions) -> TrashJob {
+    let path = path.into();
+    let copied_bytes = Arc::new(AtomicU64::new(0));
+    let total_bytes = Arc::new(AtomicU64::new(0));
+    let (copied, total) = (Arc::clone(&copied_bytes), Arc::clone(&total_bytes));
+    let callback = options.progress.clone();
+    let options = options.on_progress(move |progress| {
+        copied.store(progress.copied_bytes, Ordering::Relaxed);
+        total.store(progress.total_bytes, Ordering::Relaxed);
+        if let Some(callback) = &callback {
+            callback(progress);
+        }
+    });
+    let worker_path = path.clone();
+    let worker = std::thread::spawn(move || move_to_trash_with(worker_path, &options));
+    TrashJob {
+        path,
+        worker: Some(worker),
+        result: None,
+        copied_bytes,
+        total_bytes,
+    }
+}
+
+impl TrashJob {
+    /// The path being trashed
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// How far along copying is, which stays at zero when the file is
+    /// simply renamed into the trash
+    pub fn progress(&self) -> TrashProgress {
+        TrashProgress {
+            copied_bytes: self.copied_bytes.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The outcome if the job is done, without blocking
+    pub fn poll(&mut self) -> Option<&Result<TrashFiles, TrashError>> {
+        if self
+            .worker
+            .as_ref()
+            .is_some_and(|worker| worker.is_finished())
+        {
+            self.join();
+        }
+        self.result.as_ref()
+    }
+
+    /// Blocks until the job is done, returning its outcome
+    pub fn wait(mut self) -> Result<TrashFiles, TrashError> {
+        self.join();
+        self.result.take().expect("joined job without a result")
+    }
+
+    fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            let result = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            self.result = Some(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        list_trash_in, move_to_trash_background, move_to_trash_background_with, TrashError,
+        TrashOptions,
+    };
+
+    #[test]
+    fn test_background_job() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let dir = file_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("file"), vec![1u8; 5000]).unwrap();
+
+        // Copied, so that progress is reported
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .keep_original(true);
+        let mut job = move_to_trash_background_with(&dir, options);
+        assert_eq!(job.path(), dir);
+        while job.poll().is_none() {
+            std::thread::yield_now();
+        }
+        assert!(job.poll().unwrap().is_ok());
+        assert_eq!(job.progress().copied_bytes, 5000);
+        let files = job.wait().unwrap();
+        assert!(files.trash_file.join("file").exists());
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+
+        let job = move_to_trash_background(file_dir.path().join("missing"));
+        assert!(matches!(job.wait(), Err(TrashError::SourceNotFound(_))));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::escape::{decode_os, encode_os};
+use crate::{
+    date, move_to_trash_with, DeletionDate, EscapePolicy, RestoreStrategy, TrashError,
+    TrashLocation, TrashOptions, TrashedItem,
+};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the journal kept inside of each trash with `Journal::InTrash`
+pub const JOURNAL_FILE: &str = "journal.log";
+
+/// Where to record what was trashed, restored and purged, so it can be
+/// found out later what deleted a file and when, see `TrashOptions::journal`
+/// Entries are only ever appended, one line each. Journaling is best
+/// effort: an operation which succeeded is not reported as failed for
+/// want of a journal entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Journal {
+    /// `journal.log` in the trash directory each operation touched
+    InTrash,
+    /// One journal for every trash
+    File(PathBuf),
+}
+
+/// What was done to an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalOperation {
+    /// Moved into the trash
+    Trash,
+    /// Copied into the trash, see `copy_to_trash`
+    Copy,
+    /// Moved back out of the trash
+    Restore,
+    /// Copied back out of the trash, see `TrashedItem::restore_copy`
+    RestoreCopy,
+    /// Permanently deleted
+    Purge,
+    /// The latest operation not undone yet was undone by `undo_in`
+    Undo,
+}
+
+impl JournalOperation {
+    fn name(self) -> &'static str {
+        match self {
+            JournalOperation::Trash => "trash",
+            JournalOperation::Copy => "copy",
+            JournalOperation::Restore => "restore",
+            JournalOperation::RestoreCopy => "restore-copy",
+            JournalOperation::Purge => "purge",
+            JournalOperation::Undo => "undo",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "trash" => JournalOperation::Trash,
+            "copy" => JournalOperation::Copy,
+            "restore" => JournalOperation::Restore,
+            "restore-copy" => JournalOperation::RestoreCopy,
+            "purge" => JournalOperation::Purge,
+            "undo" => JournalOperation::Undo,
+            _ => return None,
+        })
+    }
+}
+
+/// One line of a journal
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// When it happened, in local time
+    pub date: DeletionDate,
+    /// What was done
+    pub operation: JournalOperation,
+    /// Where the item was deleted from
+    pub original_path: PathBuf,
+    /// Where the item went: its file in the trash, or where it was
+    /// restored to
+    pub path: PathBuf,
+    /// Root of the trash involved
+    pub trash_dir: PathBuf,
+}
+
+/// What became of one operation walked back by `undo_in`
+#[derive(Debug)]
+pub struct UndoOutcome {
+    /// The operation undone
+    pub entry: JournalEntry,
+    /// Where the item went by undoing it, or why it could not be undone
+    pub result: Result<PathBuf, TrashError>,
+}
+
+/// Reads back a journal, oldest entry first
+/// Lines which cannot be understood are skipped
+pub fn read_journal<P: AsRef<Path>>(path: P) -> Result<Vec<JournalEntry>, TrashError> {
+    let content = std::fs::read(path)?;
+    Ok(content
+        .split(|&b| b == b'\n')
+        .filter_map(parse_entry)
+        .collect())
+}
+
+/// Walks back the last `n` operations in a journal, latest first: trashed
+/// items are restored, restored items are trashed again and copies in
+/// the trash are purged, while purges cannot be undone and are passed over
+/// Each undone operation is journaled as `Undo`, so the next call goes on
+/// from where this one stopped, while the steps taken to undo it are not
+/// journaled themselves. Undoing stops at the first operation which
+/// cannot be undone, whose outcome is the last one returned
+pub fn undo_in<P: AsRef<Path>>(journal: P, n: usize) -> Result<Vec<UndoOutcome>, TrashError> {
+    let journal = journal.as_ref();
+    // Undo entries cancel the latest operation not undone before them
+    let mut undoable = Vec::new();
+    for entry in read_journal(journal)? {
+        match entry.operation {
+            JournalOperation::Purge => {}
+            JournalOperation::Undo => {
+                undoable.pop();
+            }
+            _ => undoable.push(entry),
+        }
+    }
+
+    let mut outcomes = Vec::new();
+    for entry in undoable.into_iter().rev().take(n) {
+        let result = undo_entry(&entry);
+        let failed = result.is_err();
+        if let Ok(path) = &result {
+            let line = format_entry(
+                JournalOperation::Undo,
+                &entry.trash_dir,
+                &entry.original_path,
+                path,
+            );
+            append(journal, &line)?;
+        }
+        outcomes.push(UndoOutcome { entry, result });
+        if failed {
+            break;
+        }
+    }
+    Ok(outcomes)
+}
+
+fn undo_entry(entry: &JournalEntry) -> Result<PathBuf, TrashError> {
+    match entry.operation {
+        JournalOperation::Trash => trashed_item(entry)?.restore(RestoreStrategy::FailOnConflict),
+        JournalOperation::Copy => {
+            trashed_item(entry)?.purge()?;
+            Ok(entry.path.clone())
+        }
+        JournalOperation::Restore | JournalOperation::RestoreCopy => {
+            let options =
+                TrashOptions::new().location(TrashLocation::Custom(entry.trash_dir.clone()));
+            Ok(move_to_trash_with(&entry.path, &options)?.trash_file)
+        }
+        JournalOperation::Purge | JournalOperation::Undo => {
+            unreachable!("never undone")
+        }
+    }
+}
+
+/// The item a trash or copy entry put in the trash
+fn trashed_item(entry: &JournalEntry) -> Result<TrashedItem, TrashError> {
+    let name = entry.path.file_name().unwrap_or_default();
+    let location = TrashLocation::Custom(entry.trash_dir.clone());
+    let item = TrashedItem::load_from(
+        name,
+        &location,
+        entry.trash_dir.clone(),
+        EscapePolicy::Strict,
+    )?;
+    if item.trash_file() != entry.path {
+        return Err(TrashError::ItemNotFound(name.to_os_string()));
+    }
+    Ok(item)
+}
+
+/// Appends an entry to `journal`, if there is one
+pub(crate) fn record(
+    journal: Option<&Journal>,
+    operation: JournalOperation,
+    trash_dir: &Path,
+    original_path: &Path,
+    path: &Path,
+) {
+    let journal_path = match journal {
+        None => return,
+        Some(Journal::InTrash) => trash_dir.join(JOURNAL_FILE),
+        Some(Journal::File(path)) => path.clone(),
+    };
+    let line = format_entry(operation, trash_dir, original_path, path);
+    let _ = append(&journal_path, &line);
+}
+
+fn format_entry(
+    operation: JournalOperation,
+    trash_dir: &Path,
+    original_path: &Path,
+    path: &Path,
+) -> String {
+    let encode = |path: &Path| encode_os(path.as_os_str());
+    format!(
+        "{}\t{}\t{}\t{}\t{}\n",
+        date::format(&date::now()),
+        operation.name(),
+        encode(original_path),
+        encode(path),
+        encode(trash_dir),
+    )
+}
+
+/// Written with a single call, so entries of several processes
+/// appending at once do not interleave
+fn append(journal_path: &Path, line: &str) -> std::io::Result<()> {
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(journal_path)?
+        .write_all(line.as_bytes())
+}
+
+fn parse_entry(line: &[u8]) -> Option<JournalEntry> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut fields = line.split('\t');
+    let date = date::parse(fields.next()?)?;
+    let operation = JournalOperation::from_name(fields.next()?)?;
+    let decode = |field: &str| PathBuf::from(decode_os(field));
+    let original_path = decode(fields.next()?);
+    let path = decode(fields.next()?);
+    let trash_dir = decode(fields.next()?);
+    Some(JournalEntry {
+        date,
+        operation,
+        original_path,
+        path,
+        trash_dir,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        list_trash_in, move_to_trash_in, move_to_trash_with, read_journal, undo_in, Journal,
+        JournalOperation, RestoreStrategy, Trash, TrashError, TrashOptions, JOURNAL_FILE,
+    };
+
+    #[test]
+    fn test_journal() {
+        let (file_dir, trash_dir, location) = fixture();
+        let file_dir_path = file_dir.path().canonicalize().unwrap();
+        let path = file_dir_path.join("a\tb.txt");
+        std::fs::write(&path, b"x").unwrap();
+
+        let journal = Some(Journal::InTrash);
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .journal(journal.clone());
+        let files = move_to_trash_with(&path, &options).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        item.with_journal(journal.clone())
+            .restore(RestoreStrategy::FailOnConflict)
+            .unwrap();
+        move_to_trash_with(&path, &options).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        item.with_journal(journal).purge().unwrap();
+
+        let elsewhere = file_dir.path().join("journal");
+        std::fs::write(&path, b"y").unwrap();
+        let copy = TrashOptions::new()
+            .location(location.clone())
+            .keep_original(true)
+            .journal(Some(Journal::File(elsewhere.clone())));
+        move_to_trash_with(&path, &copy).unwrap();
+        // Without a journal in the options nothing is recorded
+        move_to_trash_in(&path, &location).unwrap();
+
+        let entries = read_journal(trash_dir.path().join(JOURNAL_FILE)).unwrap();
+        let operations: Vec<_> = entries.iter().map(|entry| entry.operation).collect();
+        assert_eq!(
+            operations,
+            [
+                JournalOperation::Trash,
+                JournalOperation::Restore,
+                JournalOperation::Trash,
+                JournalOperation::Purge
+            ]
+        );
+        assert!(entries.iter().all(|entry| entry.original_path == path));
+        assert_eq!(entries[0].path, files.trash_file);
+        assert_eq!(entries[1].path, path);
+        let entries = read_journal(&elsewhere).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].operation, JournalOperation::Copy);
+    }
+
+    #[test]
+    fn test_undo() {
+        let (file_dir, trash_dir, location) = fixture();
+        let journal = trash_dir.path().join(JOURNAL_FILE);
+        let file_dir_path = file_dir.path().canonicalize().unwrap();
+        let (a, b) = (file_dir_path.join("a"), file_dir_path.join("b"));
+        std::fs::write(&a, b"a").unwrap();
+        std::fs::write(&b, b"b").unwrap();
+
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .journal(Some(Journal::InTrash));
+        move_to_trash_with(&a, &options).unwrap();
+        move_to_trash_with(&b, &options).unwrap();
+        let item = list_trash_in(&location)
+            .unwrap()
+            .into_iter()
+            .find(|item| item.original_path() == b)
+            .unwrap();
+        item.with_journal(Some(Journal::InTrash))
+            .restore(RestoreStrategy::FailOnConflict)
+            .unwrap();
+
+        // Trashes b again, then restores it
+        let outcomes = undo_in(&journal, 2).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].entry.operation, JournalOperation::Restore);
+        assert_eq!(outcomes[1].entry.operation, JournalOperation::Trash);
+        assert_eq!(outcomes[1].result.as_ref().unwrap(), &b);
+        assert!(b.exists() && !a.exists());
+
+        let outcomes = undo_in(&journal, 5).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].result.as_ref().unwrap(), &a);
+        assert!(a.exists());
+        assert!(list_trash_in(&location).unwrap().is_empty());
+        assert!(undo_in(&journal, 1).unwrap().is_empty());
+
+        // Purged behind the journal's back, so it cannot be restored
+        move_to_trash_with(&a, &options).unwrap();
+        list_trash_in(&location).unwrap().remove(0).purge().unwrap();
+        let outcomes = undo_in(&journal, 1).unwrap();
+        assert!(outcomes[0].result.is_err());
+
+        let unjournaled = Trash::with_options(TrashOptions::new());
+        assert!(matches!(unjournaled.undo(1), Err(TrashError::NoJournal)));
+    }
+}
@@ -1,98 +1,486 @@
-use chrono;
-use chrono::NaiveDateTime;
+use crate::platform::OsStrExt;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Local, LocalResult, NaiveDateTime, TimeZone, Utc};
 use ini::Ini;
-use percent_encoding::{percent_decode, percent_encode, DEFAULT_ENCODE_SET};
-use std::borrow::Cow;
 use std::ffi::{OsStr, OsString};
-use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::ErrorKind;
-use std::os::unix::ffi::OsStrExt;
-use std::path::{Path, PathBuf};
-use std::str::FromStr;
-use xdg;
+use std::fs::{create_dir_all, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
 
-#[derive(Debug)]
+mod admin;
+mod backend;
+mod builder;
+mod bulk;
+mod cleanup;
+mod clock;
+mod config;
+mod date;
+mod dirs;
+mod entries;
+mod escape;
+mod filetype;
+mod health;
+#[cfg(feature = "sqlite")]
+mod index;
+mod item;
+mod job;
+mod journal;
+mod location;
+mod manifest;
+#[cfg(feature = "notify")]
+mod notify;
+mod options;
+mod partial;
+mod platform;
+mod policy;
+mod preflight;
+#[cfg(feature = "ioprio")]
+mod priority;
+mod query;
+mod search;
+#[cfg(feature = "selinux")]
+mod selinux;
+mod slot;
+mod snapshot;
+#[cfg(feature = "async")]
+mod spawn;
+#[cfg(feature = "async")]
+mod stream;
+mod trash;
+mod unicode;
+mod uri;
+
+pub use crate::admin::{scan_user_trashes, scan_user_trashes_in, TrashSummary, UserTrash};
+pub use crate::backend::{MemoryTrash, TrashBackend};
+pub use crate::builder::{InvalidTrashInfo, TrashInfoBuilder};
+pub use crate::bulk::{
+    move_items_to_trash, purge_items, restore_items, restore_items_with, BatchEvents,
+    RestoreOutcome,
+};
+pub use crate::cleanup::{
+    empty_trash_in_with, empty_trash_with, preview_empty_trash, preview_empty_trash_in,
+    preview_purge_matching_in, preview_purge_missing_origins_in, purge_matching, purge_matching_in,
+    purge_missing_origins, purge_missing_origins_in, EmptyDecision, PurgeReport, TrashBreakdown,
+};
+pub use crate::clock::{Clock, FixedClock, SystemClock};
+pub use crate::config::TrashConfig;
+pub use crate::date::{DeletionDate, DeletionDay};
+use crate::dirs::TrashDirs;
+pub use crate::entries::{iter_trash_in, list_trash_page, TrashEntries, TrashPage};
+pub use crate::escape::EscapePolicy;
+use crate::escape::{decode_path, encode_path};
+pub use crate::filetype::{FileKind, FileType};
+pub use crate::health::{diagnose, diagnose_in, HealthFinding};
+#[cfg(feature = "sqlite")]
+pub use crate::index::{IndexOrder, IndexStats, TrashIndex};
+pub use crate::item::{Restorability, RestoreDecision, RestoreStrategy, TrashedItem};
+pub use crate::job::{move_to_trash_background, move_to_trash_background_with, TrashJob};
+pub use crate::journal::{
+    read_journal, undo_in, Journal, JournalEntry, JournalOperation, UndoOutcome, JOURNAL_FILE,
+};
+pub use crate::location::{all_trash_locations, TrashLocation};
+use crate::location::{TrashCache, TrashOwner};
+pub use crate::manifest::{export_manifest, export_manifest_in, MANIFEST_VERSION};
+pub use crate::options::{
+    CollisionNamer, CollisionStrategy, CrossDevicePolicy, HomeFallback, NetworkPolicy, RetryPolicy,
+    SymlinkPolicy, Throttle, TrashLayout, TrashOptions, TrashProgress,
+};
+use crate::partial::PartialMove;
+pub use crate::policy::{apply_retention, apply_retention_with, PurgeReason, RetentionPolicy};
+pub use crate::preflight::{
+    can_restore, can_trash, RestoreCheck, RestoreProblem, TrashCheck, TrashProblem,
+};
+#[cfg(feature = "ioprio")]
+pub use crate::priority::run_low_priority;
+pub use crate::query::{
+    contains_original, contains_original_in, group_by_directory, largest_items, largest_items_in,
+    latest_versions, list_tagged, list_tagged_in, size_by_top_directory, versions_of,
+    versions_of_in, DirectoryGroup, DirectoryShare, LatestVersion, SizedItem,
+};
+pub use crate::search::{search_trash, search_trash_in, TrashSearch};
+pub use crate::slot::{adopt_into_trash, reserve_trash_slot, reserve_trash_slot_in, ReservedSlot};
+pub use crate::snapshot::{diff, snapshot, snapshot_in, SnapshotItem, TrashDiff, TrashSnapshot};
+#[cfg(feature = "async")]
+pub use crate::spawn::{
+    list_trash_async, move_to_trash_async, run_blocking, Blocking, Spawner, ThreadSpawner,
+};
+#[cfg(feature = "async")]
+pub use crate::stream::{stream_trash, stream_trash_in, TrashStream};
+pub use crate::trash::Trash;
+pub use crate::uri::TrashUri;
+
+/// The `Path` of an info file as written, which the spec allows to be
+/// relative to the top directory in the trash of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedPath<'a> {
+    Absolute(&'a Path),
+    /// Relative to the top directory of the trash's device
+    Relative(&'a Path),
+}
+
+#[derive(Debug, Clone)]
 pub struct TrashInfo {
     /// Internal filename used in trashcan
     pub internal_filename: OsString,
     /// Path of file that is going to the trash
     pub path: OsString,
     /// Time file started to move to trash
-    pub deletion_date: NaiveDateTime,
+    pub deletion_date: DeletionDate,
+    /// Keys other than `Path` and `DeletionDate`, such as ones added by
+    /// other tools, in the order they appeared
+    /// They are written back as they were read
+    pub extra: Vec<(String, String)>,
+    /// The policy applied to malformed escapes in the `Path` read, or
+    /// `None` if it had none, see `TrashOptions::escape_policy`
+    pub malformed_path: Option<EscapePolicy>,
 }
 
 impl TrashInfo {
+    /// Starts a `TrashInfo` whose fields are checked once it is built
+    pub fn builder() -> TrashInfoBuilder {
+        TrashInfoBuilder::default()
+    }
+
+    #[deprecated(note = "accepts anything, use `TrashInfo::builder` instead")]
     pub fn new(internal: OsString, path: OsString) -> Self {
-        let deletion_date = chrono::Local::now().naive_local();
-        Self {
-            internal_filename: internal,
-            path,
-            deletion_date,
-        }
+        Self::unchecked(internal, path, date::now())
     }
 
+    #[deprecated(note = "accepts anything, use `TrashInfo::builder` instead")]
     pub fn with_delete_datetime(
         internal: OsString,
         path: OsString,
-        deletion_date: NaiveDateTime,
+        deletion_date: DeletionDate,
+    ) -> Self {
+        Self::unchecked(internal, path, deletion_date)
+    }
+
+    /// For names and paths which come from the filesystem or an info
+    /// file, and so need no checking
+    pub(crate) fn unchecked(
+        internal: OsString,
+        path: OsString,
+        deletion_date: DeletionDate,
     ) -> Self {
         Self {
             internal_filename: internal,
             path,
             deletion_date,
+            extra: Vec::new(),
+            malformed_path: None,
         }
     }
 
+    /// Parses an info file, refusing one with a malformed escape in its
+    /// `Path` as `EscapePolicy::Strict` does
     pub fn from_filename_and_content(
         filename: OsString,
         content: &str,
     ) -> Result<Self, ParseTrashInfoError> {
-        use std::os::unix::ffi::OsStringExt;
+        Self::from_filename_and_content_with(filename, content, EscapePolicy::Strict)
+    }
+
+    /// Like `from_filename_and_content`, treating malformed escapes in
+    /// the `Path` as `policy` says
+    pub fn from_filename_and_content_with(
+        filename: OsString,
+        content: &str,
+        policy: EscapePolicy,
+    ) -> Result<Self, ParseTrashInfoError> {
+        use crate::platform::OsStringExt;
 
+        if content.len() > MAX_INFO_SIZE {
+            return Err(ParseTrashInfoError::TooLarge {
+                filename,
+                size: content.len() as u64,
+            });
+        }
+        if content.lines().filter(|line| line.contains('=')).count() > MAX_INFO_KEYS {
+            return Err(ParseTrashInfoError::TooManyKeys { filename });
+        }
         let res = Ini::load_from_str(content)?;
-        let section = res
-            .section(Some("Trash Info"))
-            .ok_or(ParseTrashInfoError::MissingSection)?;
-        let path = section.get("Path").ok_or(ParseTrashInfoError::MissingKey)?;
-        // Credit to stephaneyfx on the Rust Discord for decoding non-utf8 percent encoded bytes
-
-        let path = percent_decode(path.as_bytes())
-            .if_any()
-            .map_or(Cow::Borrowed(path.as_bytes()), Cow::Owned);
-        let path = OsString::from_vec(path.into_owned());
+        let section =
+            res.section(Some("Trash Info"))
+                .ok_or_else(|| ParseTrashInfoError::MissingSection {
+                    filename: filename.clone(),
+                })?;
+        let missing_key = |key| ParseTrashInfoError::MissingKey {
+            filename: filename.clone(),
+            key,
+        };
+        let path = section.get("Path").ok_or_else(|| missing_key("Path"))?;
+        let (path, malformed) =
+            decode_path(path, policy).ok_or_else(|| ParseTrashInfoError::InvalidEscape {
+                filename: filename.clone(),
+                key: "Path",
+            })?;
+        let path = OsString::from_vec(path);
+        if path.is_empty() {
+            return Err(ParseTrashInfoError::MissingValue {
+                filename,
+                key: "Path",
+            });
+        }
         let deletion_datetime = section
             .get("DeletionDate")
-            .ok_or(ParseTrashInfoError::MissingKey)?;
-        let deletion_datetime = NaiveDateTime::from_str(deletion_datetime).unwrap();
-        Ok(TrashInfo::with_delete_datetime(
-            filename,
-            path,
-            deletion_datetime,
-        ))
+            .ok_or_else(|| missing_key("DeletionDate"))?;
+        let deletion_datetime =
+            date::parse(deletion_datetime).ok_or_else(|| ParseTrashInfoError::InvalidDate {
+                filename: filename.clone(),
+                value: deletion_datetime.to_owned(),
+            })?;
+        let mut info = TrashInfo::unchecked(filename, path, deletion_datetime);
+        info.extra = extra_keys(content);
+        info.malformed_path = malformed.then_some(policy);
+        Ok(info)
+    }
+
+    /// Reads an info file, such as `$trash/info/photo.jpg.trashinfo`,
+    /// naming the item after it without the `.trashinfo` extension
+    pub fn from_path<P: AsRef<Path>>(info_path: P) -> Result<Self, TrashError> {
+        let info_path = info_path.as_ref();
+        let name = info_path.file_name().unwrap_or_default();
+        let internal_filename = match name.as_bytes().strip_suffix(b".trashinfo") {
+            Some(stem) => OsStr::from_bytes(stem),
+            None => name,
+        };
+        let content = read_info_file(info_path, internal_filename)?;
+        Ok(Self::from_filename_and_content(
+            internal_filename.to_os_string(),
+            &content,
+        )?)
     }
 
-    /// Writes info to retrieve deleted file
-    fn write_infofile(&self, file: &mut File) {
+    /// Deletion date in the spec's `%Y-%m-%dT%H:%M:%S` format
+    pub fn deletion_date_string(&self) -> String {
+        date::format(&self.deletion_date)
+    }
+
+    /// Deletion date with the local offset attached
+    /// A date repeated when clocks went back resolves to the first occurrence,
+    /// and one skipped when clocks went forward is moved past the gap
+    #[cfg(feature = "chrono")]
+    pub fn deletion_date_local(&self) -> DateTime<Local> {
+        local_from_naive(self.deletion_date)
+    }
+
+    /// Deletion date converted to UTC, see `deletion_date_local`
+    #[cfg(feature = "chrono")]
+    pub fn deletion_date_utc(&self) -> DateTime<Utc> {
+        self.deletion_date_local().with_timezone(&Utc)
+    }
+
+    /// The path as written in the info file, telling absolute paths
+    /// apart from relative ones, see `TrashedItem::original_path` for
+    /// it made absolute
+    pub fn recorded_path(&self) -> RecordedPath<'_> {
+        let path = Path::new(&self.path);
+        if path.is_absolute() {
+            RecordedPath::Absolute(path)
+        } else {
+            RecordedPath::Relative(path)
+        }
+    }
+
+    /// Value of a key other than `Path` and `DeletionDate`, such as one
+    /// recorded with `TrashOptions::metadata`
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.extra
+            .iter()
+            .find(|(extra_key, _)| extra_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Address of the trashed item in the `trash:///` URI scheme
+    pub fn uri(&self) -> TrashUri {
+        TrashUri::new(self.internal_filename.clone())
+    }
+
+    /// Writes the content of the info file to `w`, which may be a file,
+    /// a buffer or anything else taking bytes
+    /// The first error from `w` is returned and nothing more is written
+    pub fn write_to<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
         let mut info = Ini::new();
         // To aid in non-utf8 strings and to comply with spec
         // All OsStrings are url encoded
 
-        let percent_path = percent_encode(self.path.as_bytes(), DEFAULT_ENCODE_SET).to_string();
+        let percent_path = encode_path(self.path.as_bytes());
 
-        let deletion_datetime = self.deletion_date.format("%Y-%m-%dT%H:%M:%S").to_string();
         info.with_section(Some("Trash Info".to_owned()))
-            .set("Path", percent_path)
-            .set("DeletionDate", deletion_datetime);
-        info.write_to(file).unwrap();
+            .set("Path", percent_path);
+        info.write_to(&mut w)?;
+        // Written after the section rather than set in it, as keys of
+        // one section come out in no particular order
+        // The section is the last one, so these lines still belong to it
+        writeln!(w, "DeletionDate={}", date::format(&self.deletion_date))?;
+        for (key, value) in &self.extra {
+            writeln!(w, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The content of the info file, as it is written to the trash
+impl std::fmt::Display for TrashInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut content = Vec::new();
+        self.write_to(&mut content).map_err(|_| std::fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&content))
+    }
+}
+
+/// The keys of the `[Trash Info]` group other than `Path` and
+/// `DeletionDate`, with their values exactly as written
+fn extra_keys(content: &str) -> Vec<(String, String)> {
+    let mut in_trash_info = false;
+    let mut extra = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_start();
+        if line.starts_with('[') {
+            in_trash_info = line.trim_end() == "[Trash Info]";
+            continue;
+        }
+        if !in_trash_info || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim_end();
+            if key != "Path" && key != "DeletionDate" {
+                extra.push((key.to_owned(), value.trim_start().to_owned()));
+            }
+        }
+    }
+    extra
+}
+
+/// Attaches the local offset to a naive local time
+#[cfg(feature = "chrono")]
+fn local_from_naive(naive: NaiveDateTime) -> DateTime<Local> {
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(date) => date,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => {
+            // Inside a gap the wall clock jumped ahead, so read the time
+            // with the offset in effect just before the transition
+            let before = naive - chrono::Duration::days(1);
+            let offset = Local.offset_from_local_datetime(&before).earliest();
+            match offset {
+                Some(offset) => DateTime::<Utc>::from_naive_utc_and_offset(
+                    naive - chrono::Duration::seconds(offset.local_minus_utc().into()),
+                    Utc,
+                )
+                .with_timezone(&Local),
+                None => Local.from_utc_datetime(&naive),
+            }
+        }
     }
 }
 
+/// Largest info file read, far beyond what a path and a few extra keys
+/// take up, so that a corrupted or hostile one cannot fill up memory
+const MAX_INFO_SIZE: usize = 64 * 1024;
+
+/// Most `key=value` lines read from an info file
+const MAX_INFO_KEYS: usize = 256;
+
+/// Reads an info file, refusing one larger than `MAX_INFO_SIZE` without
+/// reading it whole
+pub(crate) fn read_info_file(
+    info_path: &Path,
+    internal_filename: &OsStr,
+) -> Result<String, TrashError> {
+    use std::io::Read;
+
+    let file = File::open(info_path)?;
+    let mut content = String::new();
+    file.take(MAX_INFO_SIZE as u64 + 1)
+        .read_to_string(&mut content)?;
+    if content.len() > MAX_INFO_SIZE {
+        let size = info_path.metadata()?.len();
+        return Err(ParseTrashInfoError::TooLarge {
+            filename: internal_filename.to_os_string(),
+            size,
+        }
+        .into());
+    }
+    Ok(content)
+}
+
+/// Errors from reading an info file, naming the item it belongs to
 #[derive(Debug)]
 pub enum ParseTrashInfoError {
-    MissingSection,
-    MissingKey,
-    MissingValue,
+    /// The info file has no `[Trash Info]` group
+    MissingSection {
+        filename: OsString,
+    },
+    /// The `[Trash Info]` group lacks a required key
+    MissingKey {
+        filename: OsString,
+        key: &'static str,
+    },
+    /// A required key is present but empty
+    MissingValue {
+        filename: OsString,
+        key: &'static str,
+    },
+    /// The DeletionDate could not be understood
+    InvalidDate {
+        filename: OsString,
+        value: String,
+    },
     ParseError(ini::ini::ParseError),
+    /// The info file is larger than any genuine one
+    TooLarge {
+        filename: OsString,
+        size: u64,
+    },
+    /// The info file has more lines of keys than any genuine one
+    TooManyKeys {
+        filename: OsString,
+    },
+    /// A `%` in a value is not followed by two hex digits
+    InvalidEscape {
+        filename: OsString,
+        key: &'static str,
+    },
+}
+
+impl std::fmt::Display for ParseTrashInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseTrashInfoError::MissingSection { filename } => {
+                write!(f, "{:?} has no [Trash Info] group", filename)
+            }
+            ParseTrashInfoError::MissingKey { filename, key } => {
+                write!(f, "{:?} has no {} key", filename, key)
+            }
+            ParseTrashInfoError::MissingValue { filename, key } => {
+                write!(f, "{:?} has an empty {}", filename, key)
+            }
+            ParseTrashInfoError::InvalidDate { filename, value } => {
+                write!(f, "{:?} has an invalid DeletionDate {:?}", filename, value)
+            }
+            ParseTrashInfoError::ParseError(e) => write!(f, "{}", e),
+            ParseTrashInfoError::TooLarge { filename, size } => {
+                write!(f, "{:?} is too large at {} bytes", filename, size)
+            }
+            ParseTrashInfoError::TooManyKeys { filename } => {
+                write!(f, "{:?} has too many keys", filename)
+            }
+            ParseTrashInfoError::InvalidEscape { filename, key } => {
+                write!(f, "{:?} has an invalid escape in {}", filename, key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseTrashInfoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseTrashInfoError::ParseError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<ini::ini::ParseError> for ParseTrashInfoError {
@@ -101,83 +489,367 @@ impl From<ini::ini::ParseError> for ParseTrashInfoError {
     }
 }
 
+/// Errors from operating on items already in the trash
+#[derive(Debug)]
+pub enum TrashError {
+    Io(std::io::Error),
+    Move(fs_extra::error::Error),
+    ParseInfo(ParseTrashInfoError),
+    /// No trashed item has the given internal filename
+    ItemNotFound(OsString),
+    /// The file to trash does not exist, perhaps because it is already gone
+    SourceNotFound(PathBuf),
+    /// Something already exists where the item would be restored to
+    RestoreConflict(PathBuf),
+    /// The resolver given to `TrashedItem::restore_resolving` chose
+    /// `RestoreDecision::Abort` over what exists at this path
+    RestoreAborted(PathBuf),
+    /// The home trash could not be located, as neither `$XDG_DATA_HOME`
+    /// nor `$HOME` are known and no fallback was given with
+    /// `TrashOptions::home_trash_fallback`
+    NoHomeTrash,
+    /// Nothing can be undone as no journal is kept, see `TrashOptions::journal`
+    NoJournal,
+    /// A glob pattern could not be understood
+    InvalidPattern(glob::PatternError),
+    /// No trash could be used in this top directory, and falling back
+    /// to the home trash was not allowed
+    NoTopDirTrash(PathBuf),
+    /// The trash is on another filesystem than the file, and
+    /// `CrossDevicePolicy::Fail` ruled out copying it there
+    CrossDevice {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// The file or the trash is on a network filesystem, which
+    /// `NetworkPolicy::Refuse` ruled out
+    NetworkFilesystem(PathBuf),
+    /// A key given to `TrashOptions::metadata` is not namespaced with
+    /// `X-`, or it or its value cannot be written to an info file
+    InvalidMetadata(String),
+    /// A tag was empty or held a `;` or line break
+    InvalidTag(String),
+    /// The SQLite index of a trash could not be read or updated
+    #[cfg(feature = "sqlite")]
+    Index(String),
+    /// The file holds more bytes than `TrashOptions::max_size` allows
+    TooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: u64,
+    },
+    /// A directory of the trash exists but cannot be written to, such as
+    /// on a filesystem mounted read only
+    TrashNotWritable(PathBuf),
+    /// A directory of the trash belongs to another user, so files cannot
+    /// be put in it
+    TrashNotOwned {
+        path: PathBuf,
+        uid: u32,
+    },
+    /// The filesystem holding the trash directory ran out of space, or
+    /// the user out of quota, before the file was in the trash
+    /// Anything partially copied was removed and the file left in place
+    NoSpace(PathBuf),
+    /// The file, or the filesystem mounted on it, is in use and cannot be
+    /// moved, such as a running program or a busy mount point
+    /// See `TrashOptions::wait_if_busy` to keep trying for a while
+    Busy(PathBuf),
+    /// The flag given to `TrashOptions::cancel_flag` was set before the
+    /// file was in the trash, which was left as it was before
+    Cancelled,
+    /// A line of the configuration file could not be understood
+    InvalidConfig {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// An environment variable overriding the configuration, such as
+    /// `TRASH_MAX_SIZE`, could not be understood
+    InvalidEnvVar {
+        name: String,
+        message: String,
+    },
+    /// A line of the text given to `TrashSnapshot::parse` could not be
+    /// understood
+    InvalidSnapshot {
+        line: usize,
+    },
+}
+
+impl std::fmt::Display for TrashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TrashError::Io(e) => write!(f, "{}", e),
+            TrashError::Move(e) => write!(f, "{}", e),
+            TrashError::ParseInfo(e) => write!(f, "invalid trash info file: {}", e),
+            TrashError::ItemNotFound(name) => {
+                write!(f, "no item named {:?} in the trash", name)
+            }
+            TrashError::SourceNotFound(path) => write!(f, "{} does not exist", path.display()),
+            TrashError::RestoreConflict(path) => {
+                write!(f, "cannot restore, {} already exists", path.display())
+            }
+            TrashError::RestoreAborted(path) => {
+                write!(f, "restore aborted, {} already exists", path.display())
+            }
+            TrashError::NoHomeTrash => write!(
+                f,
+                "no home trash, as neither $XDG_DATA_HOME nor $HOME are set"
+            ),
+            TrashError::NoJournal => write!(f, "no journal is kept"),
+            TrashError::InvalidPattern(e) => write!(f, "invalid pattern: {}", e),
+            TrashError::NoTopDirTrash(topdir) => {
+                write!(f, "no usable trash in {}", topdir.display())
+            }
+            TrashError::CrossDevice { from, to } => write!(
+                f,
+                "{} is on another filesystem than the trash in {}",
+                from.display(),
+                to.display()
+            ),
+            TrashError::NetworkFilesystem(path) => {
+                write!(f, "{} is on a network filesystem", path.display())
+            }
+            TrashError::InvalidMetadata(key) => write!(f, "invalid metadata key {:?}", key),
+            TrashError::InvalidTag(tag) => write!(f, "invalid tag {:?}", tag),
+            #[cfg(feature = "sqlite")]
+            TrashError::Index(message) => write!(f, "trash index: {}", message),
+            TrashError::TooLarge { path, size, limit } => write!(
+                f,
+                "{} holds {} bytes, more than the limit of {}",
+                path.display(),
+                size,
+                limit
+            ),
+            TrashError::TrashNotWritable(path) => {
+                write!(f, "trash directory {} is not writable", path.display())
+            }
+            TrashError::TrashNotOwned { path, uid } => write!(
+                f,
+                "trash directory {} belongs to another user ({})",
+                path.display(),
+                uid
+            ),
+            TrashError::NoSpace(path) => write!(f, "no space left in {}", path.display()),
+            TrashError::Busy(path) => write!(f, "{} is busy", path.display()),
+            TrashError::Cancelled => write!(f, "cancelled"),
+            TrashError::InvalidConfig {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            TrashError::InvalidEnvVar { name, message } => write!(f, "${}: {}", name, message),
+            TrashError::InvalidSnapshot { line } => {
+                write!(f, "invalid trash snapshot at line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrashError {}
+
+impl From<std::io::Error> for TrashError {
+    fn from(item: std::io::Error) -> Self {
+        TrashError::Io(item)
+    }
+}
+
+impl From<fs_extra::error::Error> for TrashError {
+    fn from(item: fs_extra::error::Error) -> Self {
+        TrashError::Move(item)
+    }
+}
+
+impl From<glob::PatternError> for TrashError {
+    fn from(item: glob::PatternError) -> Self {
+        TrashError::InvalidPattern(item)
+    }
+}
+
+impl From<ParseTrashInfoError> for TrashError {
+    fn from(item: ParseTrashInfoError) -> Self {
+        TrashError::ParseInfo(item)
+    }
+}
+
+/// Location of the trash in the user's home directory, following
+/// `$XDG_DATA_HOME` or else `$HOME` as the base directory spec has it
+fn home_trash_dir() -> Option<PathBuf> {
+    let absolute = |var| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+    let data_home = absolute("XDG_DATA_HOME")
+        .or_else(|| absolute("HOME").map(|home| home.join(".local").join("share")))?;
+    Some(data_home.join("Trash"))
+}
+
 /// Given a path attempt to reserve a trashinfo file in the $trash/info directory
-fn reserve_filename<P>(path: P) -> Result<(File, PathBuf), std::io::Error>
+/// Names are stamped with `now` where the collision strategy asks for it
+fn reserve_filename<P>(
+    dirs: &TrashDirs,
+    path: P,
+    collisions: CollisionStrategy,
+    now: DeletionDate,
+) -> Result<(File, PathBuf), std::io::Error>
 where
     P: AsRef<Path>,
 {
-    let base_dirs = xdg::BaseDirectories::new().unwrap();
-    let xdg_data_home = base_dirs.get_data_home();
-
-    let trash_dir = xdg_data_home.join("Trash");
-    let info_dir = PathBuf::from("info");
-
     let base_file = path.as_ref().file_name().expect("Empty path supplied");
-    let mut filename = OsString::from(base_file);
-    let info_filename_ext = OsStr::new(".trashinfo");
-    filename.push(info_filename_ext);
-
-    let mut info_path = [
-        trash_dir.as_os_str(),
-        info_dir.as_os_str(),
-        filename.as_os_str(),
-    ]
-    .iter()
-    .collect::<PathBuf>();
-
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(&info_path);
-
-    let mut duplicates = 1u32;
+
+    let mut attempt = 1u32;
     loop {
-        match file.as_ref() {
-            Ok(_) => break,
-            Err(e) => match e.kind() {
-                ErrorKind::AlreadyExists => {
-                    duplicates += 1;
-                    // Clear existing filename
-                    filename.clear();
-                    filename.push(&base_file);
-                    filename.push(".");
-                    let s_dup = duplicates.to_string();
-                    let s_dup: OsString = s_dup.into();
-                    filename.push(s_dup);
-                    filename.push(".trashinfo");
-
-                    info_path.set_file_name(&filename);
-                    // try again
-                    file = OpenOptions::new()
-                        .write(true)
-                        .create_new(true)
-                        .open(&info_path);
-                }
-                ErrorKind::NotFound => {
-                    // try to create trash directory in user home dir
-                    std::fs::create_dir_all(&trash_dir.join(PathBuf::from(&info_dir)))
-                        .unwrap_or_else(|e| {
-                            panic!("failed to create home trash dir: {:?}, {:?}", &trash_dir, e)
-                        });
-
-                    // try again
-                    file = OpenOptions::new()
-                        .write(true)
-                        .create_new(true)
-                        .open(&info_path);
-                }
-                _ => {
-                    break;
+        let mut filename = collisions.internal_name_at(base_file, attempt, now);
+        filename.push(".trashinfo");
+        match dirs.create_info(&filename) {
+            Ok(f) => return Ok((f, dirs.info_path(&filename))),
+            // try again with the next name
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Where `write_info_via_temp` puts an info file
+enum InfoTarget<'a> {
+    /// The first name free for this path, going by the collision strategy
+    Free(&'a Path, CollisionStrategy),
+    /// This name, which must not exist yet
+    New(&'a OsStr),
+    /// This name, replacing the info file there
+    Replace(&'a OsStr),
+}
+
+/// Writes the info file for a path under a free name in $trash/info
+fn write_info_atomically(
+    dirs: &TrashDirs,
+    path: &Path,
+    collisions: CollisionStrategy,
+    info: &TrashInfo,
+    owner: &TrashOwner,
+    sync: bool,
+) -> std::io::Result<PathBuf> {
+    write_info_via_temp(dirs, InfoTarget::Free(path, collisions), info, owner, sync)
+}
+
+/// Writes `info` as the info file named by `target`
+/// The content goes to a temporary file first which is then linked or
+/// renamed into place, so an info file is never seen half written, even
+/// after a crash
+fn write_info_via_temp(
+    dirs: &TrashDirs,
+    target: InfoTarget,
+    info: &TrashInfo,
+    owner: &TrashOwner,
+    sync: bool,
+) -> std::io::Result<PathBuf> {
+    let base_name = match target {
+        InfoTarget::Free(path, _) => path.file_name().expect("Empty path supplied"),
+        InfoTarget::New(name) | InfoTarget::Replace(name) => name,
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(base_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+
+    let mut temp = dirs.create_info_temp(&temp_name)?;
+    let written = info
+        .write_to(&mut temp)
+        .and_then(|()| if sync { temp.sync_all() } else { Ok(()) })
+        .and_then(|()| owner.chown_file(&temp))
+        .and_then(|()| match target {
+            InfoTarget::Free(path, collisions) => {
+                link_info_file(dirs, &temp_name, path, collisions, info.deletion_date)
+            }
+            InfoTarget::New(name) => match dirs.link_info(&temp_name, name) {
+                Err(e) if e.kind() != ErrorKind::AlreadyExists => {
+                    // Without hard links, the name is reserved by creating
+                    // it, then replaced
+                    dirs.create_info(name)?;
+                    dirs.rename_info(&temp_name, name)?;
+                    Ok(dirs.info_path(name))
                 }
+                linked => linked.map(|()| dirs.info_path(name)),
             },
+            InfoTarget::Replace(name) => {
+                dirs.rename_info(&temp_name, name)?;
+                Ok(dirs.info_path(name))
+            }
+        });
+    drop(temp);
+    let _ = dirs.remove_info(&temp_name);
+    written
+}
+
+/// Replaces an existing info file with `info`, such as after changing
+/// its tags, keeping the file's owner
+fn rewrite_info_file(info_path: &Path, info: &TrashInfo) -> std::io::Result<()> {
+    use crate::platform::MetadataExt;
+
+    let meta = info_path.metadata()?;
+    let owner = TrashOwner {
+        uid: meta.uid(),
+        gid: meta.gid(),
+        home_trash: None,
+    };
+    let trash_dir = info_path
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new("."));
+    let dirs = TrashDirs::open(trash_dir)?;
+    let name = info_path.file_name().unwrap_or_default();
+    write_info_via_temp(&dirs, InfoTarget::Replace(name), info, &owner, false).map(|_| ())
+}
+
+/// Links a written info file to the first free name, which reserves it
+fn link_info_file(
+    dirs: &TrashDirs,
+    temp_name: &OsStr,
+    path: &Path,
+    collisions: CollisionStrategy,
+    now: DeletionDate,
+) -> std::io::Result<PathBuf> {
+    let base_file = path.file_name().expect("Empty path supplied");
+
+    let mut attempt = 1u32;
+    loop {
+        let mut filename = collisions.internal_name_at(base_file, attempt, now);
+        filename.push(".trashinfo");
+        match dirs.link_info(temp_name, &filename) {
+            Ok(()) => return Ok(dirs.info_path(&filename)),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(_) => {
+                // Filesystems without hard links such as FAT can only reserve
+                // a name by creating the file, which is then replaced
+                let (_, info_path) = reserve_filename(dirs, path, collisions, now)?;
+                let name = info_path.file_name().unwrap_or_default();
+                dirs.rename_info(temp_name, name)?;
+                return Ok(info_path);
+            }
         }
     }
-    match file {
-        Ok(f) => {
-            let p = PathBuf::from(&info_path);
-            Ok((f, p))
+}
+
+/// The info file `reserve_filename` would create, without creating it
+fn preview_filename(
+    trash_dir: &Path,
+    path: &Path,
+    collisions: CollisionStrategy,
+    now: DeletionDate,
+) -> PathBuf {
+    let info_dir = trash_dir.join("info");
+    let base_file = path.file_name().expect("Empty path supplied");
+
+    let mut attempt = 1u32;
+    loop {
+        let mut filename = collisions.internal_name_at(base_file, attempt, now);
+        filename.push(".trashinfo");
+        let info_path = info_dir.join(&filename);
+        if info_path.symlink_metadata().is_err() {
+            return info_path;
         }
-        Err(e) => Err(e),
+        attempt += 1;
     }
 }
 
@@ -188,95 +860,1047 @@ pub struct TrashFiles {
     pub trash_file: PathBuf,
     /// Info file location
     pub info_file: PathBuf,
+    /// Which trash the file went to
+    pub location: TrashLocation,
+    /// Root of that trash, containing its `files` and `info` directories
+    pub trash_dir: PathBuf,
+    /// What it took to move the file, see `TrashOptions::collect_stats`
+    pub stats: TransferStats,
+    /// Whether the home trash was used because the trash on the file's
+    /// own device could not be, see `TrashOptions::home_fallback`
+    pub home_fallback: bool,
 }
 
 impl TrashFiles {
     /// Group together internal trash info and trash file locations
-    pub fn new(trash_file: PathBuf, info_file: PathBuf) -> Self {
+    pub fn new(
+        trash_file: PathBuf,
+        info_file: PathBuf,
+        location: TrashLocation,
+        trash_dir: PathBuf,
+    ) -> Self {
         Self {
             trash_file,
             info_file,
+            location,
+            trash_dir,
+            stats: TransferStats::default(),
+            home_fallback: false,
+        }
+    }
+}
+
+/// What it took to move a file or directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferStats {
+    /// Bytes of file data moved
+    pub bytes: u64,
+    /// Number of files, directories and symlinks moved
+    pub files: u64,
+    /// Whether the data had to be copied because a rename was not possible
+    pub copied: bool,
+    /// How long the whole operation took
+    pub elapsed: std::time::Duration,
+}
+
+/// Moves a file or directory, falling back to copy and delete when
+/// a rename is not possible (such as across filesystems)
+/// Sizes are only counted after a rename when `count` is set,
+/// but always after copying, unless `counted` already holds them
+fn move_path(
+    src_path: &Path,
+    dest_path: &Path,
+    options: &TrashOptions,
+    count: bool,
+    counted: Option<(u64, u64)>,
+    partial: Option<&PartialMove>,
+    into: Option<(&TrashDirs, &OsStr)>,
+) -> Result<TransferStats, fs_extra::error::Error> {
+    let started = std::time::Instant::now();
+    // Into the files directory as it was opened, when it is the destination
+    let renamed = match into {
+        Some((dirs, name)) => dirs.rename_into_files(src_path, name),
+        None => std::fs::rename(src_path, dest_path),
+    };
+    match renamed {
+        Ok(()) => {
+            let mut stats = TransferStats::default();
+            if count {
+                let (bytes, files) = match counted {
+                    Some(counted) => counted,
+                    None => count_tree(dest_path)?,
+                };
+                stats.bytes = bytes;
+                stats.files = files;
+            }
+            stats.elapsed = started.elapsed();
+            return Ok(stats);
+        }
+        Err(ref e) if e.kind() == ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e.into()),
+    }
+    if options.cross_device == CrossDevicePolicy::Fail {
+        return Err(std::io::Error::from(ErrorKind::CrossesDevices).into());
+    }
+
+    // Recorded so that the copy can be picked up again if we are killed
+    if let Some(partial) = partial {
+        partial.begin(src_path, dest_path)?;
+    }
+    let mut stats = copy_tree(src_path, dest_path, options, counted)?;
+    remove_path(src_path)?;
+    if let Some(partial) = partial {
+        partial.finish();
+    }
+    stats.elapsed = started.elapsed();
+    Ok(stats)
+}
+
+/// Copies a file or directory, leaving the original in place
+/// `counted` is what `count_tree` gives for it, when already known
+fn copy_tree(
+    src_path: &Path,
+    dest_path: &Path,
+    options: &TrashOptions,
+    counted: Option<(u64, u64)>,
+) -> Result<TransferStats, fs_extra::error::Error> {
+    #[cfg(feature = "ioprio")]
+    if options.low_priority {
+        let options = options.clone().low_priority(false);
+        return run_low_priority(|| copy_tree(src_path, dest_path, &options, counted));
+    }
+    let started = std::time::Instant::now();
+    let (bytes, files) = match counted {
+        Some(counted) => counted,
+        None => count_tree(src_path)?,
+    };
+    let copied = copy_path(src_path, dest_path, options, bytes).and_then(|_| {
+        if options.preserve_metadata {
+            copy_times(src_path, dest_path)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = copied {
+        // Whatever made it across is of no use, such as a file truncated
+        // by the disk filling up, unless it was there all along
+        if !matches!(e.kind, fs_extra::error::ErrorKind::AlreadyExists) {
+            let _ = remove_path(dest_path);
         }
+        return Err(e);
+    }
+    Ok(TransferStats {
+        bytes,
+        files,
+        copied: true,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Removes a file, symlink or directory with everything in it
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
     }
 }
 
+/// Bytes of file data and number of entries in a file or directory
+/// Symlinks are counted but not followed
+/// This is the one walk for sizes, so callers which walked a tree
+/// already pass its result on rather than walking it again
+pub(crate) fn count_tree(path: &Path) -> std::io::Result<(u64, u64)> {
+    let meta = path.symlink_metadata()?;
+    if !meta.is_dir() {
+        let bytes = if meta.is_file() { meta.len() } else { 0 };
+        return Ok((bytes, 1));
+    }
+    let (mut bytes, mut files) = (0, 1);
+    for entry in std::fs::read_dir(path)? {
+        let (entry_bytes, entry_files) = count_tree(&entry?.path())?;
+        bytes += entry_bytes;
+        files += entry_files;
+    }
+    Ok((bytes, files))
+}
+
+/// Copies a file, symlink or directory of `total_bytes` to `dest_path`
+fn copy_path(
+    src_path: &Path,
+    dest_path: &Path,
+    options: &TrashOptions,
+    total_bytes: u64,
+) -> Result<u64, fs_extra::error::Error> {
+    let mut copier = Copier {
+        options,
+        pacer: Pacer::new(options.throttle),
+        copied_bytes: 0,
+        total_bytes,
+    };
+    copier.copy(src_path, dest_path)?;
+    Ok(copier.copied_bytes)
+}
+
+/// Copies a tree a chunk at a time, reporting progress and keeping to the
+/// throttle after each chunk, and giving up once the copy is cancelled
+struct Copier<'a> {
+    options: &'a TrashOptions,
+    pacer: Pacer,
+    copied_bytes: u64,
+    total_bytes: u64,
+}
+
+impl Copier<'_> {
+    /// Copies a file, symlink or directory, which must not exist at `dest`
+    fn copy(&mut self, src: &Path, dest: &Path) -> std::io::Result<()> {
+        let meta = src.symlink_metadata()?;
+        if meta.file_type().is_symlink() {
+            platform::symlink(&std::fs::read_link(src)?, dest)?;
+            #[cfg(feature = "selinux")]
+            self.copy_context(src, dest)?;
+            Ok(())
+        } else if meta.is_dir() {
+            std::fs::create_dir(dest)?;
+            #[cfg(feature = "selinux")]
+            self.copy_context(src, dest)?;
+            for entry in std::fs::read_dir(src)? {
+                let entry = entry?;
+                self.copy(&entry.path(), &dest.join(entry.file_name()))?;
+            }
+            Ok(())
+        } else if meta.is_file() {
+            self.copy_file(src, dest)
+        } else {
+            Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot copy special file {}", src.display()),
+            ))
+        }
+    }
+
+    /// Labels a copy as the original is, unless metadata is not preserved
+    #[cfg(feature = "selinux")]
+    fn copy_context(&self, src: &Path, dest: &Path) -> std::io::Result<()> {
+        if self.options.preserve_metadata {
+            selinux::copy_context(src, dest)?;
+        }
+        Ok(())
+    }
+
+    fn copy_file(&mut self, src: &Path, dest: &Path) -> std::io::Result<()> {
+        self.pacer.next_file();
+        let mut from = File::open(src)?;
+        let mut to = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dest)?;
+        #[cfg(feature = "selinux")]
+        self.copy_context(src, dest)?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            if self.options.is_cancelled() {
+                return Err(std::io::Error::other("copy cancelled"));
+            }
+            let read = match from.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            to.write_all(&buf[..read])?;
+            self.copied_bytes += read as u64;
+            if let Some(progress) = &self.options.progress {
+                progress(TrashProgress {
+                    copied_bytes: self.copied_bytes,
+                    total_bytes: self.total_bytes,
+                });
+            }
+            self.pacer.pace(self.copied_bytes);
+        }
+    }
+}
+
+/// Sleeps as needed to keep a copy within a `Throttle`
+struct Pacer {
+    throttle: Throttle,
+    started: std::time::Instant,
+    files: u64,
+}
+
+impl Pacer {
+    fn new(throttle: Throttle) -> Self {
+        Pacer {
+            throttle,
+            started: std::time::Instant::now(),
+            files: 0,
+        }
+    }
+
+    /// Counts a file which is about to be copied, waiting its turn
+    fn next_file(&mut self) {
+        self.files += 1;
+        if let Some(limit) = self.throttle.files_per_sec {
+            // The first file goes right away
+            self.wait_until((self.files - 1) as f64 / limit.max(1) as f64);
+        }
+    }
+
+    /// Waits until copying `copied_bytes` has taken long enough
+    fn pace(&self, copied_bytes: u64) {
+        if let Some(limit) = self.throttle.bytes_per_sec {
+            self.wait_until(copied_bytes as f64 / limit.max(1) as f64);
+        }
+    }
+
+    fn wait_until(&self, secs: f64) {
+        let due = self.started + std::time::Duration::from_secs_f64(secs);
+        let now = std::time::Instant::now();
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+}
+
+/// Gives a copy the access and modification times of the original
+fn copy_times(src_path: &Path, dest_path: &Path) -> std::io::Result<()> {
+    let meta = src_path.symlink_metadata()?;
+    if meta.file_type().is_symlink() {
+        return Ok(());
+    }
+    if meta.is_dir() {
+        for entry in std::fs::read_dir(src_path)? {
+            let entry = entry?;
+            copy_times(&entry.path(), &dest_path.join(entry.file_name()))?;
+        }
+    }
+    let times = std::fs::FileTimes::new()
+        .set_accessed(meta.accessed()?)
+        .set_modified(meta.modified()?);
+    File::open(dest_path)?.set_times(times)
+}
+
 /// Moves a file or directory to freedesktop.org trash spec folder
 /// Returns the internal path where the file is moved to in the trash
 /// Do not rely on the file still being there, as the trash item may
 /// have been deleted or restored.
-pub fn move_to_trash<P: AsRef<Path>>(path: P) -> Result<TrashFiles, fs_extra::error::Error> {
-    let (mut info_file, info_file_name) = reserve_filename(&path)?;
-    let internal_filename_for_trash = info_file_name.file_stem().unwrap();
+/// Defaults are taken from the configuration file, see `TrashConfig`
+pub fn move_to_trash<P: AsRef<Path>>(path: P) -> Result<TrashFiles, TrashError> {
+    move_to_trash_with(path, &TrashConfig::current().options())
+}
 
-    let trash_info = TrashInfo::new(
-        internal_filename_for_trash.to_os_string(),
-        path.as_ref().canonicalize().unwrap().into_os_string(),
-    );
-    trash_info.write_infofile(&mut info_file);
-
-    let base_dirs = xdg::BaseDirectories::new().unwrap();
-    let xdg_data_home = base_dirs.get_data_home();
-
-    let trash_dir = xdg_data_home.join("Trash");
-    let trash_dir_store_files = trash_dir.join("files");
-    let trash_dest_file = trash_dir_store_files.join(internal_filename_for_trash);
-
-    /// Helper funciton to move a file or directory to trash
-    fn move_to_trash_decision(
-        src_path: &Path,
-        dest_path: &Path,
-    ) -> Result<u64, fs_extra::error::Error> {
-        if src_path.is_dir() {
-            let mut copy_options = fs_extra::dir::CopyOptions::new();
-            copy_options.overwrite = false;
-            copy_options.skip_exist = false;
-            fs_extra::dir::move_dir(src_path, dest_path, &copy_options)
-        } else {
-            let mut copy_options = fs_extra::file::CopyOptions::new();
-            copy_options.overwrite = false;
-            copy_options.skip_exist = false;
+/// Like `move_to_trash`, but when running as root on a file owned by
+/// another user, the file goes to that user's trash instead of root's
+/// Everything created in their trash is handed over to them
+pub fn move_to_owner_trash<P: AsRef<Path>>(path: P) -> Result<TrashFiles, TrashError> {
+    move_to_trash_with(path, &TrashConfig::current().options().owner_trash(true))
+}
+
+/// Copies a file or directory into the trash, leaving the original in
+/// place, to keep a copy which can be restored before overwriting it
+pub fn copy_to_trash<P: AsRef<Path>>(path: P) -> Result<TrashFiles, TrashError> {
+    move_to_trash_with(path, &TrashConfig::current().options().keep_original(true))
+}
 
-            fs_extra::file::move_file(src_path, dest_path, &copy_options)
+/// Moves a file or directory into the given trash, rather than
+/// the one picked for it by `move_to_trash`
+pub fn move_to_trash_in<P: AsRef<Path>>(
+    path: P,
+    location: &TrashLocation,
+) -> Result<TrashFiles, TrashError> {
+    move_to_trash_with(
+        path,
+        &TrashConfig::current().options().location(location.clone()),
+    )
+}
+
+/// Moves a file or directory to the trash as configured by `options`
+pub fn move_to_trash_with<P: AsRef<Path>>(
+    path: P,
+    options: &TrashOptions,
+) -> Result<TrashFiles, TrashError> {
+    trash_path(path.as_ref(), options, None)
+}
+
+/// Whether trashing a path as configured by `options` means copying it
+/// to another filesystem, rather than a quick rename
+/// Nothing is created or moved to find out
+pub fn would_cross_devices<P: AsRef<Path>>(
+    path: P,
+    options: &TrashOptions,
+) -> Result<bool, TrashError> {
+    let preview = move_to_trash_with(path, &options.clone().dry_run(true))?;
+    Ok(preview.stats.copied)
+}
+
+/// Does the work of `move_to_trash_with`, reusing the current user's
+/// already resolved trash directories when given
+fn trash_path(
+    path: &Path,
+    options: &TrashOptions,
+    resolved: Option<(&TrashOwner, &TrashCache)>,
+) -> Result<TrashFiles, TrashError> {
+    if let Some(key) = options.invalid_metadata() {
+        return Err(TrashError::InvalidMetadata(key.to_owned()));
+    }
+    if options.is_cancelled() {
+        return Err(TrashError::Cancelled);
+    }
+    if let Err(e) = path.symlink_metadata() {
+        return Err(source_error(path, e));
+    }
+    let normalized = source_path(path)?;
+    let followed;
+    let mut path = normalized.as_path();
+    if options.symlinks == SymlinkPolicy::FollowLink && path.symlink_metadata()?.is_symlink() {
+        followed = path.canonicalize()?;
+        path = &followed;
+    }
+
+    // Walked at most once, for the size limit, the recorded size and
+    // the transfer stats together
+    let mut counted = None;
+    if let Some(limit) = options.max_size {
+        let (size, files) = count_tree(path)?;
+        counted = Some((size, files));
+        if size > limit {
+            return Err(TrashError::TooLarge {
+                path: path.to_path_buf(),
+                size,
+                limit,
+            });
         }
     }
 
-    let res = move_to_trash_decision(path.as_ref(), &trash_dest_file);
-    let failed_move = if let Err(e) = res {
-        e
+    let mut owner = match resolved {
+        _ if options.owner_trash => TrashOwner::of_file(path)?,
+        Some((owner, _)) => owner.clone(),
+        None => TrashOwner::current(),
+    };
+    if owner.home_trash.is_none() {
+        owner.home_trash = options.home_trash_fallback.clone();
+    }
+    // Another user's trash directories are not the ones cached
+    let cache = resolved
+        .filter(|(cached_owner, _)| cached_owner.uid == owner.uid)
+        .map(|(_, cache)| cache);
+    let create = !options.dry_run;
+    let mut home_fallback = false;
+    let (location, trash_dir) = match &options.location {
+        None => {
+            let allow_fallback = |topdir: &Path| options.allows_home_fallback(topdir, path);
+            let (location, trash_dir, fell_back) =
+                location::select_trash(path, &owner, create, cache, &allow_fallback)?;
+            home_fallback = fell_back;
+            (location, trash_dir)
+        }
+        Some(location) => (location.clone(), trash_dir_of(location, &owner, create)?),
+    };
+
+    let on_network = match options.network {
+        NetworkPolicy::Normal => None,
+        _ => [path, trash_dir.as_path()]
+            .iter()
+            .find(|checked| location::is_network_fs(checked))
+            .map(|checked| checked.to_path_buf()),
+    };
+    let relaxed = match on_network {
+        Some(checked) if options.network == NetworkPolicy::Refuse => {
+            return Err(TrashError::NetworkFilesystem(checked))
+        }
+        Some(_) => true,
+        None => false,
+    };
+
+    if options.dry_run {
+        let info_file = preview_filename(&trash_dir, path, options.collisions, options.now());
+        let mut trash_file = trash_dir.join("files");
+        if options.layout == TrashLayout::Hierarchical {
+            let original = original_path(path, !relaxed).map_err(|e| source_error(path, e))?;
+            trash_file.push(stored_dir(&original));
+        }
+        trash_file.push(info_file.file_stem().unwrap());
+        let copied = options.keep_original || !location::same_mount(path, &trash_file)?;
+        let mut files = TrashFiles::new(trash_file, info_file, location, trash_dir);
+        files.stats.copied = copied;
+        files.home_fallback = home_fallback;
+        return Ok(files);
+    }
+    let mut files = if relaxed {
+        let options = options.clone().durable(false);
+        move_into_trash(path, &owner, location, trash_dir, &options, false, counted)?
     } else {
-        return Ok(TrashFiles::new(trash_dest_file, info_file_name));
+        move_into_trash(path, &owner, location, trash_dir, options, true, counted)?
+    };
+    files.home_fallback = home_fallback;
+    Ok(files)
+}
+
+/// The path of a file to trash, ending in the file's own name
+/// Dot components and trailing slashes are dropped, and a path ending
+/// in `..` is resolved to the directory it names
+fn source_path(path: &Path) -> Result<PathBuf, TrashError> {
+    let named = match path.components().next_back() {
+        Some(Component::Normal(_)) => path.components().collect(),
+        Some(Component::CurDir) | Some(Component::ParentDir) => {
+            path.canonicalize().map_err(|e| source_error(path, e))?
+        }
+        _ => PathBuf::new(),
     };
+    if named.file_name().is_none() {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("cannot trash {}", path.display()),
+        )
+        .into());
+    }
+    Ok(named)
+}
 
-    use fs_extra::error::ErrorKind as fse_ErrorKind;
-    let retried_res = match failed_move.kind {
-        fse_ErrorKind::NotFound => {
+/// Where a file is recorded as having been trashed from: the absolute
+/// path of the directory holding it, followed by its name
+/// A symlink is recorded as itself, not as what it points to
+fn original_path(path: &Path, canonicalize: bool) -> std::io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = if canonicalize {
+        parent.canonicalize()?
+    } else {
+        std::path::absolute(parent)?
+    };
+    Ok(parent.join(path.file_name().unwrap_or_default()))
+}
+
+/// An error about the file being trashed, telling apart it having vanished
+fn source_error(path: &Path, e: std::io::Error) -> TrashError {
+    match e.kind() {
+        ErrorKind::NotFound => TrashError::SourceNotFound(path.to_path_buf()),
+        _ => e.into(),
+    }
+}
+
+fn move_into_trash(
+    path: &Path,
+    owner: &TrashOwner,
+    location: TrashLocation,
+    trash_dir: PathBuf,
+    options: &TrashOptions,
+    canonicalize: bool,
+    mut counted: Option<(u64, u64)>,
+) -> Result<TrashFiles, TrashError> {
+    if owner.needs_chown() {
+        // Created up front, or root would end up owning them
+        for dir in &[trash_dir.join("info"), trash_dir.join("files")] {
+            owner
+                .create_dir_all(dir)
+                .map_err(|e| trash_dir_error(dir, e))?;
+        }
+    }
+    let mut trash_info = TrashInfo::unchecked(
+        path.file_name()
+            .expect("Empty path supplied")
+            .to_os_string(),
+        original_path(path, canonicalize)
+            .map_err(|e| source_error(path, e))?
+            .into_os_string(),
+        options.now(),
+    );
+    trash_info.extra = options.metadata.clone();
+    let files_dir = match options.layout {
+        TrashLayout::Flat => trash_dir.join("files"),
+        TrashLayout::Hierarchical => {
+            let dir = stored_dir(Path::new(&trash_info.path));
+            let encoded = encode_path(dir.as_os_str().as_bytes());
+            trash_info.extra.push((DIR_KEY.to_owned(), encoded));
+            trash_dir.join("files").join(dir)
+        }
+    };
+    if options.layout == TrashLayout::Hierarchical && owner.needs_chown() {
+        owner
+            .create_dir_all(&files_dir)
+            .map_err(|e| trash_dir_error(&files_dir, e))?;
+    }
+    if !options.keep_original {
+        let resumed = partial::resume(
+            path,
+            Path::new(&trash_info.path),
+            owner,
+            &location,
+            &trash_dir,
+            options,
+        )?;
+        if let Some(files) = resumed {
+            return Ok(files);
+        }
+    }
+    // What root copies into the trash ends up belonging to root, so who
+    // to hand the item back to when it is restored is kept
+    if location::current_uid() == 0 {
+        use crate::platform::MetadataExt;
+
+        let meta = path.symlink_metadata().map_err(|e| source_error(path, e))?;
+        if meta.uid() != 0 {
+            let owner = format!("{}:{}", meta.uid(), meta.gid());
+            trash_info.extra.push((OWNER_KEY.to_owned(), owner));
+        }
+    }
+    if options.record_size {
+        let (size, files) = match counted {
+            Some(counted) => counted,
+            None => count_tree(path).map_err(|e| source_error(path, e))?,
+        };
+        counted = Some((size, files));
+        trash_info
+            .extra
+            .push((SIZE_KEY.to_owned(), size.to_string()));
+    }
+    // Held open from here on, so that the info file and the item end up
+    // in the same trash even if its path is swapped for another meanwhile
+    let dirs = TrashDirs::open(&trash_dir).map_err(|e| trash_dir_error(&trash_dir, e))?;
+    let info_file_name = write_info_atomically(
+        &dirs,
+        path,
+        options.collisions,
+        &trash_info,
+        owner,
+        options.durable,
+    )
+    .map_err(|e| trash_dir_error(&trash_dir.join("info"), e))?;
+    let internal_filename_for_trash = info_file_name.file_stem().unwrap();
+
+    let trash_dest_file = files_dir.join(internal_filename_for_trash);
+    let partial = PartialMove::new(
+        &trash_dir,
+        internal_filename_for_trash,
+        Path::new(&trash_info.path),
+    );
+
+    let into = match options.layout {
+        TrashLayout::Flat => Some((&dirs, internal_filename_for_trash)),
+        TrashLayout::Hierarchical => None,
+    };
+    let transfer = |dest: &Path| {
+        wait_while_busy(options.busy_wait, || {
+            retry_transient(&options.retry, || {
+                if options.keep_original {
+                    copy_tree(path, dest, options, counted)
+                } else {
+                    move_path(
+                        path,
+                        dest,
+                        options,
+                        options.collect_stats,
+                        counted,
+                        Some(&partial),
+                        into,
+                    )
+                }
+            })
+        })
+    };
+    let moved = match transfer(&trash_dest_file) {
+        Err(fs_extra::error::Error {
+            kind: fs_extra::error::ErrorKind::NotFound,
+            ..
+        }) => {
             // The directory for storing files/dirs in trash may not exist
-            create_dir_all(trash_dir_store_files).expect("failed to create trash files dir");
+            if let Err(e) = create_dir_all(&files_dir) {
+                let _ = dirs.remove_info(info_file_name.file_name().unwrap_or_default());
+                partial.finish();
+                return Err(trash_dir_error(&files_dir, e));
+            }
             // retry moving to trash
-            move_to_trash_decision(path.as_ref(), &trash_dest_file)
+            transfer(&trash_dest_file)
         }
         // Fail on any other error such as permission denied or fs error
-        _ => Err(failed_move),
+        moved => moved,
     };
 
     // If moving to trash still failed, give up and return the
-    // underlying error
-    if let Err(e) = retried_res {
-        Err(e)
-    } else {
-        // Everything went okay otherwise
-        Ok(TrashFiles::new(trash_dest_file, info_file_name))
+    // underlying error, without leaving an info file for nothing behind
+    match moved {
+        Err(e) => {
+            let _ = dirs.remove_info(info_file_name.file_name().unwrap_or_default());
+            partial.finish();
+            match e.kind {
+                _ if options.is_cancelled() => Err(TrashError::Cancelled),
+                fs_extra::error::ErrorKind::Io(ref io) if is_busy(io) => {
+                    Err(TrashError::Busy(path.to_path_buf()))
+                }
+                fs_extra::error::ErrorKind::Io(ref io)
+                    if io.kind() == ErrorKind::CrossesDevices =>
+                {
+                    Err(TrashError::CrossDevice {
+                        from: path.to_path_buf(),
+                        to: trash_dir,
+                    })
+                }
+                fs_extra::error::ErrorKind::Io(io) => Err(trash_dir_error(&files_dir, io)),
+                // Removed by someone else while we were at it
+                fs_extra::error::ErrorKind::NotFound if path.symlink_metadata().is_err() => {
+                    Err(TrashError::SourceNotFound(path.to_path_buf()))
+                }
+                // The trash rather than the file's directory is in the way
+                fs_extra::error::ErrorKind::PermissionDenied
+                    if !location::is_writable(&files_dir) =>
+                {
+                    Err(trash_dir_error(
+                        &files_dir,
+                        std::io::Error::from(ErrorKind::PermissionDenied),
+                    ))
+                }
+                _ => Err(e.into()),
+            }
+        }
+        Ok(stats) => {
+            // Everything went okay otherwise
+            give_to_owner(owner, &trash_dest_file)?;
+            if options.durable {
+                sync_moved(path, &trash_dest_file, &trash_dir, stats.copied)?;
+            }
+            let operation = if options.keep_original {
+                JournalOperation::Copy
+            } else {
+                JournalOperation::Trash
+            };
+            journal::record(
+                options.journal.as_ref(),
+                operation,
+                &trash_dir,
+                Path::new(&trash_info.path),
+                &trash_dest_file,
+            );
+            let mut files = TrashFiles::new(trash_dest_file, info_file_name, location, trash_dir);
+            files.stats = stats;
+            Ok(files)
+        }
     }
 }
 
+/// Whether a file could not be moved because it, or the filesystem
+/// mounted on it, is in use: `EBUSY` or `ETXTBSY`
+fn is_busy(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ResourceBusy | ErrorKind::ExecutableFileBusy
+    )
+}
+
+/// Runs `attempt` again while it fails because the file is busy, until
+/// `wait` has passed, sleeping twice as long after each try
+fn wait_while_busy<T, F>(
+    wait: Option<std::time::Duration>,
+    mut attempt: F,
+) -> Result<T, fs_extra::error::Error>
+where
+    F: FnMut() -> Result<T, fs_extra::error::Error>,
+{
+    let started = std::time::Instant::now();
+    let mut delay = std::time::Duration::from_millis(10);
+    loop {
+        let result = attempt();
+        let busy = matches!(
+            &result,
+            Err(fs_extra::error::Error {
+                kind: fs_extra::error::ErrorKind::Io(io),
+                ..
+            }) if is_busy(io)
+        );
+        let left = wait.and_then(|wait| wait.checked_sub(started.elapsed()));
+        match left {
+            Some(left) if busy && !left.is_zero() => std::thread::sleep(delay.min(left)),
+            _ => return result,
+        }
+        delay = (delay * 2).min(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Whether an error may well not happen again when retried: `EAGAIN`,
+/// `EINTR`, or a network filesystem timing out or losing track of a file
+fn is_transient(e: &fs_extra::error::Error) -> bool {
+    match &e.kind {
+        fs_extra::error::ErrorKind::Interrupted => true,
+        fs_extra::error::ErrorKind::Io(io) => matches!(
+            io.kind(),
+            ErrorKind::WouldBlock
+                | ErrorKind::Interrupted
+                | ErrorKind::TimedOut
+                | ErrorKind::StaleNetworkFileHandle
+        ),
+        _ => false,
+    }
+}
+
+/// Runs `attempt` again as `policy` allows while it fails with a
+/// transient error
+fn retry_transient<T, F>(policy: &RetryPolicy, mut attempt: F) -> Result<T, fs_extra::error::Error>
+where
+    F: FnMut() -> Result<T, fs_extra::error::Error>,
+{
+    let mut delay = policy.initial_delay.min(policy.max_delay);
+    for _ in 0..policy.retries {
+        match attempt() {
+            Err(ref e) if is_transient(e) => {}
+            done => return done,
+        }
+        std::thread::sleep(delay);
+        delay = delay.saturating_mul(2).min(policy.max_delay);
+    }
+    attempt()
+}
+
+/// The directory of the given trash as `owner` uses it, created if
+/// `create` is set and it is the trash of a device
+fn trash_dir_of(
+    location: &TrashLocation,
+    owner: &TrashOwner,
+    create: bool,
+) -> Result<PathBuf, TrashError> {
+    match location {
+        TrashLocation::Home => owner.home_trash.clone().ok_or(TrashError::NoHomeTrash),
+        TrashLocation::TopDir(topdir) => location::topdir_trash(topdir, owner, create)
+            .ok_or_else(|| TrashError::NoTopDirTrash(topdir.clone())),
+        TrashLocation::TopDirUser(topdir) => location::user_topdir_trash(topdir, owner, create)
+            .ok_or_else(|| TrashError::NoTopDirTrash(topdir.clone())),
+        location => location.trash_dir(),
+    }
+}
+
+/// Names the directory of a trash which could not be written to, rather
+/// than leaving a bare permission error, or passes on any other error
+fn trash_dir_error(dir: &Path, e: std::io::Error) -> TrashError {
+    use crate::platform::MetadataExt;
+
+    if matches!(e.kind(), ErrorKind::StorageFull | ErrorKind::QuotaExceeded) {
+        return TrashError::NoSpace(dir.to_path_buf());
+    }
+    if !matches!(
+        e.kind(),
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem
+    ) {
+        return e.into();
+    }
+    // The closest directory which exists is the one refusing to be written
+    let dir = dir
+        .ancestors()
+        .find(|dir| dir.symlink_metadata().is_ok())
+        .unwrap_or(dir);
+    match dir.symlink_metadata() {
+        Ok(meta) if meta.uid() != location::current_uid() => TrashError::TrashNotOwned {
+            path: dir.to_path_buf(),
+            uid: meta.uid(),
+        },
+        _ => TrashError::TrashNotWritable(dir.to_path_buf()),
+    }
+}
+
+/// Flushes a finished move to disk: the copied data if it was copied,
+/// and the directories whose entries changed
+fn sync_moved(
+    path: &Path,
+    trash_file: &Path,
+    trash_dir: &Path,
+    copied: bool,
+) -> std::io::Result<()> {
+    if copied && trash_file.symlink_metadata()?.is_file() {
+        File::open(trash_file)?.sync_all()?;
+    }
+    sync_dir(&trash_dir.join("info"))?;
+    sync_dir(&trash_dir.join("files"))?;
+    match path.parent() {
+        Some(parent) if parent != Path::new("") => sync_dir(parent),
+        _ => sync_dir(Path::new(".")),
+    }
+}
+
+/// Flushes the entries of a directory to disk
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// A copy made while moving into someone else's trash belongs to us,
+/// while a renamed file kept its owner
+fn give_to_owner(owner: &TrashOwner, trash_file: &Path) -> std::io::Result<()> {
+    use crate::platform::MetadataExt;
+
+    if owner.needs_chown() && trash_file.symlink_metadata()?.uid() != owner.uid {
+        owner.chown_all(trash_file)?;
+    }
+    Ok(())
+}
+
+/// Lists the items in the home trash, most recently deleted first
+pub fn list_trash() -> Result<Vec<TrashedItem>, TrashError> {
+    list_trash_in(&TrashLocation::Home)
+}
+
+/// Lists the items in the given trash, most recently deleted first
+pub fn list_trash_in(location: &TrashLocation) -> Result<Vec<TrashedItem>, TrashError> {
+    list_trash_dir(location, location.trash_dir()?)
+}
+
+/// Lists the items in every trash of the current user, the home trash
+/// and those on other mounted devices, most recently deleted first
+/// See `all_trash_locations`
+pub fn list_all_trash() -> Result<Vec<TrashedItem>, TrashError> {
+    let mut items = Vec::new();
+    for location in all_trash_locations() {
+        items.extend(list_trash_in(&location)?);
+    }
+    items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at()));
+    Ok(items)
+}
+
+/// Lists the items in a trash whose directory is already known
+fn list_trash_dir(
+    location: &TrashLocation,
+    trash_dir: PathBuf,
+) -> Result<Vec<TrashedItem>, TrashError> {
+    let mut items = TrashEntries::new(location, trash_dir)?.collect::<Result<Vec<_>, _>>()?;
+    items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at()));
+    Ok(items)
+}
+
+/// Moves an item in the home trash back to the location it was deleted from
+/// Returns the path the item was restored to
+pub fn restore_from_trash(internal_filename: &OsStr) -> Result<PathBuf, TrashError> {
+    restore_from_trash_in(internal_filename, &TrashLocation::Home)
+}
+
+/// Moves an item in the given trash back to the location it was deleted from
+/// Returns the path the item was restored to
+pub fn restore_from_trash_in(
+    internal_filename: &OsStr,
+    location: &TrashLocation,
+) -> Result<PathBuf, TrashError> {
+    TrashedItem::load(internal_filename, location)?.restore(RestoreStrategy::FailOnConflict)
+}
+
+/// Permanently deletes an item from the home trash
+pub fn purge_from_trash(internal_filename: &OsStr) -> Result<(), TrashError> {
+    purge_from_trash_in(internal_filename, &TrashLocation::Home)
+}
+
+/// Permanently deletes an item from the given trash
+pub fn purge_from_trash_in(
+    internal_filename: &OsStr,
+    location: &TrashLocation,
+) -> Result<(), TrashError> {
+    TrashedItem::load(internal_filename, location)?.purge()
+}
+
+/// Permanently deletes everything in the home trash
+/// Returns the items which were deleted
+pub fn empty_trash() -> Result<Vec<TrashedItem>, TrashError> {
+    empty_trash_in(&TrashLocation::Home)
+}
+
+/// Permanently deletes everything in the given trash
+/// Returns the items which were deleted
+pub fn empty_trash_in(location: &TrashLocation) -> Result<Vec<TrashedItem>, TrashError> {
+    purge_all(list_trash_in(location)?)
+}
+
+/// Permanently deletes everything in the trash of the device holding
+/// `path`, such as a removable drive, leaving the home trash alone
+/// Returns the items which were deleted
+pub fn empty_trash_on<P: AsRef<Path>>(path: P) -> Result<Vec<TrashedItem>, TrashError> {
+    empty_topdir_trashes(location::mount_root(path.as_ref())?)
+}
+
+/// Empties the current user's trashes in a top directory
+fn empty_topdir_trashes(topdir: PathBuf) -> Result<Vec<TrashedItem>, TrashError> {
+    // Both kinds of top directory trash may be in use at once
+    let uid = location::current_uid();
+    let trashes = [
+        (
+            TrashLocation::TopDir(topdir.clone()),
+            topdir.join(".Trash").join(uid.to_string()),
+        ),
+        (
+            TrashLocation::TopDirUser(topdir.clone()),
+            location::user_trash_dir(&topdir, uid),
+        ),
+    ];
+    let mut purged = Vec::new();
+    for (location, trash_dir) in trashes {
+        if trash_dir.symlink_metadata().is_ok_and(|meta| meta.is_dir()) {
+            purged.extend(purge_all(list_trash_dir(&location, trash_dir)?)?);
+        }
+    }
+    Ok(purged)
+}
+
+/// Permanently deletes the listed items, returning them
+fn purge_all(items: Vec<TrashedItem>) -> Result<Vec<TrashedItem>, TrashError> {
+    for item in &items {
+        item.clone().purge()?;
+    }
+    Ok(items)
+}
+
+/// Start of the info file keys the crate itself writes, which callers
+/// cannot set with `TrashOptions::metadata`
+pub(crate) const RESERVED_KEY_PREFIX: &str = "X-Trash-";
+
+/// Info file key holding an item's tags, separated by `;`
+pub(crate) const TAGS_KEY: &str = "X-Trash-Tags";
+
+/// Info file key holding the size of an item when it was trashed,
+/// so sizes can be added up without walking through the trash
+pub(crate) const SIZE_KEY: &str = "X-Trash-Size";
+
+/// Info file key holding the user and group which owned an item trashed
+/// by root, as `uid:gid`
+pub(crate) const OWNER_KEY: &str = "X-Trash-Owner";
+
+/// Info file key holding the directory inside of `files` an item is
+/// stored in with `TrashLayout::Hierarchical`, percent encoded
+pub(crate) const DIR_KEY: &str = "X-Trash-Directory";
+
+/// The directory inside of `files` an item deleted from `original` is
+/// stored in with `TrashLayout::Hierarchical`: the directory it was
+/// deleted from, made relative
+fn stored_dir(original: &Path) -> PathBuf {
+    original
+        .parent()
+        .unwrap_or(original)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::reserve_filename;
-    use crate::{move_to_trash, TrashInfo};
+    use crate::dirs::TrashDirs;
+    use crate::{
+        empty_trash_in, list_trash, list_trash_in, move_to_trash, move_to_trash_in,
+        restore_from_trash, restore_from_trash_in, ParseTrashInfoError, RecordedPath, TrashInfo,
+        TrashLocation,
+    };
+    use crate::{reserve_filename, CollisionStrategy};
     use std::ffi::OsString;
     use std::io::{Read, Write};
     use std::path::PathBuf;
-    use tempfile::tempdir;
+    use std::sync::{Mutex, MutexGuard};
+    use tempfile::{tempdir, TempDir};
+
+    /// Tests point XDG_DATA_HOME at their own temp dir, so they must not overlap
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A directory for the files of a test and a custom trash to put them
+    /// in, both removed when dropped
+    pub(crate) fn fixture() -> (TempDir, TempDir, TrashLocation) {
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().to_path_buf());
+        (file_dir, trash_dir, location)
+    }
 
     /*
     #[test]
@@ -297,11 +1921,19 @@ mod tests {
 
     #[test]
     fn test_path_creation_no_existing() {
+        let _env = lock_env();
         let temp_dir = tempdir().expect("temp dir creation failed");
 
         std::env::set_var("XDG_DATA_HOME", temp_dir.path().as_os_str());
         let p = PathBuf::from("test.txt");
-        let info_file = reserve_filename(p.as_path());
+        let trash_dir = temp_dir.path().join("Trash");
+        let dirs = TrashDirs::open(&trash_dir).unwrap();
+        let info_file = reserve_filename(
+            &dirs,
+            p.as_path(),
+            CollisionStrategy::Counter,
+            crate::date::now(),
+        );
         let filename = info_file
             .map_err(|e| format!("Failed to create file: {:?}", e))
             .unwrap();
@@ -317,6 +1949,7 @@ mod tests {
     #[test]
     fn test_full_trash() {
         use std::os::unix::ffi::OsStringExt;
+        let _env = lock_env();
         let file_dir = tempdir().expect("temp dir creation failed");
         let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
 
@@ -329,11 +1962,13 @@ mod tests {
             .collect::<PathBuf>();
         {
             let mut f = std::fs::File::create(&file_path)
-                .expect(&format!("Failed to create '{:?}'", file_path));
-            f.write(b"hello\n").unwrap();
+                .unwrap_or_else(|_| panic!("Failed to create '{:?}'", file_path));
+            f.write_all(b"hello\n").unwrap();
         }
 
         let res = move_to_trash(&file_path).unwrap();
+        assert_eq!(res.location, TrashLocation::Home);
+        assert_eq!(res.trash_dir, temp_xdg_data_home.path().join("Trash"));
 
         // Check that the trash file and info file are as expected
         let mut content = String::new();
@@ -344,7 +1979,7 @@ mod tests {
 
         let mut info_content = String::new();
         std::fs::File::open(&res.info_file)
-            .expect(&format!("file: {:?} does not exist", res.info_file))
+            .unwrap_or_else(|_| panic!("file: {:?} does not exist", res.info_file))
             .read_to_string(&mut info_content)
             .unwrap();
         let trash_info =
@@ -358,4 +1993,524 @@ mod tests {
         assert_eq!(content, "hello\n");
         assert_eq!(trash_info.path, file_path);
     }
+
+    #[test]
+    fn test_list_and_restore() {
+        let _env = lock_env();
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
+        std::env::set_var("XDG_DATA_HOME", temp_xdg_data_home.path().as_os_str());
+
+        let file_path = file_dir.path().join("restore me.txt");
+        std::fs::write(&file_path, b"hello\n").unwrap();
+        let dir_path = file_dir.path().join("restore dir");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("inner.txt"), b"inner\n").unwrap();
+        move_to_trash(&file_path).unwrap();
+        move_to_trash(&dir_path).unwrap();
+
+        let items = list_trash().unwrap();
+        assert_eq!(items.len(), 2);
+
+        for item in items {
+            let restored = restore_from_trash(item.internal_filename()).unwrap();
+            assert_eq!(restored, item.original_path());
+        }
+        assert!(list_trash().unwrap().is_empty());
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"hello\n");
+        assert_eq!(
+            std::fs::read(dir_path.join("inner.txt")).unwrap(),
+            b"inner\n"
+        );
+    }
+
+    #[test]
+    fn test_custom_location() {
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().join("my trash"));
+
+        let file_path = file_dir.path().join("custom.txt");
+        std::fs::write(&file_path, b"hello\n").unwrap();
+        let res = move_to_trash_in(&file_path, &location).unwrap();
+        assert_eq!(res.location, location);
+        assert_eq!(
+            res.trash_file,
+            trash_dir.path().join("my trash/files/custom.txt")
+        );
+
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 1);
+        restore_from_trash_in(items[0].internal_filename(), &location).unwrap();
+        assert!(file_path.exists());
+
+        move_to_trash_in(&file_path, &location).unwrap();
+        assert_eq!(empty_trash_in(&location).unwrap().len(), 1);
+        assert!(list_trash_in(&location).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_deletion_date_offsets() {
+        let naive = chrono::NaiveDate::from_ymd_opt(2019, 1, 15)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        let info = TrashInfo::builder()
+            .path("/tmp/a")
+            .deletion_date(naive)
+            .build()
+            .unwrap();
+        assert_eq!(info.deletion_date_local().naive_local(), naive);
+        assert_eq!(
+            info.deletion_date_utc(),
+            info.deletion_date_local().with_timezone(&chrono::Utc)
+        );
+    }
+
+    #[test]
+    fn test_info_files_written_whole() {
+        let (file_dir, trash_dir, location) = fixture();
+
+        let file_path = file_dir.path().join("twice.txt");
+        for _ in 0..2 {
+            std::fs::write(&file_path, b"again\n").unwrap();
+            move_to_trash_in(&file_path, &location).unwrap();
+        }
+        let mut names: Vec<_> = std::fs::read_dir(trash_dir.path().join("info"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["twice.txt.2.trashinfo", "twice.txt.trashinfo"]);
+        for item in list_trash_in(&location).unwrap() {
+            assert_eq!(item.original_path(), file_path);
+        }
+    }
+
+    #[test]
+    fn test_unknown_keys_kept() {
+        let content = "[Trash Info]\nPath=/home/me/a%20b\nX-Other-Tool=keep me; please\n\
+                       DeletionDate=2020-01-02T03:04:05\nOrigin = elsewhere\n";
+        let info = TrashInfo::from_filename_and_content("a b".into(), content).unwrap();
+        assert_eq!(
+            info.extra,
+            [
+                ("X-Other-Tool".to_owned(), "keep me; please".to_owned()),
+                ("Origin".to_owned(), "elsewhere".to_owned())
+            ]
+        );
+
+        let dir = tempdir().expect("temp dir creation failed");
+        let info_path = dir.path().join("a b.trashinfo");
+        info.write_to(std::fs::File::create(&info_path).unwrap())
+            .unwrap();
+        let written = std::fs::read_to_string(&info_path).unwrap();
+        assert!(written.ends_with("X-Other-Tool=keep me; please\nOrigin=elsewhere\n"));
+        assert_eq!(info.to_string(), written);
+        let reread = TrashInfo::from_path(&info_path).unwrap();
+        assert_eq!(reread.internal_filename, "a b");
+        assert_eq!(reread.extra, info.extra);
+        assert_eq!(reread.path, info.path);
+    }
+
+    #[test]
+    fn test_write_to_passes_errors_on() {
+        struct Full;
+
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::from(std::io::ErrorKind::StorageFull))
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let info = TrashInfo::builder().path("/home/me/a").build().unwrap();
+        let err = info.write_to(Full).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::StorageFull);
+        let mut buffer = Vec::new();
+        info.write_to(&mut buffer).unwrap();
+        assert_eq!(buffer, info.to_string().into_bytes());
+    }
+
+    #[test]
+    fn test_hostile_info_files() {
+        use crate::{EscapePolicy, ParseTrashInfoError, TrashError};
+
+        let parse = |content: &str| TrashInfo::from_filename_and_content("a".into(), content);
+        let huge = format!(
+            "[Trash Info]\nPath=/a\nDeletionDate=2020-01-02T03:04:05\nX-Pad={}\n",
+            "x".repeat(1 << 20)
+        );
+        assert!(matches!(
+            parse(&huge),
+            Err(ParseTrashInfoError::TooLarge { .. })
+        ));
+        let many = format!(
+            "[Trash Info]\nPath=/a\nDeletionDate=2020-01-02T03:04:05\n{}",
+            "X-Key=value\n".repeat(1000)
+        );
+        assert!(matches!(
+            parse(&many),
+            Err(ParseTrashInfoError::TooManyKeys { .. })
+        ));
+        let escape = "[Trash Info]\nPath=/a%zz\nDeletionDate=2020-01-02T03:04:05\n";
+        assert!(matches!(
+            parse(escape),
+            Err(ParseTrashInfoError::InvalidEscape { key: "Path", .. })
+        ));
+        let lossy =
+            TrashInfo::from_filename_and_content_with("a".into(), escape, EscapePolicy::Lossy)
+                .unwrap();
+        assert_eq!(lossy.path, "/a%zz");
+        assert_eq!(lossy.malformed_path, Some(EscapePolicy::Lossy));
+
+        // Not read whole from disk either
+        let dir = tempdir().expect("temp dir creation failed");
+        let info_path = dir.path().join("a.trashinfo");
+        std::fs::write(&info_path, &huge).unwrap();
+        assert!(matches!(
+            TrashInfo::from_path(&info_path),
+            Err(TrashError::ParseInfo(ParseTrashInfoError::TooLarge { .. }))
+        ));
+
+        // A literal % is escaped so that it reads back
+        let info = TrashInfo::builder().path("/home/me/100%").build().unwrap();
+        let reread = parse(&info.to_string()).unwrap();
+        assert_eq!(reread.path, "/home/me/100%");
+        assert_eq!(reread.malformed_path, None);
+    }
+
+    #[test]
+    fn test_relative_paths_parsed() {
+        let parse = |path: &str| {
+            let content = format!(
+                "[Trash Info]\nPath={}\nDeletionDate=2020-01-02T03:04:05\n",
+                path
+            );
+            TrashInfo::from_filename_and_content("a".into(), &content)
+        };
+        let relative = parse("photos/a%20b.jpg").unwrap();
+        assert_eq!(
+            relative.recorded_path(),
+            RecordedPath::Relative(std::path::Path::new("photos/a b.jpg"))
+        );
+        let absolute = parse("/media/usb/photos/a.jpg").unwrap();
+        assert_eq!(
+            absolute.recorded_path(),
+            RecordedPath::Absolute(std::path::Path::new("/media/usb/photos/a.jpg"))
+        );
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_context() {
+        let err = TrashInfo::from_filename_and_content(
+            "a.trashinfo".into(),
+            "[Trash Info]\nDeletionDate=2020-01-02T03:04:05\n",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            ParseTrashInfoError::MissingKey { filename, key: "Path" } if filename == "a.trashinfo"
+        ));
+        assert_eq!(err.to_string(), "\"a.trashinfo\" has no Path key");
+        let err =
+            TrashInfo::from_filename_and_content("b.trashinfo".into(), "[Other]\n").unwrap_err();
+        assert_eq!(err.to_string(), "\"b.trashinfo\" has no [Trash Info] group");
+        let err = TrashInfo::from_filename_and_content(
+            "c".into(),
+            "[Trash Info]\nPath=/c\nDeletionDate=yesterday\n",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &err,
+            ParseTrashInfoError::InvalidDate { filename, value } if filename == "c" && value == "yesterday"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "\"c\" has an invalid DeletionDate \"yesterday\""
+        );
+    }
+
+    #[test]
+    fn test_keep_original() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("draft.txt");
+        std::fs::write(&path, b"before").unwrap();
+
+        let options = crate::TrashOptions::new()
+            .location(location.clone())
+            .keep_original(true);
+        let files = crate::move_to_trash_with(&path, &options).unwrap();
+        assert!(files.stats.copied);
+        std::fs::write(&path, b"after").unwrap();
+        assert_eq!(std::fs::read(&files.trash_file).unwrap(), b"before");
+
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path(), path.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("gone");
+        match move_to_trash_in(&path, &location) {
+            Err(crate::TrashError::SourceNotFound(missing)) => assert_eq!(missing, path),
+            other => panic!("expected SourceNotFound, got {:?}", other),
+        }
+        assert!(list_trash_in(&location).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dot_components() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let base = file_dir.path().canonicalize().unwrap();
+
+        let dir = base.join("dir");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let files = move_to_trash_in(dir.join("sub/."), &location).unwrap();
+        assert_eq!(files.trash_file.file_name().unwrap(), "sub");
+        let files = move_to_trash_in(base.join("dir/./"), &location).unwrap();
+        assert_eq!(files.trash_file.file_name().unwrap(), "dir");
+
+        std::fs::create_dir_all(base.join("a/b")).unwrap();
+        let files = move_to_trash_in(base.join("a/b/.."), &location).unwrap();
+        assert_eq!(files.trash_file.file_name().unwrap(), "a");
+
+        // A dangling symlink is recorded as itself
+        let link = base.join("link");
+        std::os::unix::fs::symlink(base.join("nowhere"), &link).unwrap();
+        move_to_trash_in(&link, &location).unwrap();
+
+        let mut originals: Vec<PathBuf> = list_trash_in(&location)
+            .unwrap()
+            .iter()
+            .map(|item| item.original_path().to_path_buf())
+            .collect();
+        originals.sort();
+        assert_eq!(
+            originals,
+            vec![base.join("a"), dir.clone(), dir.join("sub"), link]
+        );
+        assert!(move_to_trash_in("/", &location).is_err());
+    }
+
+    #[test]
+    fn test_failed_move_leaves_no_info() {
+        let (file_dir, trash_dir, location) = fixture();
+        // A file where the files directory should be makes the move fail
+        std::fs::write(trash_dir.path().join("files"), b"").unwrap();
+
+        let file_path = file_dir.path().join("stays.txt");
+        std::fs::write(&file_path, b"here\n").unwrap();
+        assert!(move_to_trash_in(&file_path, &location).is_err());
+        assert!(file_path.exists());
+        assert!(list_trash_in(&location).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_copy_leaves_nothing() {
+        let src_dir = tempdir().expect("temp dir creation failed");
+        let dest_dir = tempdir().expect("temp dir creation failed");
+        std::fs::write(src_dir.path().join("a"), b"copied first").unwrap();
+        // A socket cannot be opened for reading, so the copy fails on it
+        let _socket =
+            std::os::unix::net::UnixListener::bind(src_dir.path().join("socket")).unwrap();
+
+        let dest = dest_dir.path().join("copy");
+        let options = crate::TrashOptions::new();
+        assert!(crate::copy_tree(src_dir.path(), &dest, &options, None).is_err());
+        assert!(dest.symlink_metadata().is_err());
+
+        // An existing destination is none of our business
+        std::fs::write(&dest, b"keep").unwrap();
+        assert!(crate::copy_tree(&src_dir.path().join("a"), &dest, &options, None).is_err());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"keep");
+    }
+
+    #[test]
+    fn test_trash_dir_error() {
+        use crate::{trash_dir_error, TrashError};
+        use std::io::{Error, ErrorKind};
+
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        // Missing directories are blamed on the closest one that exists
+        let info_dir = trash_dir.path().join("info");
+        assert!(matches!(
+            trash_dir_error(&info_dir, Error::from(ErrorKind::PermissionDenied)),
+            TrashError::TrashNotWritable(ref path) if path == trash_dir.path()
+        ));
+        std::fs::create_dir(&info_dir).unwrap();
+        assert!(matches!(
+            trash_dir_error(&info_dir, Error::from(ErrorKind::ReadOnlyFilesystem)),
+            TrashError::TrashNotWritable(ref path) if *path == info_dir
+        ));
+        assert!(matches!(
+            trash_dir_error(&info_dir, Error::from(ErrorKind::StorageFull)),
+            TrashError::NoSpace(ref path) if *path == info_dir
+        ));
+        assert!(matches!(
+            trash_dir_error(&info_dir, Error::from(ErrorKind::InvalidData)),
+            TrashError::Io(_)
+        ));
+    }
+
+    #[test]
+    fn test_wait_while_busy() {
+        use std::io::{Error, ErrorKind};
+        use std::time::Duration;
+
+        let busy = || fs_extra::error::Error::from(Error::from(ErrorKind::ResourceBusy));
+        let mut tries = 0;
+        let result: Result<(), _> = crate::wait_while_busy(None, || {
+            tries += 1;
+            Err(busy())
+        });
+        assert!(result.is_err());
+        assert_eq!(tries, 1);
+
+        tries = 0;
+        let result = crate::wait_while_busy(Some(Duration::from_secs(10)), || {
+            tries += 1;
+            if tries < 3 {
+                Err(busy())
+            } else {
+                Ok(tries)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        tries = 0;
+        let result: Result<(), _> = crate::wait_while_busy(Some(Duration::from_secs(10)), || {
+            tries += 1;
+            Err(Error::from(ErrorKind::PermissionDenied).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(tries, 1);
+        assert!(crate::is_busy(&Error::from(ErrorKind::ExecutableFileBusy)));
+    }
+
+    #[test]
+    fn test_retry_transient() {
+        use crate::RetryPolicy;
+        use std::io::{Error, ErrorKind};
+        use std::time::Duration;
+
+        let policy = RetryPolicy {
+            retries: 3,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        };
+        let mut tries = 0;
+        let result = crate::retry_transient(&policy, || {
+            tries += 1;
+            match tries {
+                1 => Err(Error::from(ErrorKind::Interrupted).into()),
+                2 => Err(Error::from(ErrorKind::WouldBlock).into()),
+                _ => Ok(tries),
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        tries = 0;
+        let result: Result<(), _> = crate::retry_transient(&policy, || {
+            tries += 1;
+            Err(Error::from(ErrorKind::TimedOut).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(tries, 4);
+
+        tries = 0;
+        let result: Result<(), _> = crate::retry_transient(&policy, || {
+            tries += 1;
+            Err(Error::from(ErrorKind::PermissionDenied).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(tries, 1);
+    }
+
+    #[test]
+    fn test_empty_trash_on_device() {
+        let topdir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::TopDir(topdir.path().to_path_buf());
+        let file_path = topdir.path().join("on-device.txt");
+        std::fs::write(&file_path, b"x").unwrap();
+        move_to_trash_in(&file_path, &location).unwrap();
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+
+        // The temp dir is not a mount point, so skip finding one
+        let purged = crate::empty_topdir_trashes(topdir.path().to_path_buf()).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert!(list_trash_in(&location).unwrap().is_empty());
+        assert!(crate::empty_trash_on(topdir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_both_topdir_trashes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let topdir = tempdir().expect("temp dir creation failed");
+        let shared = topdir.path().join(".Trash");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o1777)).unwrap();
+        let user = TrashLocation::TopDirUser(topdir.path().to_path_buf());
+        let old = topdir.path().join("old.txt");
+        std::fs::write(&old, b"x").unwrap();
+        move_to_trash_in(&old, &user).unwrap();
+        let location = TrashLocation::TopDir(topdir.path().to_path_buf());
+        let new = topdir.path().join("new.txt");
+        std::fs::write(&new, b"x").unwrap();
+        move_to_trash_in(&new, &location).unwrap();
+
+        let uid = crate::location::current_uid();
+        assert_eq!(location.trash_dir().unwrap(), shared.join(uid.to_string()));
+        assert_eq!(
+            user.trash_dir().unwrap(),
+            topdir.path().join(format!(".Trash-{}", uid))
+        );
+        let listed = list_trash_in(&user).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].original_path(), old);
+
+        let mut purged: Vec<_> = crate::empty_topdir_trashes(topdir.path().to_path_buf())
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.location().clone(), item.original_path().to_path_buf()))
+            .collect();
+        purged.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(purged, [(location, new), (user, old)]);
+    }
+
+    #[test]
+    fn test_no_home_trash() {
+        let _env = lock_env();
+        let home = std::env::var_os("HOME");
+        let data_home = std::env::var_os("XDG_DATA_HOME");
+        std::env::remove_var("HOME");
+        std::env::remove_var("XDG_DATA_HOME");
+
+        let (file_dir, fallback, _) = fixture();
+        let file_path = file_dir.path().join("homeless.txt");
+        std::fs::write(&file_path, b"x").unwrap();
+        let options = crate::TrashOptions::new().location(TrashLocation::Home);
+        let result = crate::move_to_trash_with(&file_path, &options);
+        let with_fallback = options.home_trash_fallback(Some(fallback.path().to_path_buf()));
+        let fallback_result = crate::move_to_trash_with(&file_path, &with_fallback);
+
+        if let Some(home) = home {
+            std::env::set_var("HOME", home);
+        }
+        if let Some(data_home) = data_home {
+            std::env::set_var("XDG_DATA_HOME", data_home);
+        }
+        assert!(matches!(result, Err(crate::TrashError::NoHomeTrash)));
+        let files = fallback_result.unwrap();
+        assert!(files.trash_file.starts_with(fallback.path()));
+        assert!(!file_path.exists());
+    }
 }
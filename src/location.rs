@@ -0,0 +1,667 @@
+pub(crate) use crate::platform::is_writable;
+use crate::platform::{self, DirBuilderExt, MetadataExt, PermissionsExt};
+use crate::{home_trash_dir, TrashConfig, TrashError};
+use std::collections::HashMap;
+use std::fs::DirBuilder;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Which trash a file was sent to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrashLocation {
+    /// The user's home trash, `$XDG_DATA_HOME/Trash`
+    Home,
+    /// The trash at the top directory of another mounted device, either
+    /// `$topdir/.Trash/$uid` or `$topdir/.Trash-$uid`, whichever is used
+    TopDir(PathBuf),
+    /// `$topdir/.Trash-$uid` of another mounted device, even where
+    /// `$topdir/.Trash/$uid` is used, as it can still hold items from
+    /// before that was set up
+    TopDirUser(PathBuf),
+    /// A trash directory at an arbitrary path, laid out like the others
+    /// with `files` and `info` directories inside of it
+    Custom(PathBuf),
+}
+
+impl TrashLocation {
+    /// Root of this trash, containing its `files` and `info` directories
+    /// Nothing is created, so the directory may not exist yet
+    pub fn trash_dir(&self) -> Result<PathBuf, TrashError> {
+        Ok(match self {
+            TrashLocation::Home => home_trash_dir().ok_or(TrashError::NoHomeTrash)?,
+            TrashLocation::TopDir(topdir) => {
+                let uid = current_uid().to_string();
+                let shared = topdir.join(".Trash").join(&uid);
+                if shared.is_dir() {
+                    return Ok(shared);
+                }
+                user_trash_dir(topdir, current_uid())
+            }
+            TrashLocation::TopDirUser(topdir) => user_trash_dir(topdir, current_uid()),
+            TrashLocation::Custom(trash_dir) => trash_dir.clone(),
+        })
+    }
+}
+
+/// Id of the user running this process
+pub(crate) fn current_uid() -> u32 {
+    platform::getuid()
+}
+
+/// The user whose trash receives a file
+#[derive(Debug, Clone)]
+pub(crate) struct TrashOwner {
+    pub uid: u32,
+    pub gid: u32,
+    /// Where this user's home trash lives, if they have one
+    pub home_trash: Option<PathBuf>,
+}
+
+impl TrashOwner {
+    /// The user running this process
+    pub fn current() -> Self {
+        Self {
+            uid: current_uid(),
+            gid: platform::getgid(),
+            home_trash: home_trash_dir(),
+        }
+    }
+
+    /// The owner of `path`, when running as root and that is somebody else
+    /// Otherwise the user running this process
+    pub fn of_file(path: &Path) -> std::io::Result<Self> {
+        let meta = path.symlink_metadata()?;
+        if current_uid() != 0 || meta.uid() == 0 {
+            return Ok(Self::current());
+        }
+        // Their XDG_DATA_HOME is unknown to us, so assume the default
+        let home = home_dir_of(meta.uid()).ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("no home directory for user {}", meta.uid()),
+            )
+        })?;
+        Ok(Self {
+            uid: meta.uid(),
+            gid: meta.gid(),
+            home_trash: Some(home.join(".local/share/Trash")),
+        })
+    }
+
+    /// Whether files we create need to be handed over to this user
+    pub fn needs_chown(&self) -> bool {
+        self.uid != current_uid()
+    }
+
+    /// Creates `dir` and any missing parents, giving them to this user
+    pub fn create_dir_all(&self, dir: &Path) -> std::io::Result<()> {
+        if dir.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = dir.parent() {
+            self.create_dir_all(parent)?;
+        }
+        match std::fs::create_dir(dir) {
+            Ok(()) => self.chown(dir),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Hands a file we created over to this user
+    pub fn chown(&self, path: &Path) -> std::io::Result<()> {
+        if self.needs_chown() {
+            platform::lchown(path, self.uid, self.gid)?;
+        }
+        Ok(())
+    }
+
+    /// Hands a file we created and still have open over to this user
+    pub(crate) fn chown_file(&self, file: &std::fs::File) -> std::io::Result<()> {
+        if self.needs_chown() {
+            platform::fchown(file, self.uid, self.gid)?;
+        }
+        Ok(())
+    }
+
+    /// Hands a file and everything in it over to this user
+    pub fn chown_all(&self, path: &Path) -> std::io::Result<()> {
+        self.chown(path)?;
+        if path.symlink_metadata()?.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                self.chown_all(&entry?.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Trash directories of the user running this process, resolved once
+/// and reused across operations
+#[derive(Debug)]
+pub(crate) struct TrashCache {
+    /// Mount holding the home trash
+    home_mount: Option<MountId>,
+    /// Mount root of each mount seen, and the trash used there if any
+    topdirs: Mutex<HashMap<MountId, (PathBuf, Option<PathBuf>)>>,
+}
+
+impl TrashCache {
+    pub fn new(owner: &TrashOwner) -> Self {
+        let home_mount = owner
+            .home_trash
+            .as_deref()
+            .and_then(|home_trash| MountId::of_dir(home_trash).ok());
+        Self {
+            home_mount,
+            topdirs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Tells mounts apart: a device id alone does not, as a bind mount or
+/// btrfs subvolume can share it with a mount files cannot be renamed across
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MountId {
+    dev: u64,
+    /// Where the path is mounted according to the mount table, if known
+    mount_point: Option<PathBuf>,
+}
+
+impl MountId {
+    /// The mount holding a directory, or its closest existing ancestor
+    pub fn of_dir(dir: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            dev: device_of(dir)?,
+            mount_point: mounted_at(&resolve(dir)),
+        })
+    }
+
+    /// The mount holding a file, without following it if it is a symlink
+    pub fn of_file(path: &Path) -> std::io::Result<Self> {
+        let dev = path.symlink_metadata()?.dev();
+        let location = match path.file_name() {
+            Some(name) => resolve(parent_dir(path)).join(name),
+            None => resolve(path),
+        };
+        Ok(Self {
+            dev,
+            mount_point: mounted_at(&location),
+        })
+    }
+}
+
+/// Directory containing `path`, `.` for a bare file name
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => path,
+    }
+}
+
+/// Canonical form of a path which may not exist yet: its closest
+/// existing ancestor canonicalized, followed by the rest of it
+fn resolve(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name));
+        }
+        match existing.file_name() {
+            Some(name) => {
+                rest.push(name);
+                existing = parent_dir(existing);
+            }
+            None => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Every trash of the user running this process: the home trash if
+/// there is one, followed by the top directory trashes which exist on
+/// mounted filesystems, in the order of the mount table
+/// Mounts listed in `excluded_mounts` of the configuration are skipped
+pub fn all_trash_locations() -> Vec<TrashLocation> {
+    let owner = TrashOwner::current();
+    let config = TrashConfig::current();
+    let mut locations = Vec::new();
+    let mut seen = Vec::new();
+    let mut unseen = |dir: &Path| match dir.metadata() {
+        Ok(meta) if meta.is_dir() => {
+            let id = (meta.dev(), meta.ino());
+            let new = !seen.contains(&id);
+            seen.push(id);
+            new
+        }
+        _ => false,
+    };
+    if let Some(home_trash) = &owner.home_trash {
+        unseen(home_trash);
+        locations.push(TrashLocation::Home);
+    }
+    for topdir in mount_points() {
+        if config.is_excluded(&topdir) {
+            continue;
+        }
+        let trash_dir = match topdir_trash(&topdir, &owner, false) {
+            Some(trash_dir) => trash_dir,
+            None => continue,
+        };
+        if unseen(&trash_dir) {
+            locations.push(TrashLocation::TopDir(topdir.clone()));
+        }
+        // Where `$topdir/.Trash/$uid` is used, `$topdir/.Trash-$uid`
+        // can still hold items from before it was set up
+        let user_dir = user_trash_dir(&topdir, owner.uid);
+        if user_dir != trash_dir && unseen(&user_dir) {
+            locations.push(TrashLocation::TopDirUser(topdir));
+        }
+    }
+    locations
+}
+
+/// Every mount point in the mount table
+fn mount_points() -> Vec<PathBuf> {
+    let table = match std::fs::read("/proc/self/mountinfo") {
+        Ok(table) => table,
+        Err(_) => return Vec::new(),
+    };
+    table
+        .split(|&b| b == b'\n')
+        .filter_map(|line| line.split(|&b| b == b' ').nth(4))
+        .map(unescape_mount_path)
+        .collect()
+}
+
+/// The path a file would be recorded under if it was trashed now,
+/// whether or not it exists: its directory resolved, followed by its name
+pub(crate) fn expected_original(path: &Path) -> PathBuf {
+    match path.file_name() {
+        Some(name) => resolve(parent_dir(path)).join(name),
+        None => resolve(path),
+    }
+}
+
+/// The deepest mount point in the mount table containing an absolute path
+/// `None` where there is no `/proc/self/mountinfo` to read
+fn mounted_at(path: &Path) -> Option<PathBuf> {
+    mount_entry(path).map(|(mount_point, _)| mount_point)
+}
+
+/// Mount point and filesystem type of the deepest mount containing a path
+fn mount_entry(path: &Path) -> Option<(PathBuf, String)> {
+    let table = std::fs::read("/proc/self/mountinfo").ok()?;
+    table
+        .split(|&b| b == b'\n')
+        .filter_map(|line| {
+            let fields: Vec<&[u8]> = line.split(|&b| b == b' ').collect();
+            let mount_point = unescape_mount_path(fields.get(4)?);
+            // Optional fields end with a lone `-`, followed by the type
+            let separator = fields.iter().position(|field| *field == b"-")?;
+            let fs_type = String::from_utf8_lossy(fields.get(separator + 1)?).into_owned();
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+}
+
+/// Whether a path, or its closest existing ancestor, is on a network
+/// filesystem such as NFS, CIFS/SMB or sshfs
+pub(crate) fn is_network_fs(path: &Path) -> bool {
+    const NETWORK_MAGIC: &[i64] = &[
+        0x6969,      // NFS
+        0x517b,      // SMB
+        0xff53_4d42, // CIFS
+        0xfe53_4d42, // SMB2
+        0x564c,      // NCP
+        0x6b41_4653, // AFS
+        0x00c3_6400, // Ceph
+        0x0102_1997, // 9P
+    ];
+    const NETWORK_TYPES: &[&str] = &[
+        "nfs",
+        "nfs4",
+        "cifs",
+        "smb3",
+        "smbfs",
+        "ncpfs",
+        "afs",
+        "ceph",
+        "9p",
+        "glusterfs",
+        "fuse.sshfs",
+        "fuse.rclone",
+        "fuse.glusterfs",
+        "davfs",
+        "fuse.davfs2",
+    ];
+
+    let resolved = resolve(path);
+    if let Some(magic) = statfs_type(&resolved) {
+        if NETWORK_MAGIC.contains(&magic) {
+            return true;
+        }
+    }
+    match mount_entry(&resolved) {
+        Some((_, fs_type)) => NETWORK_TYPES.contains(&fs_type.as_str()),
+        None => false,
+    }
+}
+
+/// Filesystem type magic number of a path, from statfs
+#[cfg(unix)]
+fn statfs_type(path: &Path) -> Option<i64> {
+    use crate::platform::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_type as i64)
+}
+
+#[cfg(not(unix))]
+fn statfs_type(_path: &Path) -> Option<i64> {
+    None
+}
+
+/// Undoes the octal escapes of spaces, tabs, newlines and backslashes
+/// in mount table paths
+fn unescape_mount_path(escaped: &[u8]) -> PathBuf {
+    use crate::platform::OsStringExt;
+
+    let mut path = Vec::with_capacity(escaped.len());
+    let mut i = 0;
+    while i < escaped.len() {
+        let octal = escaped.get(i + 1..i + 4).filter(|digits| {
+            escaped[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d))
+        });
+        match octal {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u32, |value, d| value * 8 + u32::from(d - b'0'));
+                path.push(value as u8);
+                i += 4;
+            }
+            None => {
+                path.push(escaped[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(path))
+}
+
+/// Home directory of a user from the password database
+pub(crate) fn home_dir_of(uid: u32) -> Option<PathBuf> {
+    passwd_entry(uid).map(|(_, home)| home)
+}
+
+/// Name of a user from the password database
+pub(crate) fn user_name(uid: u32) -> Option<String> {
+    passwd_entry(uid).map(|(name, _)| name)
+}
+
+/// Name and home directory of a user from the password database
+#[cfg(unix)]
+fn passwd_entry(uid: u32) -> Option<(String, PathBuf)> {
+    use crate::platform::OsStrExt;
+    use std::ffi::CStr;
+
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let ret = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() || pwd.pw_dir.is_null() || pwd.pw_name.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+    let home = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some((
+        name.to_string_lossy().into_owned(),
+        PathBuf::from(std::ffi::OsStr::from_bytes(home.to_bytes())),
+    ))
+}
+
+#[cfg(not(unix))]
+fn passwd_entry(_uid: u32) -> Option<(String, PathBuf)> {
+    None
+}
+
+/// Device id of a path, or of its closest existing ancestor
+/// (the home trash may not have been created yet)
+pub(crate) fn device_of(path: &Path) -> std::io::Result<u64> {
+    let mut path = path;
+    loop {
+        match path.symlink_metadata() {
+            Ok(meta) => return Ok(meta.dev()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => match path.parent() {
+                Some(parent) => path = parent,
+                None => return Err(std::io::Error::from(ErrorKind::NotFound)),
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The mount point containing `path`: its highest ancestor on the same
+/// device, or a deeper mount point from the mount table such as the
+/// target of a bind mount
+pub(crate) fn mount_root(path: &Path) -> std::io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let dev = path.metadata()?.dev();
+    let mut root = path.as_path();
+    while let Some(parent) = root.parent() {
+        if parent.metadata()?.dev() != dev {
+            break;
+        }
+        root = parent;
+    }
+    match mounted_at(&path) {
+        Some(mount_point) if mount_point.starts_with(root) => Ok(mount_point),
+        _ => Ok(root.to_path_buf()),
+    }
+}
+
+/// Whether a file can be renamed into a directory, as far as can be told
+/// before trying: both must be on the same device and the same mount
+pub(crate) fn same_mount(path: &Path, dir: &Path) -> std::io::Result<bool> {
+    Ok(MountId::of_file(path)? == MountId::of_dir(dir)?)
+}
+
+/// Picks the trash for a file following the freedesktop.org trash spec:
+/// the home trash for files on the same mount as it, otherwise the
+/// trash in the top directory of the file's mount
+/// Falls back to the home trash when no top directory trash can be used
+/// and `allow_fallback` agrees, given the top directory
+/// Without a home trash only top directory trashes can be used
+/// Unless `create` is set, no trash directory is created, and one
+/// which could be created is picked as if it had been
+/// With a cache, directories found for `owner` before are reused
+/// Returns the trash, and whether it is the home trash as a fallback
+pub(crate) fn select_trash(
+    path: &Path,
+    owner: &TrashOwner,
+    create: bool,
+    cache: Option<&TrashCache>,
+    allow_fallback: &dyn Fn(&Path) -> bool,
+) -> Result<(TrashLocation, PathBuf, bool), TrashError> {
+    let file_mount = MountId::of_file(path)?;
+    if let Some(home_trash) = &owner.home_trash {
+        let home_mount = match cache.and_then(|cache| cache.home_mount.clone()) {
+            Some(home_mount) => home_mount,
+            None => MountId::of_dir(home_trash)?,
+        };
+        if file_mount == home_mount {
+            return Ok((TrashLocation::Home, home_trash.clone(), false));
+        }
+    }
+
+    // Symlinks live on the device of the directory containing them
+    let parent = parent_dir(path);
+    let (topdir, trash_dir) = match cache {
+        Some(cache) => {
+            let parent_mount = MountId::of_dir(parent)?;
+            let mut topdirs = cache.topdirs.lock().unwrap_or_else(|e| e.into_inner());
+            match topdirs.get(&parent_mount) {
+                Some(found) => found.clone(),
+                None => {
+                    let topdir = mount_root(parent)?;
+                    let trash_dir = topdir_trash(&topdir, owner, create);
+                    // What a dry run would pick may not exist yet
+                    if create {
+                        topdirs.insert(parent_mount, (topdir.clone(), trash_dir.clone()));
+                    }
+                    (topdir, trash_dir)
+                }
+            }
+        }
+        None => {
+            let topdir = mount_root(parent)?;
+            let trash_dir = topdir_trash(&topdir, owner, create);
+            (topdir, trash_dir)
+        }
+    };
+    match trash_dir {
+        Some(trash_dir) => Ok((TrashLocation::TopDir(topdir), trash_dir, false)),
+        None => match &owner.home_trash {
+            Some(_) if !allow_fallback(&topdir) => Err(TrashError::NoTopDirTrash(topdir)),
+            Some(home_trash) => Ok((TrashLocation::Home, home_trash.clone(), true)),
+            None => Err(TrashError::NoHomeTrash),
+        },
+    }
+}
+
+/// Finds or creates a user's trash in a top directory
+pub(crate) fn topdir_trash(topdir: &Path, owner: &TrashOwner, create: bool) -> Option<PathBuf> {
+    let uid = owner.uid.to_string();
+
+    // An administrator provided $topdir/.Trash must be a real directory
+    // with the sticky bit set, otherwise it is not to be trusted
+    let shared = topdir.join(".Trash");
+    if let Ok(meta) = shared.symlink_metadata() {
+        if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+            let trash_dir = shared.join(&uid);
+            if create_private_dir(&trash_dir, owner, create) {
+                return Some(trash_dir);
+            }
+        }
+    }
+
+    user_topdir_trash(topdir, owner, create)
+}
+
+/// Finds or creates a user's `$topdir/.Trash-$uid`
+pub(crate) fn user_topdir_trash(
+    topdir: &Path,
+    owner: &TrashOwner,
+    create: bool,
+) -> Option<PathBuf> {
+    let trash_dir = user_trash_dir(topdir, owner.uid);
+    if create_private_dir(&trash_dir, owner, create) {
+        return Some(trash_dir);
+    }
+    None
+}
+
+/// `$topdir/.Trash-$uid`, whether or not it exists
+pub(crate) fn user_trash_dir(topdir: &Path, uid: u32) -> PathBuf {
+    let mut user_dir_name = std::ffi::OsString::from(".Trash-");
+    user_dir_name.push(uid.to_string());
+    topdir.join(user_dir_name)
+}
+
+/// Makes sure `dir` is a directory (not a symlink) belonging to `owner`,
+/// creating it with 0700 permissions if needed
+/// Without `create`, only checks that it exists or its parent is writable
+fn create_private_dir(dir: &Path, owner: &TrashOwner, create: bool) -> bool {
+    if !create && dir.symlink_metadata().is_err() {
+        return dir.parent().is_some_and(is_writable);
+    }
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => owner.chown(dir).is_ok(),
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => match dir.symlink_metadata() {
+            Ok(meta) => meta.is_dir() && meta.uid() == owner.uid,
+            Err(_) => false,
+        },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        all_trash_locations, home_dir_of, is_network_fs, mount_entry, mount_root, resolve,
+        same_mount, unescape_mount_path,
+    };
+    use crate::TrashLocation;
+    use std::path::Path;
+
+    #[test]
+    fn test_home_dir_of_root() {
+        assert!(home_dir_of(0).is_some());
+    }
+
+    #[test]
+    fn test_mount_root() {
+        assert_eq!(mount_root(Path::new("/")).unwrap(), Path::new("/"));
+        let dir = tempfile::tempdir().unwrap();
+        let root = mount_root(dir.path()).unwrap();
+        assert!(dir.path().canonicalize().unwrap().starts_with(&root));
+    }
+
+    #[test]
+    fn test_mount_table_paths() {
+        assert_eq!(
+            unescape_mount_path(br"/media/my\040disk\134x"),
+            Path::new("/media/my disk\\x")
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+        assert_eq!(resolve(&dir.path().join("a/b")), canonical.join("a/b"));
+
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"x").unwrap();
+        assert!(same_mount(&file, &dir.path().join("trash/files")).unwrap());
+        if let Ok(other) = tempfile::tempdir_in("/dev/shm") {
+            assert!(!same_mount(&file, other.path()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_all_trash_locations() {
+        let locations = all_trash_locations();
+        let unique: std::collections::HashSet<_> = locations
+            .iter()
+            .map(|location| location.trash_dir().unwrap())
+            .collect();
+        assert_eq!(unique.len(), locations.len());
+        for location in &locations {
+            if let TrashLocation::TopDir(topdir) | TrashLocation::TopDirUser(topdir) = location {
+                assert!(location.trash_dir().unwrap().starts_with(topdir));
+            }
+        }
+    }
+
+    #[test]
+    fn test_network_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_network_fs(dir.path()));
+        assert!(!is_network_fs(&dir.path().join("not/yet/created")));
+        if let Some((mount_point, fs_type)) = mount_entry(Path::new("/proc/self")) {
+            assert_eq!(
+                (mount_point.as_path(), fs_type.as_str()),
+                (Path::new("/proc"), "proc")
+            );
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Manifests of what is in the trash, holding what is known about each
+//! item but none of its content, for backup tools to decide what needs
+//! archiving on its own
+//!
+//! A manifest is JSON Lines: a header line `{"format":"trash-manifest",
+//! "version":1}`, then one object per item with
+//!
+//! - `trash_dir`: root of the trash holding the item
+//! - `name`: name of the item inside of the trash
+//! - `trash_file`: where its content is
+//! - `original_path`: where it was deleted from
+//! - `deleted_at`: when, in local time as `YYYY-MM-DDThh:mm:ss`
+//! - `size`: its size in bytes, or `null` if it could not be found out
+//! - `checksums`: recorded metadata naming a checksum, such as
+//!   `X-Checksum-SHA256`, by key
+//!
+//! Paths are percent-encoded as in `.trashinfo` files, so that any path
+//! is written as it is. Fields may be added in later versions, but never
+//! removed or changed without raising the version.
+
+use crate::escape::encode_os;
+use crate::{list_all_trash, list_trash_in, TrashError, TrashLocation, TrashedItem};
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::path::Path;
+
+/// Version of the manifest format written
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Writes a manifest of every trash of the user, see
+/// `all_trash_locations`, returning how many items it lists
+pub fn export_manifest<W: Write>(writer: W) -> Result<usize, TrashError> {
+    write_manifest(&list_all_trash()?, writer)
+}
+
+/// Writes a manifest of a single trash, returning how many items it lists
+pub fn export_manifest_in<W: Write>(
+    location: &TrashLocation,
+    writer: W,
+) -> Result<usize, TrashError> {
+    write_manifest(&list_trash_in(location)?, writer)
+}
+
+fn write_manifest<W: Write>(items: &[TrashedItem], mut writer: W) -> Result<usize, TrashError> {
+    let header = json!({ "format": "trash-manifest", "version": MANIFEST_VERSION });
+    writeln!(writer, "{}", header)?;
+    for item in items {
+        writeln!(writer, "{}", manifest_entry(item))?;
+    }
+    writer.flush()?;
+    Ok(items.len())
+}
+
+fn manifest_entry(item: &TrashedItem) -> Value {
+    let encode = |path: &Path| encode_os(path.as_os_str());
+    let checksums: Map<String, Value> = item
+        .info()
+        .extra
+        .iter()
+        .filter(|(key, _)| is_checksum_key(key))
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+    json!({
+        "trash_dir": encode(item.trash_dir()),
+        "name": encode_os(item.internal_filename()),
+        "trash_file": encode(&item.trash_file()),
+        "original_path": encode(item.original_path()),
+        "deleted_at": item.info().deletion_date_string(),
+        "size": item.size().ok(),
+        "checksums": checksums,
+    })
+}
+
+/// Whether a metadata key names a checksum, going by the words other
+/// tools use for them
+fn is_checksum_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["checksum", "digest", "hash"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{export_manifest_in, move_to_trash_with, TrashOptions};
+    use serde_json::Value;
+
+    #[test]
+    fn test_export_manifest() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("a file");
+        std::fs::write(&path, b"12345").unwrap();
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .metadata("X-Checksum-SHA256", "abc123")
+            .metadata("X-Origin", "test");
+        move_to_trash_with(&path, &options).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(export_manifest_in(&location, &mut out).unwrap(), 1);
+        let lines: Vec<Value> = out
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["format"], "trash-manifest");
+        assert_eq!(lines[0]["version"], 1);
+        let entry = &lines[1];
+        assert_eq!(entry["name"], "a%20file");
+        assert!(entry["original_path"]
+            .as_str()
+            .unwrap()
+            .ends_with("/a%20file"));
+        assert_eq!(entry["size"], 5);
+        assert_eq!(
+            entry["checksums"],
+            serde_json::json!({ "X-Checksum-SHA256": "abc123" })
+        );
+    }
+}
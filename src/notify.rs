@@ -0,0 +1,103 @@
+//! Desktop notifications of items purged by a retention policy, behind
+//! the `notify` feature, so that nothing is permanently deleted without
+//! the user hearing of it
+//! They go through `notify-send` on Linux and the BSDs, and `osascript`
+//! on macOS; failing to notify never fails the purge
+
+use std::io;
+#[cfg(unix)]
+use std::process::{Command, Stdio};
+
+/// Tells the user that `count` items of `bytes` in total were purged
+pub(crate) fn purged(count: usize, bytes: u64) {
+    // Tests purge plenty, without anyone to tell
+    if count == 0 || cfg!(test) {
+        return;
+    }
+    let (summary, body) = message(count, bytes);
+    let _ = send(&summary, &body);
+}
+
+fn message(count: usize, bytes: u64) -> (String, String) {
+    let summary = if count == 1 {
+        "1 item purged from the trash".to_owned()
+    } else {
+        format!("{} items purged from the trash", count)
+    };
+    let body = format!(
+        "{} permanently deleted by the retention policy",
+        human_size(bytes)
+    );
+    (summary, body)
+}
+
+/// Sizes in powers of 1024, as `ls -h` shows them
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn send(summary: &str, body: &str) -> io::Result<()> {
+    run(Command::new("notify-send").args([
+        "--app-name=trash",
+        "--icon=user-trash-full",
+        summary,
+        body,
+    ]))
+}
+
+#[cfg(target_os = "macos")]
+fn send(summary: &str, body: &str) -> io::Result<()> {
+    let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        quote(body),
+        quote(summary)
+    );
+    run(Command::new("osascript").arg("-e").arg(script))
+}
+
+#[cfg(not(unix))]
+fn send(_summary: &str, _body: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run(command: &mut Command) -> io::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::message;
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            message(1, 512),
+            (
+                "1 item purged from the trash".to_owned(),
+                "512 bytes permanently deleted by the retention policy".to_owned()
+            )
+        );
+        assert_eq!(
+            message(3, 5 * 1024 * 1024 + 512 * 1024).1,
+            "5.5 MiB permanently deleted by the retention policy"
+        );
+    }
+}
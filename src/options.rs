@@ -0,0 +1,944 @@
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "async")]
+use crate::spawn::{Spawner, ThreadSpawner};
+use crate::{date, DeletionDate, EscapePolicy, Journal, TrashLocation, RESERVED_KEY_PREFIX};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What to trash when given a symbolic link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Trash the link itself, leaving its target alone
+    TrashLink,
+    /// Trash the file the link points to, leaving a dangling link
+    FollowLink,
+}
+
+/// What to do when the trash is on another filesystem than the file,
+/// so it cannot simply be renamed into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossDevicePolicy {
+    /// Copy the file into the trash, then delete the original
+    Copy,
+    /// Fail with `TrashError::CrossDevice` rather than copying
+    Fail,
+}
+
+/// Whether to use the home trash for a file on another device whose
+/// own top directory trash cannot be used, which means copying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeFallback {
+    /// Fall back to the home trash
+    Always,
+    /// Fail with `TrashError::NoTopDirTrash`
+    Never,
+    /// Ask the callback given to `TrashOptions::on_home_fallback`
+    Ask,
+}
+
+/// How to treat files or trashes on network filesystems such as NFS,
+/// CIFS/SMB or sshfs, where every round trip to the server is slow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkPolicy {
+    /// Trash them like any other file
+    Normal,
+    /// Skip resolving symlinks in the recorded path and flushing to disk
+    Relaxed,
+    /// Fail with `TrashError::NetworkFilesystem`
+    Refuse,
+}
+
+/// How items are arranged in the `files` directory of a trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrashLayout {
+    /// Side by side as the spec has it, `files/photo.jpg`
+    Flat,
+    /// Under the directories they were deleted from, as in
+    /// `files/home/user/Pictures/photo.jpg`, so the trash can be browsed
+    /// like the original tree
+    /// Not part of the spec, so other tools cannot find these items, and a
+    /// trash is best kept to one layout as an item named like a top level
+    /// directory, such as `home`, cannot be stored flat beside the tree
+    Hierarchical,
+}
+
+/// Asked whether to fall back to the home trash
+type FallbackPrompt = Arc<dyn Fn(&Path, &Path) -> bool + Send + Sync>;
+
+/// Names an item for an attempt, see `CollisionStrategy::Custom`
+pub type CollisionNamer = fn(base: &OsStr, attempt: u32) -> OsString;
+
+/// How to name an item when its name is already taken in the trash
+#[derive(Debug, Clone, Copy)]
+pub enum CollisionStrategy {
+    /// Append a counter: `photo.jpg`, `photo.jpg.2`, `photo.jpg.3`
+    Counter,
+    /// Put the counter before the extension: `photo.jpg`, `photo.2.jpg`
+    CounterBeforeExtension,
+    /// Mark copies before the extension as file managers do:
+    /// `photo.jpg`, `photo (copy 2).jpg`, `photo (copy 3).jpg`
+    Copy,
+    /// Append the local time of trashing, then a counter should that be
+    /// taken too: `photo.jpg`, `photo.jpg.20240102T030405`,
+    /// `photo.jpg.20240102T030405.2`
+    Timestamp,
+    /// Names from a function of the name and attempt, for names matching
+    /// what other tools left in the trash
+    /// The first attempt is always the name itself. Names which are empty,
+    /// `.`, `..` or hold a `/` are replaced by `Counter`'s, as are all
+    /// names after a hundred attempts, should the function keep repeating
+    Custom(CollisionNamer),
+}
+
+impl PartialEq for CollisionStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CollisionStrategy::Custom(a), CollisionStrategy::Custom(b)) => {
+                std::ptr::fn_addr_eq(*a, *b)
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for CollisionStrategy {}
+
+/// Attempts after which a custom namer is no longer asked
+const CUSTOM_ATTEMPTS: u32 = 100;
+
+impl CollisionStrategy {
+    /// Internal name to try for the `attempt`th item named `base`,
+    /// starting from 1
+    pub fn internal_name(&self, base: &OsStr, attempt: u32) -> OsString {
+        self.internal_name_at(base, attempt, date::now())
+    }
+
+    /// Like `internal_name`, stamping names with `now` rather than the
+    /// system clock, such as the deletion date of the item
+    pub fn internal_name_at(&self, base: &OsStr, attempt: u32, now: DeletionDate) -> OsString {
+        if attempt <= 1 {
+            return base.to_os_string();
+        }
+        let mut name = OsString::new();
+        match self {
+            CollisionStrategy::Counter => {
+                name.push(base);
+                name.push(format!(".{}", attempt));
+            }
+            CollisionStrategy::CounterBeforeExtension => {
+                before_extension(&mut name, base, &format!(".{}", attempt))
+            }
+            CollisionStrategy::Copy => {
+                before_extension(&mut name, base, &format!(" (copy {})", attempt))
+            }
+            CollisionStrategy::Timestamp => {
+                name.push(base);
+                let stamp = date::format(&now).replace(['-', ':'], "");
+                name.push(format!(".{}", stamp));
+                if attempt > 2 {
+                    name.push(format!(".{}", attempt - 1));
+                }
+            }
+            CollisionStrategy::Custom(namer) => {
+                let custom = namer(base, attempt);
+                let usable = attempt <= CUSTOM_ATTEMPTS
+                    && !custom.is_empty()
+                    && custom != "."
+                    && custom != ".."
+                    && !custom.to_string_lossy().contains('/');
+                if usable {
+                    return custom;
+                }
+                return CollisionStrategy::Counter.internal_name(base, attempt);
+            }
+        }
+        name
+    }
+}
+
+/// Pushes `base` with `mark` before its extension, or at its end
+/// when it has none
+fn before_extension(name: &mut OsString, base: &OsStr, mark: &str) {
+    let path = std::path::Path::new(base);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(ext)) => {
+            name.push(stem);
+            name.push(mark);
+            name.push(".");
+            name.push(ext);
+        }
+        _ => {
+            name.push(base);
+            name.push(mark);
+        }
+    }
+}
+
+/// How far along copying a file into the trash is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrashProgress {
+    /// Bytes copied so far
+    pub copied_bytes: u64,
+    /// Bytes to copy in total
+    pub total_bytes: u64,
+}
+
+/// Limits on how fast a file is copied when it has to be copied into
+/// the trash, so that a background cleanup does not keep the disk busy
+/// while the user is working; see `TrashOptions::throttle`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Throttle {
+    /// Most bytes of file data copied per second
+    pub bytes_per_sec: Option<u64>,
+    /// Most files copied per second, for directories of many small files
+    pub files_per_sec: Option<u64>,
+}
+
+/// How often to try again when moving or copying a file into the trash
+/// fails with an error which may well pass, such as `EAGAIN`, `EINTR` or
+/// a network filesystem timing out; see `TrashOptions::retry`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries after the first one, none by default
+    pub retries: u32,
+    /// How long to wait before the first retry, doubled for each after it
+    pub initial_delay: Duration,
+    /// The longest to wait between two tries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            initial_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Settings for `move_to_trash_with`
+///
+/// ```no_run
+/// use trash::{move_to_trash_with, CrossDevicePolicy, TrashOptions};
+///
+/// let options = TrashOptions::new()
+///     .cross_device(CrossDevicePolicy::Fail)
+///     .dry_run(true);
+/// let files = move_to_trash_with("notes.txt", &options).unwrap();
+/// println!("would go to {}", files.trash_file.display());
+/// ```
+#[derive(Clone)]
+pub struct TrashOptions {
+    pub(crate) location: Option<TrashLocation>,
+    pub(crate) owner_trash: bool,
+    pub(crate) symlinks: SymlinkPolicy,
+    pub(crate) cross_device: CrossDevicePolicy,
+    pub(crate) collisions: CollisionStrategy,
+    pub(crate) preserve_metadata: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) collect_stats: bool,
+    pub(crate) durable: bool,
+    pub(crate) max_size: Option<u64>,
+    pub(crate) home_fallback: HomeFallback,
+    pub(crate) home_trash_fallback: Option<PathBuf>,
+    pub(crate) escape_policy: EscapePolicy,
+    pub(crate) journal: Option<Journal>,
+    pub(crate) network: NetworkPolicy,
+    pub(crate) layout: TrashLayout,
+    pub(crate) keep_original: bool,
+    pub(crate) metadata: Vec<(String, String)>,
+    pub(crate) record_size: bool,
+    pub(crate) fallback_prompt: Option<FallbackPrompt>,
+    pub(crate) progress: Option<Arc<dyn Fn(TrashProgress) + Send + Sync>>,
+    pub(crate) throttle: Throttle,
+    pub(crate) busy_wait: Option<Duration>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    #[cfg(feature = "ioprio")]
+    pub(crate) low_priority: bool,
+    #[cfg(feature = "async")]
+    pub(crate) spawner: Option<Arc<dyn Spawner>>,
+}
+
+impl Default for TrashOptions {
+    fn default() -> Self {
+        Self {
+            location: None,
+            owner_trash: false,
+            symlinks: SymlinkPolicy::TrashLink,
+            cross_device: CrossDevicePolicy::Copy,
+            collisions: CollisionStrategy::Counter,
+            preserve_metadata: true,
+            dry_run: false,
+            collect_stats: false,
+            durable: false,
+            max_size: None,
+            home_fallback: HomeFallback::Always,
+            home_trash_fallback: None,
+            escape_policy: EscapePolicy::Strict,
+            journal: None,
+            network: NetworkPolicy::Normal,
+            layout: TrashLayout::Flat,
+            keep_original: false,
+            metadata: Vec::new(),
+            record_size: false,
+            fallback_prompt: None,
+            progress: None,
+            throttle: Throttle::default(),
+            busy_wait: None,
+            retry: RetryPolicy::default(),
+            cancel: None,
+            clock: None,
+            #[cfg(feature = "ioprio")]
+            low_priority: false,
+            #[cfg(feature = "async")]
+            spawner: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for TrashOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut f = f.debug_struct("TrashOptions");
+        f.field("location", &self.location)
+            .field("owner_trash", &self.owner_trash)
+            .field("symlinks", &self.symlinks)
+            .field("cross_device", &self.cross_device)
+            .field("collisions", &self.collisions)
+            .field("preserve_metadata", &self.preserve_metadata)
+            .field("dry_run", &self.dry_run)
+            .field("collect_stats", &self.collect_stats)
+            .field("durable", &self.durable)
+            .field("max_size", &self.max_size)
+            .field("home_fallback", &self.home_fallback)
+            .field("home_trash_fallback", &self.home_trash_fallback)
+            .field("escape_policy", &self.escape_policy)
+            .field("journal", &self.journal)
+            .field("network", &self.network)
+            .field("layout", &self.layout)
+            .field("keep_original", &self.keep_original)
+            .field("metadata", &self.metadata)
+            .field("record_size", &self.record_size)
+            .field("progress", &self.progress.is_some())
+            .field("throttle", &self.throttle)
+            .field("busy_wait", &self.busy_wait)
+            .field("retry", &self.retry)
+            .field("cancel", &self.cancel)
+            .field("clock", &self.clock.is_some());
+        #[cfg(feature = "ioprio")]
+        f.field("low_priority", &self.low_priority);
+        #[cfg(feature = "async")]
+        f.field("spawner", &self.spawner.is_some());
+        f.finish()
+    }
+}
+
+impl TrashOptions {
+    /// The same behavior as `move_to_trash`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use this trash instead of picking one based on where the file is
+    pub fn location(mut self, location: TrashLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// When running as root, send other users' files to their own trash
+    /// (see `move_to_owner_trash`)
+    pub fn owner_trash(mut self, owner_trash: bool) -> Self {
+        self.owner_trash = owner_trash;
+        self
+    }
+
+    /// What to trash when given a symlink, the link itself by default
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Whether to copy files into a trash on another filesystem,
+    /// which is allowed by default
+    pub fn cross_device(mut self, policy: CrossDevicePolicy) -> Self {
+        self.cross_device = policy;
+        self
+    }
+
+    /// How to name items whose name is already taken in the trash
+    pub fn collisions(mut self, strategy: CollisionStrategy) -> Self {
+        self.collisions = strategy;
+        self
+    }
+
+    /// Whether copies keep the modification and access times of the
+    /// original, and with the `selinux` feature its security context,
+    /// on by default
+    pub fn preserve_metadata(mut self, preserve: bool) -> Self {
+        self.preserve_metadata = preserve;
+        self
+    }
+
+    /// Work out where the file would go without changing anything
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Flush the info file, copied data and the directories involved to
+    /// disk before returning, so the trash survives a power loss
+    pub fn durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    /// Refuse with `TrashError::TooLarge` to trash anything holding more
+    /// than this many bytes, such as a dataset that would otherwise be
+    /// copied into the home trash
+    pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Count the bytes and files moved even when the file could simply
+    /// be renamed into the trash, which means walking through directories
+    /// Without this, `TransferStats` only has sizes for copied files
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
+    /// Whether files on other devices may go to the home trash when
+    /// their device's trash cannot be used
+    pub fn home_fallback(mut self, home_fallback: HomeFallback) -> Self {
+        self.home_fallback = home_fallback;
+        self
+    }
+
+    /// Use this directory as the home trash when it cannot be found from
+    /// `$XDG_DATA_HOME` or `$HOME`, as in containers or system services
+    /// Without one, trashing into the home trash fails with `NoHomeTrash`
+    pub fn home_trash_fallback(mut self, trash_dir: Option<PathBuf>) -> Self {
+        self.home_trash_fallback = trash_dir;
+        self
+    }
+
+    /// How a `Trash` handle reading its items treats malformed escapes
+    /// in the `Path` of info files, `EscapePolicy::Strict` by default
+    /// Which policy was applied to an item is in `TrashInfo::malformed_path`
+    pub fn escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.escape_policy = policy;
+        self
+    }
+
+    /// Record what is trashed with these options in `journal`, none by
+    /// default, along with what is restored and purged through a `Trash`
+    /// handle made with them
+    pub fn journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// How to treat files or trashes on network filesystems
+    pub fn network(mut self, network: NetworkPolicy) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// How to arrange items in the trash, flat as the spec has it by default
+    pub fn layout(mut self, layout: TrashLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Copy into the trash and leave the original in place, as
+    /// `copy_to_trash` does, rather than moving it
+    pub fn keep_original(mut self, keep_original: bool) -> Self {
+        self.keep_original = keep_original;
+        self
+    }
+
+    /// Whether to record the size of what is trashed in its info file,
+    /// so `TrashedItem::size` can skip walking through it, off by default
+    /// Finding the size of a large directory takes a walk through it
+    /// before it is moved, unless `max_size` walked it already
+    pub fn record_size(mut self, record_size: bool) -> Self {
+        self.record_size = record_size;
+        self
+    }
+
+    /// Records an application specific key in the info file, read back
+    /// with `TrashedItem::metadata`
+    /// Keys are namespaced like `X-MyApp-Reason`: `X-` followed by letters,
+    /// digits and dashes, while values are a single line
+    /// Keys starting with `X-Trash-` are the crate's own and not allowed
+    /// Anything else fails with `TrashError::InvalidMetadata` when trashing
+    pub fn metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        let key = key.into();
+        self.metadata.retain(|(existing, _)| *existing != key);
+        self.metadata.push((key, value.into()));
+        self
+    }
+
+    /// Decides whether to fall back to the home trash, given the top
+    /// directory whose trash could not be used and the file being trashed
+    /// Sets the fallback to `HomeFallback::Ask`
+    pub fn on_home_fallback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path, &Path) -> bool + Send + Sync + 'static,
+    {
+        self.home_fallback = HomeFallback::Ask;
+        self.fallback_prompt = Some(Arc::new(callback));
+        self
+    }
+
+    /// The first metadata key which cannot be written to an info file
+    pub(crate) fn invalid_metadata(&self) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(key, value)| !is_valid_metadata(key, value))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// Whether falling back to the home trash is allowed for a file
+    pub(crate) fn allows_home_fallback(&self, topdir: &Path, path: &Path) -> bool {
+        match self.home_fallback {
+            HomeFallback::Always => true,
+            HomeFallback::Never => false,
+            HomeFallback::Ask => self
+                .fallback_prompt
+                .as_ref()
+                .is_some_and(|prompt| prompt(topdir, path)),
+        }
+    }
+
+    /// Called as data is copied when the file has to be copied into the trash
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(TrashProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Takes deletion dates from `clock` rather than the system clock,
+    /// such as a `FixedClock` for reproducible info files
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// The deletion date for a file trashed now
+    pub(crate) fn now(&self) -> DeletionDate {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Slows down copying a file into the trash to stay within `throttle`
+    /// Renames within a filesystem are not affected, as they are instant
+    pub fn throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Keeps trying for up to `wait` when the file or its mount is in use,
+    /// waiting longer after each try, rather than failing at once with
+    /// `TrashError::Busy`
+    pub fn wait_if_busy(mut self, wait: Option<Duration>) -> Self {
+        self.busy_wait = wait;
+        self
+    }
+
+    /// Tries moving or copying a file into the trash again as `retry`
+    /// allows when it fails with a transient error, so that batches over
+    /// flaky network filesystems do not fail spuriously
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Gives up trashing once `flag` is set, such as from a signal
+    /// handler, with `TrashError::Cancelled`
+    /// A copy under way is stopped, and what was copied and the info file
+    /// are removed, leaving the file where it was
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Whether the flag given to `cancel_flag` is set
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::SeqCst))
+    }
+
+    /// Copies a file into the trash at the lowest CPU and IO priority,
+    /// see `run_low_priority`, rather than at the caller's
+    #[cfg(feature = "ioprio")]
+    pub fn low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    /// Runs the blocking work of the async functions given these options,
+    /// and of the `Trash` handle made with them, on `spawner` rather than
+    /// on a thread per task
+    #[cfg(feature = "async")]
+    pub fn spawner<S: Spawner + 'static>(mut self, spawner: S) -> Self {
+        self.spawner = Some(Arc::new(spawner));
+        self
+    }
+
+    /// Where blocking work is run for the async functions
+    #[cfg(feature = "async")]
+    pub(crate) fn blocking_spawner(&self) -> &dyn Spawner {
+        match &self.spawner {
+            Some(spawner) => spawner.as_ref(),
+            None => &ThreadSpawner,
+        }
+    }
+}
+
+/// Whether a key and its value can be added to an info file: the key
+/// must be an `X-` extension key other than those the crate writes
+/// itself, and the value a single line
+pub(crate) fn is_valid_metadata(key: &str, value: &str) -> bool {
+    let reserved = key
+        .get(..RESERVED_KEY_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(RESERVED_KEY_PREFIX));
+    match key.strip_prefix("X-") {
+        Some(name) => {
+            !reserved
+                && !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                && !value.contains(['\n', '\r'])
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        list_trash_in, move_to_trash_with, would_cross_devices, CollisionStrategy,
+        CrossDevicePolicy, HomeFallback, NetworkPolicy, RestoreStrategy, Throttle, TrashError,
+        TrashLayout, TrashLocation, TrashOptions,
+    };
+    use std::ffi::{OsStr, OsString};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collision_names() {
+        let name = OsStr::new("photo.jpg");
+        assert_eq!(
+            CollisionStrategy::Counter.internal_name(name, 1),
+            "photo.jpg"
+        );
+        assert_eq!(
+            CollisionStrategy::Counter.internal_name(name, 2),
+            "photo.jpg.2"
+        );
+        assert_eq!(
+            CollisionStrategy::CounterBeforeExtension.internal_name(name, 3),
+            "photo.3.jpg"
+        );
+        assert_eq!(
+            CollisionStrategy::CounterBeforeExtension.internal_name(OsStr::new("README"), 2),
+            "README.2"
+        );
+        assert_eq!(
+            CollisionStrategy::Copy.internal_name(name, 2),
+            "photo (copy 2).jpg"
+        );
+        let stamped = CollisionStrategy::Timestamp.internal_name(name, 3);
+        let stamped = stamped.to_str().unwrap();
+        assert!(stamped.starts_with("photo.jpg.") && stamped.ends_with(".2"));
+        assert_eq!(stamped.len(), "photo.jpg.20240102T030405.2".len());
+        let now = crate::date::parse("2024-01-02T03:04:05").unwrap();
+        assert_eq!(
+            CollisionStrategy::Timestamp.internal_name_at(name, 2, now),
+            "photo.jpg.20240102T030405"
+        );
+
+        fn numbered(base: &OsStr, attempt: u32) -> OsString {
+            let mut name = OsString::from(format!("{}_", attempt));
+            name.push(base);
+            name
+        }
+        fn unusable(_: &OsStr, _: u32) -> OsString {
+            OsString::from("a/b")
+        }
+        let custom = CollisionStrategy::Custom(numbered);
+        assert_eq!(custom.internal_name(name, 1), "photo.jpg");
+        assert_eq!(custom.internal_name(name, 2), "2_photo.jpg");
+        assert_eq!(custom.internal_name(name, 101), "photo.jpg.101");
+        assert_eq!(
+            CollisionStrategy::Custom(unusable).internal_name(name, 2),
+            "photo.jpg.2"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_and_collisions() {
+        let (file_dir, trash_dir, location) = fixture();
+        let options = TrashOptions::new()
+            .location(location)
+            .collisions(CollisionStrategy::CounterBeforeExtension);
+
+        let file_path = file_dir.path().join("photo.jpg");
+        std::fs::write(&file_path, b"1").unwrap();
+        move_to_trash_with(&file_path, &options).unwrap();
+        std::fs::write(&file_path, b"2").unwrap();
+
+        let preview = move_to_trash_with(&file_path, &options.clone().dry_run(true)).unwrap();
+        assert_eq!(
+            preview.trash_file,
+            trash_dir.path().join("files/photo.2.jpg")
+        );
+        assert!(file_path.exists());
+        assert!(!preview.info_file.exists());
+
+        let files = move_to_trash_with(&file_path, &options.durable(true)).unwrap();
+        assert_eq!(files.trash_file, preview.trash_file);
+        assert_eq!(std::fs::read(&files.trash_file).unwrap(), b"2");
+    }
+
+    #[test]
+    fn test_hierarchical_layout() {
+        let (file_dir, trash_dir, location) = fixture();
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .layout(TrashLayout::Hierarchical);
+        let sub_dir = file_dir.path().canonicalize().unwrap().join("sub");
+        std::fs::create_dir(&sub_dir).unwrap();
+        for name in &["a.txt", "b.txt"] {
+            std::fs::write(sub_dir.join(name), name).unwrap();
+            let files = move_to_trash_with(sub_dir.join(name), &options).unwrap();
+            let stored = sub_dir.strip_prefix("/").unwrap().join(name);
+            assert_eq!(
+                files.trash_file,
+                trash_dir.path().join("files").join(stored)
+            );
+            assert!(files.trash_file.is_file());
+        }
+
+        let mut items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| item.trash_file().is_file()));
+        items.pop().unwrap().purge().unwrap();
+        items
+            .pop()
+            .unwrap()
+            .restore(RestoreStrategy::FailOnConflict)
+            .unwrap();
+        assert_eq!(
+            std::fs::read_dir(trash_dir.path().join("files"))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert_eq!(std::fs::read_dir(&sub_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let options = TrashOptions::new().location(location).max_size(Some(4));
+
+        let dir_path = file_dir.path().join("data");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("a"), b"123").unwrap();
+        move_to_trash_with(&dir_path, &options.clone().dry_run(true)).unwrap();
+        std::fs::write(dir_path.join("b"), b"45").unwrap();
+        match move_to_trash_with(&dir_path, &options) {
+            Err(TrashError::TooLarge {
+                size: 5, limit: 4, ..
+            }) => {}
+            other => panic!("expected too large, got {:?}", other),
+        }
+        assert!(dir_path.exists());
+        move_to_trash_with(&dir_path, &options.max_size(None)).unwrap();
+    }
+
+    #[test]
+    fn test_cross_device_policy() {
+        // /dev/shm is a separate tmpfs on most Linux systems
+        let other_fs = match tempfile::tempdir_in("/dev/shm") {
+            Ok(dir) => dir,
+            Err(_) => return,
+        };
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let options = TrashOptions::new()
+            .location(TrashLocation::Custom(trash_dir.path().to_path_buf()))
+            .cross_device(CrossDevicePolicy::Fail);
+
+        let file_path = other_fs.path().join("far");
+        std::fs::write(&file_path, b"x").unwrap();
+        let crosses = would_cross_devices(&file_path, &options).unwrap();
+        match move_to_trash_with(&file_path, &options) {
+            Err(TrashError::CrossDevice { from, .. }) => {
+                assert!(crosses);
+                assert_eq!(from, file_path);
+                assert!(file_path.exists());
+            }
+            Ok(_) => assert!(!crosses),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_home_fallback() {
+        let (topdir, path) = (Path::new("/media/usb"), Path::new("/media/usb/file"));
+        assert!(TrashOptions::new().allows_home_fallback(topdir, path));
+        let never = TrashOptions::new().home_fallback(HomeFallback::Never);
+        assert!(!never.allows_home_fallback(topdir, path));
+        // Asking without anyone to ask means no
+        let ask = TrashOptions::new().home_fallback(HomeFallback::Ask);
+        assert!(!ask.allows_home_fallback(topdir, path));
+        let ask = TrashOptions::new().on_home_fallback(|topdir, path| {
+            topdir == Path::new("/media/usb") && path.ends_with("file")
+        });
+        assert!(ask.allows_home_fallback(topdir, path));
+        assert!(!ask.allows_home_fallback(Path::new("/mnt"), path));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("tagged");
+        std::fs::write(&path, b"x").unwrap();
+
+        let reserved = [
+            "X-Trash-Directory",
+            "X-Trash-Owner",
+            "X-Trash-Size",
+            "X-Trash-Tags",
+            "x-trash-owner",
+        ];
+        for bad in ["Reason", "X-", "X-My App", "X-MyApp=Reason"]
+            .iter()
+            .chain(reserved.iter())
+        {
+            let options = TrashOptions::new()
+                .location(location.clone())
+                .metadata(*bad, "auto-clean");
+            match move_to_trash_with(&path, &options) {
+                Err(TrashError::InvalidMetadata(key)) => assert_eq!(key, *bad),
+                other => panic!("expected InvalidMetadata, got {:?}", other),
+            }
+        }
+        let multiline = TrashOptions::new()
+            .location(location.clone())
+            .metadata("X-MyApp-Reason", "a\nb");
+        assert!(move_to_trash_with(&path, &multiline).is_err());
+        assert!(path.exists());
+
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .metadata("X-MyApp-Reason", "first")
+            .metadata("X-MyApp-Reason", "auto-clean")
+            .metadata("X-MyApp-Run", "42");
+        move_to_trash_with(&path, &options).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert_eq!(item.metadata("X-MyApp-Reason"), Some("auto-clean"));
+        assert_eq!(item.metadata("X-MyApp-Run"), Some("42"));
+        assert_eq!(item.metadata("X-Other"), None);
+    }
+
+    #[test]
+    fn test_network_policy() {
+        let (file_dir, _trash_dir, location) = fixture();
+        // Local files are trashed whatever the policy
+        for policy in &[NetworkPolicy::Relaxed, NetworkPolicy::Refuse] {
+            let path = file_dir.path().join("local");
+            std::fs::write(&path, b"x").unwrap();
+            let options = TrashOptions::new()
+                .location(location.clone())
+                .network(*policy)
+                .durable(true);
+            move_to_trash_with(&path, &options).unwrap();
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn test_throttle() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let big = file_dir.path().join("big");
+        std::fs::write(&big, vec![0u8; 40_000]).unwrap();
+        let many = file_dir.path().join("many");
+        std::fs::create_dir(&many).unwrap();
+        for i in 0..5 {
+            std::fs::write(many.join(i.to_string()), b"x").unwrap();
+        }
+
+        // Copying, as within a filesystem renames are not throttled
+        let copy = TrashOptions::new().location(location).keep_original(true);
+        let started = std::time::Instant::now();
+        let options = copy.clone().throttle(Throttle {
+            bytes_per_sec: Some(100_000),
+            files_per_sec: None,
+        });
+        move_to_trash_with(&big, &options).unwrap();
+        assert!(started.elapsed() >= std::time::Duration::from_millis(400));
+
+        let started = std::time::Instant::now();
+        let options = copy.throttle(Throttle {
+            bytes_per_sec: None,
+            files_per_sec: Some(10),
+        });
+        move_to_trash_with(&many, &options).unwrap();
+        assert!(started.elapsed() >= std::time::Duration::from_millis(400));
+        assert_eq!(list_trash_in(&options.location.unwrap()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_cancel_flag() {
+        let (file_dir, trash_dir, location) = fixture();
+        let dir = file_dir.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("big"), vec![0u8; 500_000]).unwrap();
+
+        // Cancelled once the copy is under way, as if by a signal
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .keep_original(true)
+            .cancel_flag(Arc::clone(&cancel))
+            .on_progress(move |_| flag.store(true, Ordering::SeqCst));
+        match move_to_trash_with(&dir, &options) {
+            Err(TrashError::Cancelled) => {}
+            other => panic!("expected a cancellation, got {:?}", other),
+        }
+        assert!(list_trash_in(&location).unwrap().is_empty());
+        assert_eq!(
+            std::fs::read_dir(trash_dir.path().join("files"))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert_eq!(std::fs::metadata(dir.join("big")).unwrap().len(), 500_000);
+
+        assert!(matches!(
+            move_to_trash_with(&dir, &options),
+            Err(TrashError::Cancelled)
+        ));
+        cancel.store(false, Ordering::SeqCst);
+        let options = options.on_progress(|_| {});
+        move_to_trash_with(&dir, &options).unwrap();
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,287 @@
+//! Records of moves to another filesystem which are under way, so that a
+//! move cut short by the process being killed is picked up by the next
+//! attempt to trash the same file, rather than leaving the file both in
+//! place and half copied into the trash
+
+use crate::escape::{decode_os, encode_os};
+use crate::journal::{self, JournalOperation};
+use crate::location::TrashOwner;
+use crate::{
+    copy_times, give_to_owner, remove_path, sync_moved, trash_dir_error, TransferStats, TrashError,
+    TrashFiles, TrashLocation, TrashOptions,
+};
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Directory of a trash holding a record of each move under way,
+/// named after the item being moved
+const PARTIAL_DIR: &str = "partial";
+
+/// The record of one move under way
+#[derive(Debug)]
+pub(crate) struct PartialMove {
+    marker: PathBuf,
+    original: PathBuf,
+}
+
+impl PartialMove {
+    /// For moving `original` into the trash as `internal_filename`
+    pub(crate) fn new(trash_dir: &Path, internal_filename: &OsStr, original: &Path) -> Self {
+        PartialMove {
+            marker: trash_dir.join(PARTIAL_DIR).join(internal_filename),
+            original: original.to_path_buf(),
+        }
+    }
+
+    /// Records that `src` is about to be copied to `dest`, along with
+    /// what it looks like now, to tell later whether it has changed
+    pub(crate) fn begin(&self, src: &Path, dest: &Path) -> std::io::Result<()> {
+        if let Some(dir) = self.marker.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let meta = src.symlink_metadata()?;
+        let record = format!(
+            "Path={}\nDest={}\nSize={}\nModified={}\n",
+            encode_os(self.original.as_os_str()),
+            encode_os(dest.as_os_str()),
+            meta.len(),
+            modified_nanos(&meta)
+        );
+        std::fs::write(&self.marker, record)
+    }
+
+    /// Forgets about the move, once it is done or has been undone
+    pub(crate) fn finish(&self) {
+        let _ = std::fs::remove_file(&self.marker);
+        // Only goes once no other moves are under way
+        if let Some(dir) = self.marker.parent() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+/// A move which was cut short, as found in its record
+struct Interrupted {
+    record: PartialMove,
+    internal_filename: OsString,
+    dest: PathBuf,
+    size: u64,
+    modified: u128,
+}
+
+/// Picks up where an earlier move of `path` into this trash was cut short
+///
+/// A regular file which has not changed since has the rest of it copied,
+/// and the finished move is returned. Anything else has what was copied
+/// and its info file removed, so that the move starts over.
+pub(crate) fn resume(
+    path: &Path,
+    original: &Path,
+    owner: &TrashOwner,
+    location: &TrashLocation,
+    trash_dir: &Path,
+    options: &TrashOptions,
+) -> Result<Option<TrashFiles>, TrashError> {
+    let mut resumed = None;
+    for interrupted in interrupted(trash_dir, original)? {
+        let mut info_name = interrupted.internal_filename.clone();
+        info_name.push(".trashinfo");
+        let info_file = trash_dir.join("info").join(info_name);
+        if resumed.is_none() && can_resume(path, &interrupted, &info_file) {
+            let stats = copy_rest(path, &interrupted.dest, options)
+                .map_err(|e| trash_dir_error(&interrupted.dest, e))?;
+            remove_path(path)?;
+            give_to_owner(owner, &interrupted.dest)?;
+            if options.durable {
+                sync_moved(path, &interrupted.dest, trash_dir, true)?;
+            }
+            interrupted.record.finish();
+            journal::record(
+                options.journal.as_ref(),
+                JournalOperation::Trash,
+                trash_dir,
+                original,
+                &interrupted.dest,
+            );
+            let mut files = TrashFiles::new(
+                interrupted.dest,
+                info_file,
+                location.clone(),
+                trash_dir.to_path_buf(),
+            );
+            files.stats = stats;
+            resumed = Some(files);
+        } else {
+            match remove_path(&interrupted.dest) {
+                Err(ref e) if e.kind() != ErrorKind::NotFound => continue,
+                _ => {}
+            }
+            let _ = std::fs::remove_file(&info_file);
+            interrupted.record.finish();
+        }
+    }
+    Ok(resumed)
+}
+
+/// The moves of `original` into this trash which were cut short
+/// There are none to read through unless a move was interrupted
+fn interrupted(trash_dir: &Path, original: &Path) -> Result<Vec<Interrupted>, TrashError> {
+    let entries = match std::fs::read_dir(trash_dir.join(PARTIAL_DIR)) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut found = Vec::new();
+    for entry in entries {
+        let marker = entry?.path();
+        let internal_filename = match marker.file_name() {
+            Some(name) => name.to_os_string(),
+            None => continue,
+        };
+        let record = match std::fs::read_to_string(&marker) {
+            Ok(record) => record,
+            // Another process is done with it already
+            Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let value = |key: &str| {
+            record
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        let (recorded, dest, size, modified) = match (
+            value("Path"),
+            value("Dest"),
+            value("Size").and_then(|size| size.parse().ok()),
+            value("Modified").and_then(|modified| modified.parse().ok()),
+        ) {
+            (Some(recorded), Some(dest), Some(size), Some(modified)) => {
+                let (recorded, dest) = (decode_os(recorded), decode_os(dest));
+                (PathBuf::from(recorded), PathBuf::from(dest), size, modified)
+            }
+            _ => continue,
+        };
+        // Nothing outside of the trash is ever removed on a record's word
+        if recorded != original || !dest.starts_with(trash_dir.join("files")) {
+            continue;
+        }
+        found.push(Interrupted {
+            record: PartialMove {
+                marker,
+                original: recorded,
+            },
+            internal_filename,
+            dest,
+            size,
+            modified,
+        });
+    }
+    Ok(found)
+}
+
+/// Whether the copy of a file can be finished rather than started over
+fn can_resume(path: &Path, interrupted: &Interrupted, info_file: &Path) -> bool {
+    let unchanged = path.symlink_metadata().is_ok_and(|meta| {
+        meta.is_file()
+            && meta.len() == interrupted.size
+            && modified_nanos(&meta) == interrupted.modified
+    });
+    let partial = interrupted
+        .dest
+        .symlink_metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.len() <= interrupted.size);
+    unchanged && partial && info_file.is_file()
+}
+
+/// Appends what is missing from the copy of a file
+fn copy_rest(path: &Path, dest: &Path, options: &TrashOptions) -> std::io::Result<TransferStats> {
+    let started = std::time::Instant::now();
+    let mut src = File::open(path)?;
+    let mut out = OpenOptions::new().append(true).open(dest)?;
+    src.seek(SeekFrom::Start(out.metadata()?.len()))?;
+    std::io::copy(&mut src, &mut out)?;
+    drop(out);
+    if options.preserve_metadata {
+        copy_times(path, dest)?;
+    }
+    Ok(TransferStats {
+        bytes: src.metadata()?.len(),
+        files: 1,
+        copied: true,
+        elapsed: started.elapsed(),
+    })
+}
+
+fn modified_nanos(meta: &std::fs::Metadata) -> u128 {
+    meta.modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PartialMove;
+    use crate::tests::fixture;
+    use crate::{list_trash_in, move_to_trash_in, move_to_trash_with, TrashLocation, TrashOptions};
+
+    /// Leaves the trash as if moving `path` into it was killed after
+    /// copying `copied` bytes
+    fn interrupt(path: &std::path::Path, location: &TrashLocation, copied: u64) {
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .keep_original(true);
+        let files = move_to_trash_with(path, &options).unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&files.trash_file)
+            .unwrap()
+            .set_len(copied)
+            .unwrap();
+        let original = path.canonicalize().unwrap();
+        PartialMove::new(
+            &files.trash_dir,
+            files.trash_file.file_name().unwrap(),
+            &original,
+        )
+        .begin(path, &files.trash_file)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_resume_interrupted_move() {
+        let (file_dir, trash_dir, location) = fixture();
+        let path = file_dir.path().join("big");
+        let content: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        interrupt(&path, &location, 4000);
+        let files = move_to_trash_in(&path, &location).unwrap();
+        assert!(files.stats.copied);
+        assert_eq!(std::fs::read(&files.trash_file).unwrap(), content);
+        assert!(!path.exists());
+        assert!(!trash_dir.path().join("partial").exists());
+        assert_eq!(list_trash_in(&location).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_restart_interrupted_move() {
+        let (file_dir, trash_dir, location) = fixture();
+        let path = file_dir.path().join("changed");
+        std::fs::write(&path, b"first version").unwrap();
+
+        interrupt(&path, &location, 5);
+        std::fs::write(&path, b"second, longer version").unwrap();
+        let files = move_to_trash_in(&path, &location).unwrap();
+        assert_eq!(
+            std::fs::read(&files.trash_file).unwrap(),
+            b"second, longer version"
+        );
+        assert!(!trash_dir.path().join("partial").exists());
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].internal_filename(), "changed");
+    }
+}
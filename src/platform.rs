@@ -0,0 +1,205 @@
+//! What the crate needs from the operating system beyond std
+//!
+//! The trash spec is written for unix, where all of this comes from std
+//! and libc. Other platforms still compile: names that are not UTF-8 are
+//! read lossily, files all appear to be on one device and owned by one
+//! user, and changing ownership or creating symlinks is not supported.
+
+#[cfg(unix)]
+pub(crate) use std::os::unix::ffi::{OsStrExt, OsStringExt};
+#[cfg(unix)]
+pub(crate) use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+#[cfg(not(unix))]
+pub(crate) use self::fallback::{
+    DirBuilderExt, MetadataExt, OsStrExt, OsStringExt, PermissionsExt,
+};
+
+use std::path::Path;
+
+/// Id of the user running this process
+#[cfg(unix)]
+pub(crate) fn getuid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn getuid() -> u32 {
+    0
+}
+
+/// Primary group of the user running this process
+#[cfg(unix)]
+pub(crate) fn getgid() -> u32 {
+    unsafe { libc::getgid() }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn getgid() -> u32 {
+    0
+}
+
+/// Changes the owner of a path, not following a final symlink
+#[cfg(unix)]
+pub(crate) fn lchown(path: &Path, uid: u32, gid: u32) -> std::io::Result<()> {
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))
+}
+
+/// Files all belong to the one user there is, so only that user is accepted
+#[cfg(not(unix))]
+pub(crate) fn lchown(_path: &Path, uid: u32, gid: u32) -> std::io::Result<()> {
+    if uid == getuid() && gid == getgid() {
+        Ok(())
+    } else {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+}
+
+/// Changes the owner of an open file
+#[cfg(unix)]
+pub(crate) fn fchown(file: &std::fs::File, uid: u32, gid: u32) -> std::io::Result<()> {
+    std::os::unix::fs::fchown(file, Some(uid), Some(gid))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn fchown(_file: &std::fs::File, uid: u32, gid: u32) -> std::io::Result<()> {
+    lchown(Path::new(""), uid, gid)
+}
+
+/// Creates a symlink at `link` pointing to `target`
+#[cfg(unix)]
+pub(crate) fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn symlink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+/// Whether this process may create files in `dir`
+#[cfg(unix)]
+pub(crate) fn is_writable(dir: &Path) -> bool {
+    match std::ffi::CString::new(dir.as_os_str().as_bytes()) {
+        Ok(dir) => unsafe { libc::access(dir.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_writable(dir: &Path) -> bool {
+    dir.metadata()
+        .is_ok_and(|meta| meta.is_dir() && !meta.permissions().readonly())
+}
+
+/// Bytes available to unprivileged users and total size of the
+/// filesystem holding `path`
+#[cfg(unix)]
+pub(crate) fn disk_space(path: &Path) -> std::io::Result<(u64, u64)> {
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let block = stat.f_frsize as u64;
+    Ok((
+        (stat.f_bavail as u64).saturating_mul(block),
+        (stat.f_blocks as u64).saturating_mul(block),
+    ))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn disk_space(_path: &Path) -> std::io::Result<(u64, u64)> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use std::ffi::{OsStr, OsString};
+    use std::fs::{DirBuilder, Metadata, Permissions};
+
+    pub(crate) trait OsStrExt {
+        fn from_bytes(bytes: &[u8]) -> &Self;
+        fn as_bytes(&self) -> &[u8];
+    }
+
+    impl OsStrExt for OsStr {
+        /// Only the bytes up to the first that are not UTF-8 are kept
+        fn from_bytes(bytes: &[u8]) -> &OsStr {
+            let text = match std::str::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+            };
+            OsStr::new(text)
+        }
+
+        fn as_bytes(&self) -> &[u8] {
+            self.as_encoded_bytes()
+        }
+    }
+
+    pub(crate) trait OsStringExt {
+        fn from_vec(vec: Vec<u8>) -> Self;
+    }
+
+    impl OsStringExt for OsString {
+        fn from_vec(vec: Vec<u8>) -> OsString {
+            String::from_utf8_lossy(&vec).into_owned().into()
+        }
+    }
+
+    pub(crate) trait MetadataExt {
+        fn dev(&self) -> u64;
+        fn ino(&self) -> u64;
+        fn uid(&self) -> u32;
+        fn gid(&self) -> u32;
+    }
+
+    impl MetadataExt for Metadata {
+        fn dev(&self) -> u64 {
+            0
+        }
+
+        /// Never the same for two files, so none are taken as duplicates
+        fn ino(&self) -> u64 {
+            static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+            NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        }
+
+        fn uid(&self) -> u32 {
+            super::getuid()
+        }
+
+        fn gid(&self) -> u32 {
+            super::getgid()
+        }
+    }
+
+    pub(crate) trait PermissionsExt {
+        fn mode(&self) -> u32;
+    }
+
+    impl PermissionsExt for Permissions {
+        /// Read only files are taken as readable by everyone and the rest
+        /// as writable by their owner, with no sticky bit
+        fn mode(&self) -> u32 {
+            if self.readonly() {
+                0o555
+            } else {
+                0o755
+            }
+        }
+    }
+
+    pub(crate) trait DirBuilderExt {
+        fn mode(&mut self, mode: u32) -> &mut Self;
+    }
+
+    impl DirBuilderExt for DirBuilder {
+        /// Permissions are left to the platform
+        fn mode(&mut self, _mode: u32) -> &mut Self {
+            self
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::query::size_or_zero;
+use crate::{date, list_all_trash, list_trash, Clock, SystemClock, TrashError, TrashedItem};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Limits on what is kept in the trash
+#[derive(Clone, Default)]
+pub struct RetentionPolicy {
+    /// Items deleted longer ago than this are purged
+    pub max_age: Option<Duration>,
+    /// When the trash grows beyond this many bytes,
+    /// the oldest items are purged until it fits
+    pub max_size: Option<u64>,
+    /// Whether the policy covers every trash of the user together, see
+    /// `all_trash_locations`, rather than the home trash alone
+    /// `max_size` then caps them combined, purging the oldest items
+    /// whichever device they are on
+    pub all_trashes: bool,
+    /// Where the time `max_age` counts back from comes from, the system
+    /// clock if none is given, see `TrashOptions::clock`
+    pub clock: Option<Arc<dyn Clock>>,
+}
+
+impl std::fmt::Debug for RetentionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetentionPolicy")
+            .field("max_age", &self.max_age)
+            .field("max_size", &self.max_size)
+            .field("all_trashes", &self.all_trashes)
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+impl RetentionPolicy {
+    /// Whether the policy would never purge anything
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.max_size.is_none()
+    }
+}
+
+/// Why an item was purged by a retention policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurgeReason {
+    /// It was deleted longer ago than `max_age`
+    Age,
+    /// The trash was larger than `max_size`
+    Size,
+}
+
+/// Purges items from the trash which fall outside of the policy
+/// Returns the items which were purged, oldest first
+/// With the `notify` feature, a desktop notification tells the user how
+/// many items and bytes were purged
+pub fn apply_retention(policy: &RetentionPolicy) -> Result<Vec<TrashedItem>, TrashError> {
+    apply_retention_with(policy, |_, _| {})
+}
+
+/// Like `apply_retention`, calling `on_purge` with each item right after
+/// it was permanently deleted, so applications can log or tell the user
+/// what was lost, even when a later purge fails
+pub fn apply_retention_with<F>(
+    policy: &RetentionPolicy,
+    on_purge: F,
+) -> Result<Vec<TrashedItem>, TrashError>
+where
+    F: FnMut(&TrashedItem, PurgeReason),
+{
+    let items = if policy.all_trashes {
+        list_all_trash()?
+    } else {
+        list_trash()?
+    };
+    let mut purged = Vec::new();
+    let mut freed = 0;
+    let result = enforce(items, policy, &mut purged, &mut freed, on_purge);
+    // Even after a failure, what is gone already is gone for good
+    #[cfg(feature = "notify")]
+    crate::notify::purged(purged.len(), freed);
+    result.map(|()| purged)
+}
+
+/// Purges what falls outside of the policy from `items`, most recently
+/// deleted first, adding them to `purged` and their size to `freed`
+fn enforce<F>(
+    mut items: Vec<TrashedItem>,
+    policy: &RetentionPolicy,
+    purged: &mut Vec<TrashedItem>,
+    freed: &mut u64,
+    mut on_purge: F,
+) -> Result<(), TrashError>
+where
+    F: FnMut(&TrashedItem, PurgeReason),
+{
+    // Oldest first, as those are the first to go
+    items.reverse();
+
+    if let Some(max_age) = policy.max_age {
+        let now = match &policy.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        };
+        let cutoff = date::saturating_sub(now, max_age);
+        let (expired, kept): (Vec<TrashedItem>, Vec<TrashedItem>) = items
+            .into_iter()
+            .partition(|item| item.deleted_at() < cutoff);
+        for item in expired {
+            let size = size_to_report(&item);
+            item.clone().purge()?;
+            on_purge(&item, PurgeReason::Age);
+            *freed += size;
+            purged.push(item);
+        }
+        items = kept;
+    }
+
+    if let Some(max_size) = policy.max_size {
+        let mut sized = Vec::with_capacity(items.len());
+        let mut total = 0u64;
+        for item in items {
+            let size = size_or_zero(&item)?;
+            total += size;
+            sized.push((item, size));
+        }
+        for (item, size) in sized {
+            if total <= max_size {
+                break;
+            }
+            item.clone().purge()?;
+            on_purge(&item, PurgeReason::Size);
+            total -= size;
+            *freed += size;
+            purged.push(item);
+        }
+    }
+    Ok(())
+}
+
+/// Size of an item purged for its age, only measured when it is reported
+fn size_to_report(item: &TrashedItem) -> u64 {
+    if cfg!(feature = "notify") {
+        size_or_zero(item).unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::lock_env;
+    use crate::{
+        apply_retention, apply_retention_with, date, list_trash, list_trash_in, move_to_trash,
+        move_to_trash_with, FixedClock, PurgeReason, RetentionPolicy, TrashLocation, TrashOptions,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_retention_age_and_size() {
+        let _env = lock_env();
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
+        std::env::set_var("XDG_DATA_HOME", temp_xdg_data_home.path().as_os_str());
+
+        for name in &["a", "b", "c"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"12345").unwrap();
+            move_to_trash(&path).unwrap();
+        }
+        // Backdate one item by rewriting its info file
+        let info_dir = temp_xdg_data_home.path().join("Trash").join("info");
+        let info = std::fs::read_to_string(info_dir.join("a.trashinfo")).unwrap();
+        let old = info
+            .lines()
+            .map(|line| {
+                if line.starts_with("DeletionDate=") {
+                    "DeletionDate=2000-01-01T00:00:00"
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(info_dir.join("a.trashinfo"), old).unwrap();
+
+        let policy = RetentionPolicy {
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            ..RetentionPolicy::default()
+        };
+        let purged = apply_retention(&policy).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].internal_filename(), "a");
+
+        let policy = RetentionPolicy {
+            max_size: Some(7),
+            ..RetentionPolicy::default()
+        };
+        // Nothing is old yet as of a clock stopped back then
+        let stopped = RetentionPolicy {
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            clock: Some(Arc::new(FixedClock(
+                date::parse("2000-01-15T00:00:00").unwrap(),
+            ))),
+            ..RetentionPolicy::default()
+        };
+        assert!(apply_retention(&stopped).unwrap().is_empty());
+        assert_eq!(list_trash().unwrap().len(), 2);
+
+        let mut reported = Vec::new();
+        let purged = apply_retention_with(&policy, |item, reason| {
+            reported.push((item.internal_filename().to_owned(), reason))
+        })
+        .unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(
+            reported,
+            [(purged[0].internal_filename().to_owned(), PurgeReason::Size)]
+        );
+        assert_eq!(list_trash().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_size_across_trashes() {
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let trashes = [tempdir().unwrap(), tempdir().unwrap()];
+        let locations: Vec<TrashLocation> = trashes
+            .iter()
+            .map(|dir| TrashLocation::Custom(dir.path().to_path_buf()))
+            .collect();
+        // Oldest first, alternating between the trashes
+        for (i, name) in ["a", "b", "c", "d"].iter().enumerate() {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"12345").unwrap();
+            let options = TrashOptions::new()
+                .location(locations[i % 2].clone())
+                .clock(FixedClock(
+                    date::parse(&format!("2020-01-02T03:04:0{}", i)).unwrap(),
+                ));
+            move_to_trash_with(&path, &options).unwrap();
+        }
+
+        let mut items = Vec::new();
+        for location in &locations {
+            items.extend(list_trash_in(location).unwrap());
+        }
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at()));
+        let policy = RetentionPolicy {
+            max_size: Some(12),
+            all_trashes: true,
+            ..RetentionPolicy::default()
+        };
+        let mut purged = Vec::new();
+        let mut freed = 0;
+        super::enforce(items, &policy, &mut purged, &mut freed, |_, _| {}).unwrap();
+        assert_eq!(freed, 10);
+        let names: Vec<_> = purged.iter().map(|item| item.internal_filename()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(
+            list_trash_in(&locations[0]).unwrap()[0].internal_filename(),
+            "c"
+        );
+        assert_eq!(
+            list_trash_in(&locations[1]).unwrap()[0].internal_filename(),
+            "d"
+        );
+    }
+}
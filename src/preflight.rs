@@ -0,0 +1,209 @@
+use crate::location::is_writable;
+use crate::{
+    move_to_trash_with, Restorability, TrashError, TrashLocation, TrashOptions, TrashedItem,
+};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Something that would stop a file from being trashed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrashProblem {
+    /// The file does not exist
+    SourceMissing,
+    /// The directory holding the file does not let us remove it
+    ParentNotWritable(PathBuf),
+    /// There is no trash to send the file to
+    NoTrash,
+    /// The trash directory cannot be written to
+    TrashNotWritable(PathBuf),
+    /// The file holds more bytes than `TrashOptions::max_size` allows
+    TooLarge { size: u64, limit: u64 },
+    /// The file or trash is on a network filesystem, and the options refuse those
+    NetworkFilesystem(PathBuf),
+    /// Anything else which went wrong while checking
+    Other(String),
+}
+
+/// What trashing a file would do, and what would stop it
+#[derive(Debug, Clone)]
+pub struct TrashCheck {
+    /// The trash the file would go to, if one was found
+    pub location: Option<TrashLocation>,
+    /// Root of that trash
+    pub trash_dir: Option<PathBuf>,
+    /// Name the file would be stored under in the trash
+    pub internal_name: Option<OsString>,
+    /// Whether the file would be copied to another filesystem
+    pub crosses_devices: bool,
+    /// Whether the home trash would be used for want of another
+    pub home_fallback: bool,
+    /// Everything in the way, empty if the file can be trashed
+    pub problems: Vec<TrashProblem>,
+}
+
+impl TrashCheck {
+    /// Whether nothing stands in the way of trashing the file
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks whether a path could be trashed as configured by `options`,
+/// without creating or moving anything
+pub fn can_trash<P: AsRef<Path>>(path: P, options: &TrashOptions) -> TrashCheck {
+    let path = path.as_ref();
+    let mut check = TrashCheck {
+        location: None,
+        trash_dir: None,
+        internal_name: None,
+        crosses_devices: false,
+        home_fallback: false,
+        problems: Vec::new(),
+    };
+    if path.symlink_metadata().is_err() {
+        check.problems.push(TrashProblem::SourceMissing);
+        return check;
+    }
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => path,
+    };
+    if !is_writable(parent) {
+        check
+            .problems
+            .push(TrashProblem::ParentNotWritable(parent.to_path_buf()));
+    }
+
+    match move_to_trash_with(path, &options.clone().dry_run(true)) {
+        Ok(preview) => {
+            if let Some(dir) = closest_existing(&preview.trash_dir) {
+                if !is_writable(dir) {
+                    check
+                        .problems
+                        .push(TrashProblem::TrashNotWritable(preview.trash_dir.clone()));
+                }
+            }
+            check.internal_name = preview.trash_file.file_name().map(|name| name.to_owned());
+            check.crosses_devices = preview.stats.copied;
+            check.home_fallback = preview.home_fallback;
+            check.location = Some(preview.location);
+            check.trash_dir = Some(preview.trash_dir);
+        }
+        Err(e) => check.problems.push(match e {
+            TrashError::NoHomeTrash | TrashError::NoTopDirTrash(_) => TrashProblem::NoTrash,
+            TrashError::TooLarge { size, limit, .. } => TrashProblem::TooLarge { size, limit },
+            TrashError::NetworkFilesystem(path) => TrashProblem::NetworkFilesystem(path),
+            TrashError::TrashNotWritable(path) | TrashError::TrashNotOwned { path, .. } => {
+                TrashProblem::TrashNotWritable(path)
+            }
+            e => TrashProblem::Other(e.to_string()),
+        }),
+    }
+    check
+}
+
+/// Something that would stop an item from being restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreProblem {
+    /// The item's content is no longer in the trash
+    ItemMissing,
+    /// The trash does not let us take the item out of it
+    TrashNotWritable(PathBuf),
+    /// Something already exists at the original path
+    Conflict,
+    /// The directory the item came from no longer exists
+    MissingParent,
+    /// The directory the item came from cannot be written to
+    ParentNotWritable,
+}
+
+/// What would stop a trashed item from being restored to its original path
+#[derive(Debug, Clone)]
+pub struct RestoreCheck {
+    /// Where the item would be restored to
+    pub destination: PathBuf,
+    /// Everything in the way, empty if the item can be restored
+    pub problems: Vec<RestoreProblem>,
+}
+
+impl RestoreCheck {
+    /// Whether nothing stands in the way of restoring the item
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks whether an item could be restored to its original path,
+/// without touching it
+pub fn can_restore(item: &TrashedItem) -> RestoreCheck {
+    let mut problems = Vec::new();
+    if item.trash_file().symlink_metadata().is_err() {
+        problems.push(RestoreProblem::ItemMissing);
+    }
+    for dir in &["files", "info"] {
+        let dir = item.trash_dir().join(dir);
+        if !is_writable(&dir) {
+            problems.push(RestoreProblem::TrashNotWritable(dir));
+        }
+    }
+    match item.restorability() {
+        Restorability::Restorable => {}
+        Restorability::Conflict => problems.push(RestoreProblem::Conflict),
+        Restorability::MissingParent => problems.push(RestoreProblem::MissingParent),
+        Restorability::ParentNotWritable => problems.push(RestoreProblem::ParentNotWritable),
+    }
+    RestoreCheck {
+        destination: item.original_path().to_path_buf(),
+        problems,
+    }
+}
+
+/// The closest ancestor of a path which exists, the path itself included
+fn closest_existing(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|dir| dir.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{can_restore, can_trash, RestoreProblem, TrashProblem};
+    use crate::{list_trash_in, move_to_trash_in, TrashLocation, TrashOptions};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_preflight() {
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().join("trash"));
+        let options = TrashOptions::new().location(location.clone());
+
+        let path = file_dir.path().join("notes.txt");
+        let check = can_trash(&path, &options);
+        assert_eq!(check.problems, vec![TrashProblem::SourceMissing]);
+
+        std::fs::write(&path, b"12345").unwrap();
+        let check = can_trash(&path, &options);
+        assert!(check.is_ok(), "{:?}", check.problems);
+        assert_eq!(check.internal_name.as_deref(), Some("notes.txt".as_ref()));
+        // Nothing was created to find out
+        assert!(!trash_dir.path().join("trash").exists());
+
+        let check = can_trash(&path, &options.clone().max_size(Some(4)));
+        assert_eq!(
+            check.problems,
+            vec![TrashProblem::TooLarge { size: 5, limit: 4 }]
+        );
+
+        move_to_trash_in(&path, &location).unwrap();
+        let item = list_trash_in(&location).unwrap().remove(0);
+        assert!(can_restore(&item).is_ok());
+        std::fs::write(&path, b"new").unwrap();
+        assert_eq!(can_restore(&item).problems, vec![RestoreProblem::Conflict]);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(item.trash_file()).unwrap();
+        assert_eq!(
+            can_restore(&item).problems,
+            vec![RestoreProblem::ItemMissing]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Running heavy work at a lower priority, behind the `ioprio` feature
+//! Only Linux lets a single thread lower its own priority; elsewhere the
+//! work runs as usual
+
+/// Runs `work` on a thread of its own with the lowest CPU priority and
+/// the idle IO class, so that copying or deleting a large tree does not
+/// slow down the rest of the desktop
+/// Priorities cannot be raised again without privileges, so the calling
+/// thread keeps its own while it waits for `work` to finish
+///
+/// ```no_run
+/// let item = trash::list_trash()?.remove(0);
+/// trash::run_low_priority(move || item.purge())?;
+/// # Ok::<(), trash::TrashError>(())
+/// ```
+pub fn run_low_priority<T, F>(work: F) -> T
+where
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                lower_thread_priority();
+                work()
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Best effort, as the work is done either way
+#[cfg(target_os = "linux")]
+fn lower_thread_priority() {
+    /// From linux/ioprio.h, which libc does not carry
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+    unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        // On Linux, nice values belong to threads rather than processes
+        libc::setpriority(libc::PRIO_PROCESS, tid, 19);
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_thread_priority() {}
+
+#[cfg(test)]
+mod tests {
+    use super::run_low_priority;
+
+    #[test]
+    fn test_run_low_priority() {
+        let caller = std::thread::current().id();
+        let (ran_on, result) = run_low_priority(|| (std::thread::current().id(), 6 * 7));
+        assert_ne!(ran_on, caller);
+        assert_eq!(result, 42);
+        #[cfg(target_os = "linux")]
+        {
+            let nice = run_low_priority(|| unsafe {
+                libc::getpriority(libc::PRIO_PROCESS, libc::syscall(libc::SYS_gettid) as _)
+            });
+            assert_eq!(nice, 19);
+        }
+    }
+}
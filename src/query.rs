@@ -0,0 +1,534 @@
+use crate::location::{current_uid, expected_original, home_dir_of};
+use crate::{iter_trash_in, list_trash_in, TrashEntries, TrashError, TrashLocation, TrashedItem};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Trashed items which were deleted from the same directory
+#[derive(Debug, Clone)]
+pub struct DirectoryGroup {
+    /// The directory the items were deleted from
+    pub directory: PathBuf,
+    /// The items, in the order they were listed
+    pub items: Vec<TrashedItem>,
+    /// Combined size of the items in bytes
+    pub size: u64,
+}
+
+impl DirectoryGroup {
+    /// Number of items deleted from the directory
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+}
+
+/// Groups items by the directory they were deleted from,
+/// ordered by directory
+pub fn group_by_directory(items: Vec<TrashedItem>) -> Result<Vec<DirectoryGroup>, TrashError> {
+    let mut groups: BTreeMap<PathBuf, DirectoryGroup> = BTreeMap::new();
+    for item in items {
+        let size = size_or_zero(&item)?;
+        let directory = item
+            .original_path()
+            .parent()
+            .unwrap_or_else(|| Path::new("/"))
+            .to_path_buf();
+        let group = groups
+            .entry(directory.clone())
+            .or_insert_with(|| DirectoryGroup {
+                directory,
+                items: Vec::new(),
+                size: 0,
+            });
+        group.size += size;
+        group.items.push(item);
+    }
+    Ok(groups.into_values().collect())
+}
+
+/// Bytes trashed from one top level directory, like a line of `du`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryShare {
+    /// A directory right below the home directory, such as `~/Downloads`,
+    /// or right below the root for items from outside the home directory
+    /// Items deleted from the home directory itself count towards it
+    pub directory: PathBuf,
+    /// Number of items deleted from within the directory
+    pub count: usize,
+    /// Combined size of the items in bytes
+    pub size: u64,
+    /// Fraction of all the bytes given which came from the directory,
+    /// between 0 and 1
+    pub share: f64,
+}
+
+/// Sums up the size of items by the top level directory they were
+/// deleted from, largest first, to see where most of the trash came from
+pub fn size_by_top_directory(items: &[TrashedItem]) -> Result<Vec<DirectoryShare>, TrashError> {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .filter(|home| home.is_absolute())
+        .or_else(|| home_dir_of(current_uid()));
+    shares_by_top_directory(items, home.as_deref())
+}
+
+fn shares_by_top_directory(
+    items: &[TrashedItem],
+    home: Option<&Path>,
+) -> Result<Vec<DirectoryShare>, TrashError> {
+    let mut sizes: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+    let mut total = 0;
+    for item in items {
+        let size = size_or_zero(item)?;
+        total += size;
+        let entry = sizes
+            .entry(top_directory(item.original_path(), home))
+            .or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += size;
+    }
+    let mut shares: Vec<DirectoryShare> = sizes
+        .into_iter()
+        .map(|(directory, (count, size))| DirectoryShare {
+            directory,
+            count,
+            size,
+            share: if total == 0 {
+                0.0
+            } else {
+                size as f64 / total as f64
+            },
+        })
+        .collect();
+    shares.sort_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then_with(|| a.directory.cmp(&b.directory))
+    });
+    Ok(shares)
+}
+
+/// The top level directory `path` is in, counting below `home` if it is in there
+fn top_directory(path: &Path, home: Option<&Path>) -> PathBuf {
+    let (base, rest) = match home.and_then(|home| Some((home, path.strip_prefix(home).ok()?))) {
+        Some((home, rest)) => (home, rest),
+        None => (Path::new("/"), path.strip_prefix("/").unwrap_or(path)),
+    };
+    let mut components = rest.components();
+    match (components.next(), components.next()) {
+        // Below a directory of the base, rather than in the base itself
+        (Some(first), Some(_)) => base.join(first),
+        _ => base.to_path_buf(),
+    }
+}
+
+/// The most recently deleted version of a path, standing in for all of them
+#[derive(Debug, Clone)]
+pub struct LatestVersion {
+    /// The newest trashed item with its original path
+    pub item: TrashedItem,
+    /// How many older versions of the same path are in the trash
+    pub older_versions: usize,
+}
+
+/// Collapses items deleted from the same path into the newest of them,
+/// most recently deleted first
+pub fn latest_versions(items: Vec<TrashedItem>) -> Vec<LatestVersion> {
+    let mut latest: HashMap<PathBuf, LatestVersion> = HashMap::new();
+    for item in items {
+        match latest.entry(item.original_path().to_path_buf()) {
+            Entry::Vacant(entry) => {
+                entry.insert(LatestVersion {
+                    item,
+                    older_versions: 0,
+                });
+            }
+            Entry::Occupied(mut entry) => {
+                let version = entry.get_mut();
+                version.older_versions += 1;
+                if item.deleted_at() > version.item.deleted_at() {
+                    version.item = item;
+                }
+            }
+        }
+    }
+    let mut latest: Vec<LatestVersion> = latest.into_values().collect();
+    latest.sort_by_key(|version| std::cmp::Reverse(version.item.deleted_at()));
+    latest
+}
+
+/// A trashed item along with its size
+#[derive(Debug, Clone)]
+pub struct SizedItem {
+    /// The item, whose `deleted_at` says when it was deleted
+    pub item: TrashedItem,
+    /// Size of the item in bytes, as recorded when it was trashed if it was
+    pub size: u64,
+}
+
+/// The `n` largest items in the home trash, largest first,
+/// for freeing up space fast
+pub fn largest_items(n: usize) -> Result<Vec<SizedItem>, TrashError> {
+    largest_items_in(&TrashLocation::Home, n)
+}
+
+/// The `n` largest items in the given trash, largest first
+/// Only `n` items are held on to at any time, and sizes recorded with
+/// `TrashOptions::record_size` are used instead of walking directories
+pub fn largest_items_in(location: &TrashLocation, n: usize) -> Result<Vec<SizedItem>, TrashError> {
+    largest_of(iter_trash_in(location)?, n)
+}
+
+pub(crate) fn largest_of<I>(items: I, n: usize) -> Result<Vec<SizedItem>, TrashError>
+where
+    I: IntoIterator<Item = Result<TrashedItem, TrashError>>,
+{
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    // The smallest of the largest so far is on top, ready to be replaced
+    let mut largest = BinaryHeap::with_capacity(n.saturating_add(1).min(1024));
+    for (index, item) in items.into_iter().enumerate() {
+        let item = item?;
+        let size = size_or_zero(&item)?;
+        largest.push(Reverse(BySize { size, index, item }));
+        if largest.len() > n {
+            largest.pop();
+        }
+    }
+    Ok(largest
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(sized)| SizedItem {
+            item: sized.item,
+            size: sized.size,
+        })
+        .collect())
+}
+
+/// An item ordered by size, then by the order it was listed in
+struct BySize {
+    size: u64,
+    index: usize,
+    item: TrashedItem,
+}
+
+impl BySize {
+    fn key(&self) -> (u64, Reverse<usize>) {
+        (self.size, Reverse(self.index))
+    }
+}
+
+impl PartialEq for BySize {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for BySize {}
+
+impl PartialOrd for BySize {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySize {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Items in the home trash carrying a tag, most recently deleted first
+pub fn list_tagged(tag: &str) -> Result<Vec<TrashedItem>, TrashError> {
+    list_tagged_in(&TrashLocation::Home, tag)
+}
+
+/// Items in the given trash carrying a tag, most recently deleted first
+pub fn list_tagged_in(location: &TrashLocation, tag: &str) -> Result<Vec<TrashedItem>, TrashError> {
+    Ok(list_trash_in(location)?
+        .into_iter()
+        .filter(|item| item.has_tag(tag))
+        .collect())
+}
+
+/// Whether anything trashed from `path` is in the home trash, such as
+/// an earlier version of a file which exists again
+/// Only info files named after the file are read, not the whole trash
+pub fn contains_original<P: AsRef<Path>>(path: P) -> Result<bool, TrashError> {
+    contains_original_in(&TrashLocation::Home, path)
+}
+
+/// Whether anything trashed from `path` is in the given trash
+pub fn contains_original_in<P: AsRef<Path>>(
+    location: &TrashLocation,
+    path: P,
+) -> Result<bool, TrashError> {
+    let original = expected_original(path.as_ref());
+    for item in TrashEntries::named_after(location, location.trash_dir()?, &original)? {
+        if item?.original_path() == original {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Every item in the home trash which was trashed from `path`,
+/// least recently deleted first, to pick a version to restore from
+pub fn versions_of<P: AsRef<Path>>(path: P) -> Result<Vec<TrashedItem>, TrashError> {
+    versions_of_in(&TrashLocation::Home, path)
+}
+
+/// Every item in the given trash which was trashed from `path`,
+/// least recently deleted first
+pub fn versions_of_in<P: AsRef<Path>>(
+    location: &TrashLocation,
+    path: P,
+) -> Result<Vec<TrashedItem>, TrashError> {
+    let original = expected_original(path.as_ref());
+    let mut versions = Vec::new();
+    for item in TrashEntries::named_after(location, location.trash_dir()?, &original)? {
+        let item = item?;
+        if item.original_path() == original {
+            versions.push(item);
+        }
+    }
+    versions.sort_by_key(|item| item.deleted_at());
+    Ok(versions)
+}
+
+/// Size of an item, where an info file without a matching file takes up no space
+pub(crate) fn size_or_zero(item: &TrashedItem) -> Result<u64, TrashError> {
+    match item.size() {
+        Ok(size) => Ok(size),
+        Err(TrashError::ItemNotFound(_)) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        contains_original_in, group_by_directory, largest_items_in, latest_versions,
+        list_tagged_in, list_trash_in, move_to_trash_in, versions_of_in, TrashError, TrashLocation,
+    };
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_group_by_directory() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let downloads = file_dir.path().join("Downloads");
+        std::fs::create_dir(&downloads).unwrap();
+        for (dir, name) in &[(&downloads, "a"), (&downloads, "b")] {
+            std::fs::write(dir.join(name), b"123").unwrap();
+            move_to_trash_in(dir.join(name), &location).unwrap();
+        }
+        std::fs::write(file_dir.path().join("c"), b"1").unwrap();
+        move_to_trash_in(file_dir.path().join("c"), &location).unwrap();
+
+        let groups = group_by_directory(list_trash_in(&location).unwrap()).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].directory, file_dir.path());
+        assert_eq!((groups[0].count(), groups[0].size), (1, 1));
+        assert_eq!(groups[1].directory, downloads);
+        assert_eq!((groups[1].count(), groups[1].size), (2, 6));
+    }
+
+    #[test]
+    fn test_shares_by_top_directory() {
+        let home = tempdir().expect("temp dir creation failed");
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().to_path_buf());
+
+        let nested = home.path().join("Downloads/iso");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("a"), b"123456").unwrap();
+        move_to_trash_in(nested.join("a"), &location).unwrap();
+        std::fs::create_dir(home.path().join("Documents")).unwrap();
+        std::fs::write(home.path().join("Documents/b"), b"12").unwrap();
+        move_to_trash_in(home.path().join("Documents/b"), &location).unwrap();
+        std::fs::write(home.path().join("c"), b"12").unwrap();
+        move_to_trash_in(home.path().join("c"), &location).unwrap();
+
+        let items = list_trash_in(&location).unwrap();
+        let shares = super::shares_by_top_directory(&items, Some(home.path())).unwrap();
+        let summary: Vec<_> = shares
+            .iter()
+            .map(|share| {
+                (
+                    share.directory.clone(),
+                    share.count,
+                    share.size,
+                    share.share,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (home.path().join("Downloads"), 1, 6, 0.6),
+                (home.path().to_path_buf(), 1, 2, 0.2),
+                (home.path().join("Documents"), 1, 2, 0.2),
+            ]
+        );
+
+        // Outside the home directory, directories below the root are used
+        let shares = super::shares_by_top_directory(&items, Some(Path::new("/nowhere"))).unwrap();
+        assert_eq!(shares.len(), 1);
+        assert_eq!(
+            shares[0].directory,
+            Path::new("/").join(home.path().components().nth(1).unwrap())
+        );
+        assert_eq!((shares[0].count, shares[0].size), (3, 10));
+    }
+
+    #[test]
+    fn test_largest_items() {
+        let (file_dir, _trash_dir, location) = fixture();
+        for (name, size) in &[("a", 3), ("b", 10), ("c", 1), ("d", 7), ("e", 7)] {
+            std::fs::write(file_dir.path().join(name), vec![b'x'; *size]).unwrap();
+            move_to_trash_in(file_dir.path().join(name), &location).unwrap();
+        }
+
+        let largest = largest_items_in(&location, 3).unwrap();
+        let sizes: Vec<_> = largest.iter().map(|sized| sized.size).collect();
+        assert_eq!(sizes, [10, 7, 7]);
+        assert_eq!(largest[0].item.internal_filename(), "b");
+        assert_eq!(largest_items_in(&location, 10).unwrap().len(), 5);
+        assert!(largest_items_in(&location, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tags() {
+        let (file_dir, trash_dir, location) = fixture();
+        for name in &["a", "b"] {
+            std::fs::write(file_dir.path().join(name), b"x").unwrap();
+            move_to_trash_in(file_dir.path().join(name), &location).unwrap();
+        }
+
+        let mut item = list_trash_in(&location).unwrap().remove(0);
+        item.add_tag("project-x").unwrap();
+        item.add_tag("maybe-restore").unwrap();
+        item.add_tag("project-x").unwrap();
+        assert!(matches!(
+            item.add_tag("a;b"),
+            Err(TrashError::InvalidTag(_))
+        ));
+        assert_eq!(item.tags(), ["project-x", "maybe-restore"]);
+
+        let tagged = list_tagged_in(&location, "project-x").unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].internal_filename(), item.internal_filename());
+        assert_eq!(tagged[0].tags(), ["project-x", "maybe-restore"]);
+
+        assert!(item.remove_tag("project-x").unwrap());
+        assert!(!item.remove_tag("project-x").unwrap());
+        assert!(list_tagged_in(&location, "project-x").unwrap().is_empty());
+        assert_eq!(list_tagged_in(&location, "maybe-restore").unwrap().len(), 1);
+        // Nothing is left behind in the info directory
+        assert_eq!(
+            std::fs::read_dir(trash_dir.path().join("info"))
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_contains_original() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let report = file_dir.path().join("report.txt");
+        assert!(!contains_original_in(&location, &report).unwrap());
+
+        std::fs::write(&report, b"v1").unwrap();
+        move_to_trash_in(&report, &location).unwrap();
+        std::fs::create_dir(file_dir.path().join("sub")).unwrap();
+        let other = file_dir.path().join("sub/report.txt");
+        std::fs::write(&other, b"v1").unwrap();
+        move_to_trash_in(&other, &location).unwrap();
+        std::fs::remove_dir(file_dir.path().join("sub")).unwrap();
+
+        assert!(contains_original_in(&location, &report).unwrap());
+        assert!(contains_original_in(&location, file_dir.path().join("./report.txt")).unwrap());
+        assert!(contains_original_in(&location, &other).unwrap());
+        assert!(!contains_original_in(&location, file_dir.path().join("report")).unwrap());
+        assert!(!contains_original_in(&location, file_dir.path().join("x/report.txt")).unwrap());
+    }
+
+    #[test]
+    fn test_versions_of() {
+        let (file_dir, trash_dir, location) = fixture();
+        let report = file_dir.path().join("report.txt");
+        for content in &["v1", "v2", "v3"] {
+            std::fs::write(&report, content).unwrap();
+            move_to_trash_in(&report, &location).unwrap();
+        }
+        std::fs::write(file_dir.path().join("report.txt.bak"), b"x").unwrap();
+        move_to_trash_in(file_dir.path().join("report.txt.bak"), &location).unwrap();
+
+        // Backdate the first version, as they were all trashed this second
+        let info_file = trash_dir.path().join("info/report.txt.trashinfo");
+        let info = std::fs::read_to_string(&info_file).unwrap();
+        let dated = info
+            .lines()
+            .map(|line| {
+                if line.starts_with("DeletionDate=") {
+                    "DeletionDate=2001-02-03T04:05:06"
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&info_file, dated).unwrap();
+
+        let versions = versions_of_in(&location, &report).unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0].internal_filename(), "report.txt");
+        assert!(versions
+            .windows(2)
+            .all(|pair| pair[0].deleted_at() <= pair[1].deleted_at()));
+        assert!(versions_of_in(&location, file_dir.path().join("gone"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_latest_versions() {
+        let (file_dir, _trash_dir, location) = fixture();
+
+        let report = file_dir.path().join("report.txt");
+        for content in &["v1", "v2", "v3"] {
+            std::fs::write(&report, content).unwrap();
+            move_to_trash_in(&report, &location).unwrap();
+        }
+        std::fs::write(file_dir.path().join("other"), b"x").unwrap();
+        move_to_trash_in(file_dir.path().join("other"), &location).unwrap();
+
+        let mut items = list_trash_in(&location).unwrap();
+        // Same second, so make the newest version unmistakable
+        for item in &mut items {
+            if item.internal_filename() == "report.txt.3" {
+                let info = item.info().clone();
+                *item = crate::TrashedItem::new(
+                    crate::TrashInfo::unchecked(
+                        info.internal_filename,
+                        info.path,
+                        info.deletion_date + std::time::Duration::from_secs(60),
+                    ),
+                    item.location().clone(),
+                    item.trash_dir().to_path_buf(),
+                );
+            }
+        }
+        let latest = latest_versions(items);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].item.internal_filename(), "report.txt.3");
+        assert_eq!(latest[0].older_versions, 2);
+        assert_eq!(latest[1].older_versions, 0);
+    }
+}
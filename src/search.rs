@@ -0,0 +1,125 @@
+use crate::unicode::{decompose, fold_case, fold_case_canonical};
+use crate::{list_trash_in, TrashError, TrashLocation, TrashedItem};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+
+/// Text to look for in the names of trashed items
+#[derive(Debug, Clone)]
+pub struct TrashSearch {
+    text: String,
+    ignore_normalization: bool,
+    ignore_case: bool,
+}
+
+impl TrashSearch {
+    /// Matches items whose original file name contains `text`
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            ignore_normalization: false,
+            ignore_case: false,
+        }
+    }
+
+    /// Whether names which only differ in Unicode normalization form,
+    /// such as `é` written as one character or as `e` and an accent, match
+    pub fn ignore_normalization(mut self, ignore: bool) -> Self {
+        self.ignore_normalization = ignore;
+        self
+    }
+
+    /// Whether names which only differ in case match, with full Unicode
+    /// case folding so that `STRASSE` finds `Straße`
+    pub fn ignore_case(mut self, ignore: bool) -> Self {
+        self.ignore_case = ignore;
+        self
+    }
+
+    /// Whether a file name contains the text searched for
+    pub fn matches_name(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        self.prepare(&name)
+            .contains(self.prepare(&self.text).as_ref())
+    }
+
+    /// Whether the original file name of an item contains the text searched for
+    pub fn matches(&self, item: &TrashedItem) -> bool {
+        item.original_path()
+            .file_name()
+            .is_some_and(|name| self.matches_name(name))
+    }
+
+    /// Text in the form it is compared in
+    fn prepare<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match (self.ignore_normalization, self.ignore_case) {
+            (false, false) => Cow::Borrowed(text),
+            (true, false) => Cow::Owned(decompose(text)),
+            (false, true) => Cow::Owned(fold_case(text)),
+            (true, true) => Cow::Owned(fold_case_canonical(text)),
+        }
+    }
+}
+
+/// Items in the home trash matching a search, most recently deleted first
+pub fn search_trash(search: &TrashSearch) -> Result<Vec<TrashedItem>, TrashError> {
+    search_trash_in(&TrashLocation::Home, search)
+}
+
+/// Items in the given trash matching a search, most recently deleted first
+pub fn search_trash_in(
+    location: &TrashLocation,
+    search: &TrashSearch,
+) -> Result<Vec<TrashedItem>, TrashError> {
+    Ok(list_trash_in(location)?
+        .into_iter()
+        .filter(|item| search.matches(item))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{move_to_trash_in, search_trash_in, TrashSearch};
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_normalization() {
+        let composed = TrashSearch::new("caf\u{e9}");
+        assert!(composed.matches_name(OsStr::new("caf\u{e9} menu.txt")));
+        assert!(!composed.matches_name(OsStr::new("cafe\u{301} menu.txt")));
+        let composed = composed.ignore_normalization(true);
+        assert!(composed.matches_name(OsStr::new("cafe\u{301} menu.txt")));
+        assert!(!composed.matches_name(OsStr::new("cafe menu.txt")));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let search = TrashSearch::new("STRASSE");
+        assert!(!search.matches_name(OsStr::new("Stra\u{df}e.odt")));
+        let search = search.ignore_case(true);
+        assert!(search.matches_name(OsStr::new("Stra\u{df}e.odt")));
+        assert!(search.matches_name(OsStr::new("strasse.odt")));
+
+        let search = TrashSearch::new("\u{c9}T\u{c9}")
+            .ignore_case(true)
+            .ignore_normalization(true);
+        assert!(search.matches_name(OsStr::new("e\u{301}te\u{301}.jpg")));
+    }
+
+    #[test]
+    fn test_search_trash() {
+        let (file_dir, _trash_dir, location) = fixture();
+        for name in &["Cafe\u{301}.txt", "notes.txt"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"x").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+
+        let search = TrashSearch::new("Caf\u{e9}").ignore_normalization(true);
+        let found = search_trash_in(&location, &search).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].original_path().ends_with("Cafe\u{301}.txt"));
+        let found = search_trash_in(&location, &TrashSearch::new(".txt")).unwrap();
+        assert_eq!(found.len(), 2);
+    }
+}
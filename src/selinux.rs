@@ -0,0 +1,114 @@
+//! Keeping SELinux security contexts on copies, behind the `selinux`
+//! feature, so that an item moved across filesystems is labeled as it
+//! was once restored, rather than as wherever it was copied to
+//! Only Linux has them; elsewhere there is nothing to keep
+
+use std::path::Path;
+
+/// Gives `dest` the security context of `src`, if it has one and the
+/// filesystem of `dest` can hold it
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_context(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let context = match context_of(src)? {
+        Some(context) => context,
+        None => return Ok(()),
+    };
+    let dest = c_path(dest)?;
+    let set = unsafe {
+        libc::lsetxattr(
+            dest.as_ptr(),
+            XATTR.as_ptr().cast(),
+            context.as_ptr().cast(),
+            context.len(),
+            0,
+        )
+    };
+    if set == 0 {
+        return Ok(());
+    }
+    match std::io::Error::last_os_error() {
+        e if e.raw_os_error() == Some(libc::ENOTSUP) => Ok(()),
+        e => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn copy_context(_src: &Path, _dest: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Extended attribute holding the context
+#[cfg(target_os = "linux")]
+const XATTR: &[u8] = b"security.selinux\0";
+
+/// The security context of a path, not following a final symlink, or
+/// `None` if it has none, as without SELinux
+#[cfg(target_os = "linux")]
+pub(crate) fn context_of(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    let path = c_path(path)?;
+    let mut context = vec![0u8; 256];
+    loop {
+        let len = unsafe {
+            libc::lgetxattr(
+                path.as_ptr(),
+                XATTR.as_ptr().cast(),
+                context.as_mut_ptr().cast(),
+                context.len(),
+            )
+        };
+        if len >= 0 {
+            context.truncate(len as usize);
+            return Ok(Some(context));
+        }
+        let e = std::io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::ERANGE) => context.resize(context.len() * 4, 0),
+            Some(libc::ENODATA) | Some(libc::ENOTSUP) => return Ok(None),
+            _ => return Err(e),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> std::io::Result<std::ffi::CString> {
+    use crate::platform::OsStrExt;
+
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{c_path, context_of, copy_context, XATTR};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_copy_context() {
+        let dir = tempdir().expect("temp dir creation failed");
+        let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+        std::fs::write(&src, b"x").unwrap();
+        std::fs::write(&dest, b"x").unwrap();
+        // Nothing to copy, as on systems without SELinux
+        if context_of(&src).unwrap().is_none() {
+            copy_context(&src, &dest).unwrap();
+            assert_eq!(context_of(&dest).unwrap(), None);
+        }
+
+        let context = b"system_u:object_r:user_home_t:s0\0";
+        let set = unsafe {
+            libc::lsetxattr(
+                c_path(&src).unwrap().as_ptr(),
+                XATTR.as_ptr().cast(),
+                context.as_ptr().cast(),
+                context.len(),
+                0,
+            )
+        };
+        // Labels cannot be set by hand under every policy or filesystem
+        if set != 0 {
+            return;
+        }
+        copy_context(&src, &dest).unwrap();
+        assert_eq!(context_of(&dest).unwrap(), context_of(&src).unwrap());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::dirs::TrashDirs;
+use crate::journal::{self, Journal, JournalOperation};
+use crate::location::TrashOwner;
+use crate::{
+    date, home_trash_dir, trash_dir_error, trash_dir_of, write_info_atomically,
+    write_info_via_temp, DeletionDate, InfoTarget, TrashConfig, TrashError, TrashFiles, TrashInfo,
+    TrashLocation,
+};
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A name in the trash with its info file already written, for tools
+/// which put the data in place themselves, such as with rsync
+///
+/// Nothing is moved by this crate: once the data is at `trash_file`,
+/// `commit` makes the item count as trashed. A slot dropped without
+/// being committed is given up again, as with `abort`.
+///
+/// ```no_run
+/// let slot = trash::reserve_trash_slot("/mnt/remote/report.pdf")?;
+/// let status = std::process::Command::new("rsync")
+///     .arg("--remove-source-files")
+///     .arg("remote:report.pdf")
+///     .arg(slot.trash_file())
+///     .status()?;
+/// if status.success() {
+///     slot.commit()?;
+/// } else {
+///     slot.abort();
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct ReservedSlot {
+    original: PathBuf,
+    trash_file: PathBuf,
+    info_file: PathBuf,
+    location: TrashLocation,
+    trash_dir: PathBuf,
+    journal: Option<Journal>,
+    done: bool,
+}
+
+/// Reserves a name in the home trash for the data of `original`
+pub fn reserve_trash_slot<P: AsRef<Path>>(original: P) -> Result<ReservedSlot, TrashError> {
+    reserve_trash_slot_in(original, &TrashLocation::Home)
+}
+
+/// Reserves a name in the given trash for the data of `original`, which
+/// is recorded as where the data came from and need not exist
+pub fn reserve_trash_slot_in<P: AsRef<Path>>(
+    original: P,
+    location: &TrashLocation,
+) -> Result<ReservedSlot, TrashError> {
+    let original = std::path::absolute(original.as_ref())?;
+    let name = match original.file_name() {
+        Some(name) => name.to_os_string(),
+        None => {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("no file name in {}", original.display()),
+            )
+            .into())
+        }
+    };
+    let owner = TrashOwner::current();
+    let trash_dir = trash_dir_of(location, &owner, true)?;
+    let dirs = TrashDirs::open(&trash_dir).map_err(|e| trash_dir_error(&trash_dir, e))?;
+    let info = TrashInfo::unchecked(name, original.clone().into_os_string(), date::now());
+    let info_file = write_info_atomically(
+        &dirs,
+        &original,
+        TrashConfig::current().options().collisions,
+        &info,
+        &owner,
+        false,
+    )
+    .map_err(|e| trash_dir_error(&trash_dir.join("info"), e))?;
+    let trash_file = trash_dir
+        .join("files")
+        .join(info_file.file_stem().unwrap_or_default());
+    Ok(ReservedSlot {
+        original,
+        trash_file,
+        info_file,
+        location: location.clone(),
+        trash_dir,
+        journal: None,
+        done: false,
+    })
+}
+
+/// Makes data which is already in the files directory of a trash, put
+/// there by another tool or left behind by a run which crashed, a trashed
+/// item deleted from `original` at `deleted_at`, by writing its info file
+///
+/// Nothing is moved. Fails with `SourceNotFound` if nothing is at
+/// `trash_file`, and if the item already has an info file, which is left
+/// as it was. The adoption is recorded in `journal` as a trash, if given.
+pub fn adopt_into_trash<P: AsRef<Path>, Q: AsRef<Path>>(
+    trash_file: P,
+    original: Q,
+    deleted_at: DeletionDate,
+    journal: Option<&Journal>,
+) -> Result<TrashFiles, TrashError> {
+    let trash_file = std::path::absolute(trash_file.as_ref())?;
+    if trash_file.symlink_metadata().is_err() {
+        return Err(TrashError::SourceNotFound(trash_file));
+    }
+    let (trash_dir, name) = match (trash_file.parent(), trash_file.file_name()) {
+        (Some(files), Some(name)) if files.file_name() == Some(OsStr::new("files")) => (
+            files.parent().unwrap_or(files).to_path_buf(),
+            name.to_os_string(),
+        ),
+        _ => {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} is not in the files directory of a trash",
+                    trash_file.display()
+                ),
+            )
+            .into())
+        }
+    };
+    let original = std::path::absolute(original.as_ref())?;
+
+    let dirs = TrashDirs::open(&trash_dir).map_err(|e| trash_dir_error(&trash_dir, e))?;
+    let info = TrashInfo::unchecked(name.clone(), original.clone().into_os_string(), deleted_at);
+    let mut info_name = name.clone();
+    info_name.push(".trashinfo");
+    let written = write_info_via_temp(
+        &dirs,
+        InfoTarget::New(&info_name),
+        &info,
+        &TrashOwner::current(),
+        false,
+    );
+    written.map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => std::io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} is already in the trash", trash_file.display()),
+        )
+        .into(),
+        _ => trash_dir_error(&trash_dir.join("info"), e),
+    })?;
+
+    journal::record(
+        journal,
+        JournalOperation::Trash,
+        &trash_dir,
+        &original,
+        &trash_file,
+    );
+    let location = if home_trash_dir().as_deref() == Some(trash_dir.as_path()) {
+        TrashLocation::Home
+    } else {
+        TrashLocation::Custom(trash_dir.clone())
+    };
+    let info_file = trash_dir.join("info").join(info_name);
+    Ok(TrashFiles::new(trash_file, info_file, location, trash_dir))
+}
+
+impl ReservedSlot {
+    /// Where the data is to be put
+    pub fn trash_file(&self) -> &Path {
+        &self.trash_file
+    }
+
+    /// The info file written for the slot
+    pub fn info_file(&self) -> &Path {
+        &self.info_file
+    }
+
+    /// The name reserved in the trash
+    pub fn internal_filename(&self) -> &OsStr {
+        self.trash_file.file_name().unwrap_or_default()
+    }
+
+    /// The path recorded as where the data came from
+    pub fn original_path(&self) -> &Path {
+        &self.original
+    }
+
+    /// Records the commit of the slot in `journal`, none by default
+    pub fn journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Marks the data put at `trash_file` as trashed
+    /// Fails with `SourceNotFound` if nothing is there, giving up the slot
+    pub fn commit(mut self) -> Result<TrashFiles, TrashError> {
+        if self.trash_file.symlink_metadata().is_err() {
+            return Err(TrashError::SourceNotFound(self.trash_file.clone()));
+        }
+        self.done = true;
+        journal::record(
+            self.journal.as_ref(),
+            JournalOperation::Trash,
+            &self.trash_dir,
+            &self.original,
+            &self.trash_file,
+        );
+        Ok(TrashFiles::new(
+            std::mem::take(&mut self.trash_file),
+            std::mem::take(&mut self.info_file),
+            self.location.clone(),
+            std::mem::take(&mut self.trash_dir),
+        ))
+    }
+
+    /// Gives up the slot, removing its info file
+    /// Anything already put at `trash_file` is left for the caller
+    pub fn abort(self) {}
+}
+
+impl Drop for ReservedSlot {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = std::fs::remove_file(&self.info_file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        adopt_into_trash, date, list_trash_in, reserve_trash_slot_in, RestoreStrategy, TrashError,
+        TrashLocation,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reserved_slot() {
+        let trash_dir = tempdir().expect("temp dir creation failed");
+        let location = TrashLocation::Custom(trash_dir.path().to_path_buf());
+
+        let slot = reserve_trash_slot_in("/elsewhere/data.bin", &location).unwrap();
+        let other = reserve_trash_slot_in("/elsewhere/data.bin", &location).unwrap();
+        assert_eq!(slot.internal_filename(), "data.bin");
+        assert_ne!(other.internal_filename(), "data.bin");
+        assert!(other.info_file().is_file());
+        let info_file = other.info_file().to_path_buf();
+        other.abort();
+        assert!(!info_file.exists());
+
+        std::fs::write(slot.trash_file(), b"x").unwrap();
+        let files = slot.commit().unwrap();
+        assert!(files.trash_file.is_file());
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path(), "/elsewhere/data.bin");
+
+        // Nothing put in place
+        let slot = reserve_trash_slot_in("/elsewhere/empty", &location).unwrap();
+        let info_file = slot.info_file().to_path_buf();
+        assert!(matches!(slot.commit(), Err(TrashError::SourceNotFound(_))));
+        assert!(!info_file.exists());
+    }
+
+    #[test]
+    fn test_adopt_into_trash() {
+        let (file_dir, trash_dir, location) = fixture();
+        let files_dir = trash_dir.path().join("files");
+        std::fs::create_dir(&files_dir).unwrap();
+        let trash_file = files_dir.join("moved.txt");
+        std::fs::write(&trash_file, b"x").unwrap();
+        let original = file_dir.path().join("moved.txt");
+        let deleted_at = date::parse("2021-02-03T04:05:06").unwrap();
+
+        let files = adopt_into_trash(&trash_file, &original, deleted_at, None).unwrap();
+        assert_eq!(files.trash_file, trash_file);
+        assert!(files.info_file.is_file());
+        // Already adopted
+        let again = adopt_into_trash(&trash_file, &original, deleted_at, None);
+        assert!(
+            matches!(again, Err(TrashError::Io(ref e)) if e.kind() == std::io::ErrorKind::AlreadyExists)
+        );
+        // Not in a trash, or not there at all
+        let stray = file_dir.path().join("stray");
+        std::fs::write(&stray, b"x").unwrap();
+        assert!(adopt_into_trash(&stray, &original, deleted_at, None).is_err());
+        assert!(matches!(
+            adopt_into_trash(files_dir.join("gone"), &original, deleted_at, None),
+            Err(TrashError::SourceNotFound(_))
+        ));
+
+        let items = list_trash_in(&location).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].deleted_at(), deleted_at);
+        let restored = items[0]
+            .clone()
+            .restore(RestoreStrategy::FailOnConflict)
+            .unwrap();
+        assert_eq!(restored, original);
+        assert!(original.is_file());
+    }
+}
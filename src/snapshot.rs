@@ -0,0 +1,252 @@
+//! Inventories of the trash taken at one point in time, to find out later
+//! what came and went in between
+
+use crate::escape::{decode_os, encode_os};
+use crate::{
+    date, list_all_trash, list_trash_in, DeletionDate, TrashError, TrashLocation, TrashedItem,
+};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// `format` of the header line
+const FORMAT: &str = "trash-snapshot";
+/// `version` of the header line, raised whenever a field changes
+const VERSION: u32 = 1;
+
+/// An item as it was in the trash when a snapshot was taken
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SnapshotItem {
+    /// Root of the trash holding the item
+    pub trash_dir: PathBuf,
+    /// Name of the item inside of the trash
+    pub internal_filename: OsString,
+    /// Where the item was deleted from
+    pub original_path: PathBuf,
+    /// When the item was deleted
+    pub deleted_at: DeletionDate,
+    /// Size in bytes, if it could be found out
+    pub size: Option<u64>,
+}
+
+impl SnapshotItem {
+    fn new(item: &TrashedItem) -> Self {
+        SnapshotItem {
+            trash_dir: item.trash_dir().to_path_buf(),
+            internal_filename: item.internal_filename().to_os_string(),
+            original_path: item.original_path().to_path_buf(),
+            deleted_at: item.deleted_at(),
+            size: item.size().ok(),
+        }
+    }
+
+    /// What tells items apart, leaving out the size which may change
+    fn key(&self) -> (&Path, &OsString, DeletionDate) {
+        (&self.trash_dir, &self.internal_filename, self.deleted_at)
+    }
+}
+
+/// What was in the trash at one point in time, see `snapshot`
+/// It is written out and read back as JSON Lines with `to_text` and
+/// `parse`, one line per item, to keep between runs of an application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashSnapshot {
+    /// When the snapshot was taken, in local time, to the second
+    pub taken_at: DeletionDate,
+    /// The items in the trash, most recently deleted first
+    pub items: Vec<SnapshotItem>,
+}
+
+impl TrashSnapshot {
+    /// The snapshot as JSON Lines, in the shape of a manifest: a header
+    /// `{"format":"trash-snapshot","version":1,"taken_at":...}`, then one
+    /// object per item with `trash_dir`, `name`, `original_path`,
+    /// `deleted_at` and `size`, paths being percent-encoded
+    pub fn to_text(&self) -> String {
+        let encode = |path: &Path| encode_os(path.as_os_str());
+        let header = json!({
+            "format": FORMAT,
+            "version": VERSION,
+            "taken_at": date::format(&self.taken_at),
+        });
+        let mut text = format!("{}\n", header);
+        for item in &self.items {
+            let entry = json!({
+                "trash_dir": encode(&item.trash_dir),
+                "name": encode_os(&item.internal_filename),
+                "original_path": encode(&item.original_path),
+                "deleted_at": date::format(&item.deleted_at),
+                "size": item.size,
+            });
+            text.push_str(&format!("{}\n", entry));
+        }
+        text
+    }
+
+    /// Reads back a snapshot written with `to_text`
+    pub fn parse(text: &str) -> Result<TrashSnapshot, TrashError> {
+        let mut lines = text.lines().enumerate();
+        let invalid = |line: usize| TrashError::InvalidSnapshot { line: line + 1 };
+
+        let header = lines.next().map(|(_, line)| line).unwrap_or_default();
+        let header: Value = serde_json::from_str(header).map_err(|_| invalid(0))?;
+        if header["format"] != FORMAT || header["version"] != VERSION {
+            return Err(invalid(0));
+        }
+        let taken_at = header["taken_at"]
+            .as_str()
+            .and_then(date::parse)
+            .ok_or_else(|| invalid(0))?;
+
+        let mut items = Vec::new();
+        for (number, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            items.push(parse_item(line).ok_or_else(|| invalid(number))?);
+        }
+        Ok(TrashSnapshot { taken_at, items })
+    }
+}
+
+fn parse_item(line: &str) -> Option<SnapshotItem> {
+    let entry: Value = serde_json::from_str(line).ok()?;
+    let path = |key: &str| Some(PathBuf::from(decode_os(entry[key].as_str()?)));
+    let size = match &entry["size"] {
+        Value::Null => None,
+        size => Some(size.as_u64()?),
+    };
+    Some(SnapshotItem {
+        trash_dir: path("trash_dir")?,
+        internal_filename: decode_os(entry["name"].as_str()?),
+        original_path: path("original_path")?,
+        deleted_at: date::parse(entry["deleted_at"].as_str()?)?,
+        size,
+    })
+}
+
+/// What changed in the trash from one snapshot to a later one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrashDiff {
+    /// Items trashed in between
+    pub added: Vec<SnapshotItem>,
+    /// Items which left the trash and are back where they were deleted from
+    pub restored: Vec<SnapshotItem>,
+    /// Items which left the trash for good
+    pub purged: Vec<SnapshotItem>,
+}
+
+impl TrashDiff {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.restored.is_empty() && self.purged.is_empty()
+    }
+}
+
+/// Takes a snapshot of every trash of the user, see `all_trash_locations`
+pub fn snapshot() -> Result<TrashSnapshot, TrashError> {
+    Ok(snapshot_of(&list_all_trash()?))
+}
+
+/// Takes a snapshot of a single trash
+pub fn snapshot_in(location: &TrashLocation) -> Result<TrashSnapshot, TrashError> {
+    Ok(snapshot_of(&list_trash_in(location)?))
+}
+
+fn snapshot_of(items: &[TrashedItem]) -> TrashSnapshot {
+    TrashSnapshot {
+        taken_at: date::whole_seconds(date::now()),
+        items: items.iter().map(SnapshotItem::new).collect(),
+    }
+}
+
+/// What was added to the trash after snapshot `a` was taken and before
+/// `b` was, and what left it
+/// Snapshots do not tell why an item left the trash, so it counts as
+/// restored if something is at its original path now, and as purged
+/// otherwise
+pub fn diff(a: &TrashSnapshot, b: &TrashSnapshot) -> TrashDiff {
+    let before: HashSet<_> = a.items.iter().map(SnapshotItem::key).collect();
+    let after: HashSet<_> = b.items.iter().map(SnapshotItem::key).collect();
+
+    let added = b
+        .items
+        .iter()
+        .filter(|item| !before.contains(&item.key()))
+        .cloned()
+        .collect();
+    let (restored, purged) = a
+        .items
+        .iter()
+        .filter(|item| !after.contains(&item.key()))
+        .cloned()
+        .partition(|item| item.original_path.symlink_metadata().is_ok());
+    TrashDiff {
+        added,
+        restored,
+        purged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::{
+        diff, list_trash_in, move_to_trash_in, snapshot_in, RestoreStrategy, TrashError,
+        TrashSnapshot,
+    };
+
+    #[test]
+    fn test_snapshot_diff() {
+        let (file_dir, _trash_dir, location) = fixture();
+        for name in &["kept", "restored", "purged"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"12345").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+        let before = snapshot_in(&location).unwrap();
+        assert_eq!(before.items.len(), 3);
+        assert!(before.items.iter().all(|item| item.size == Some(5)));
+
+        let path = file_dir.path().join("new\tname");
+        std::fs::write(&path, b"").unwrap();
+        move_to_trash_in(&path, &location).unwrap();
+        for item in list_trash_in(&location).unwrap() {
+            match item.internal_filename().to_str() {
+                Some("restored") => {
+                    item.restore(RestoreStrategy::FailOnConflict).unwrap();
+                }
+                Some("purged") => item.purge().unwrap(),
+                _ => {}
+            }
+        }
+        let after = snapshot_in(&location).unwrap();
+
+        let parsed = TrashSnapshot::parse(&after.to_text()).unwrap();
+        assert_eq!(parsed, after);
+        let changes = diff(&before, &parsed);
+        let names = |items: &[crate::SnapshotItem]| {
+            items
+                .iter()
+                .map(|item| item.internal_filename.clone().into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&changes.added), ["new\tname"]);
+        assert_eq!(names(&changes.restored), ["restored"]);
+        assert_eq!(names(&changes.purged), ["purged"]);
+        assert!(diff(&after, &parsed).is_empty());
+
+        let header = "{\"format\":\"trash-snapshot\",\"version\":1,\
+                      \"taken_at\":\"2020-01-01T00:00:00\"}";
+        assert!(TrashSnapshot::parse(header).unwrap().items.is_empty());
+        match TrashSnapshot::parse(&format!("{}\nbogus\n", header)) {
+            Err(TrashError::InvalidSnapshot { line }) => assert_eq!(line, 2),
+            other => panic!("unexpected {:?}", other),
+        }
+        match TrashSnapshot::parse("trash-snapshot\t1\t2020-01-01T00:00:00\n") {
+            Err(TrashError::InvalidSnapshot { line }) => assert_eq!(line, 1),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
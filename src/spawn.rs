@@ -0,0 +1,209 @@
+//! Where the async layer runs blocking filesystem work, behind the
+//! `async` feature
+//! By default each piece of work gets a thread of its own. Apps on an
+//! executor with a pool for blocking work can hand it that work instead
+//! with `TrashOptions::spawner`, without the crate depending on any executor
+
+use crate::{move_to_trash_with, TrashError, TrashFiles, TrashLocation, TrashOptions, TrashedItem};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Runs blocking work somewhere it may block, such as an executor's
+/// pool of threads for blocking work
+///
+/// For tokio, that is:
+///
+/// ```ignore
+/// struct Tokio;
+///
+/// impl trash::Spawner for Tokio {
+///     fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+///         tokio::task::spawn_blocking(task);
+///     }
+/// }
+///
+/// let options = trash::TrashOptions::new().spawner(Tokio);
+/// ```
+pub trait Spawner: Send + Sync {
+    /// Runs `task` to completion without blocking the caller
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>);
+}
+
+/// Runs each task on a new thread, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSpawner;
+
+impl Spawner for ThreadSpawner {
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+        std::thread::spawn(task);
+    }
+}
+
+/// Blocking work running on the spawner, finishing with its result
+#[derive(Debug)]
+pub struct Blocking<T> {
+    shared: Arc<Mutex<Outcome<T>>>,
+}
+
+#[derive(Debug)]
+struct Outcome<T> {
+    result: Option<std::thread::Result<T>>,
+    /// The spawner dropped the task without running it
+    lost: bool,
+    waker: Option<Waker>,
+}
+
+/// Sets the outcome of a task, or marks it lost if it never ran
+struct Finisher<T> {
+    shared: Arc<Mutex<Outcome<T>>>,
+    result: Option<std::thread::Result<T>>,
+}
+
+impl<T> Finisher<T> {
+    fn finish(mut self, result: std::thread::Result<T>) {
+        self.result = Some(result);
+    }
+}
+
+impl<T> Drop for Finisher<T> {
+    fn drop(&mut self) {
+        let mut outcome = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match self.result.take() {
+            Some(result) => outcome.result = Some(result),
+            None => outcome.lost = true,
+        }
+        if let Some(waker) = outcome.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Runs blocking `work` on `spawner`, for awaiting its result
+pub fn run_blocking<T, F>(spawner: &dyn Spawner, work: F) -> Blocking<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Outcome {
+        result: None,
+        lost: false,
+        waker: None,
+    }));
+    let finisher = Finisher {
+        shared: Arc::clone(&shared),
+        result: None,
+    };
+    spawner.spawn_blocking(Box::new(move || {
+        // A panic is handed over to whoever awaits the result
+        finisher.finish(std::panic::catch_unwind(std::panic::AssertUnwindSafe(work)));
+    }));
+    Blocking { shared }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut outcome = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match outcome.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None if outcome.lost => panic!("the spawner dropped a task without running it"),
+            None => {
+                outcome.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Moves a file or directory to the trash as configured by `options`,
+/// without blocking the executor, on the spawner they give
+pub fn move_to_trash_async<P: Into<PathBuf>>(
+    path: P,
+    options: TrashOptions,
+) -> Blocking<Result<TrashFiles, TrashError>> {
+    let path = path.into();
+    let spawner = options.spawner.clone();
+    let work = move || move_to_trash_with(path, &options);
+    match spawner {
+        Some(spawner) => run_blocking(spawner.as_ref(), work),
+        None => run_blocking(&ThreadSpawner, work),
+    }
+}
+
+/// Lists the items in the given trash, most recently deleted first,
+/// without blocking the executor, on a thread of its own
+/// See `stream_trash_in` to have the items as they are read, and
+/// `run_blocking` to list on another spawner
+pub fn list_trash_async(location: TrashLocation) -> Blocking<Result<Vec<TrashedItem>, TrashError>> {
+    run_blocking(&ThreadSpawner, move || crate::list_trash_in(&location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_blocking, Spawner};
+    use crate::stream::tests::block_on;
+    use crate::tests::fixture;
+    use crate::tests::lock_env;
+    use crate::{list_trash_async, move_to_trash_async, Trash, TrashOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Runs tasks right away, counting them
+    #[derive(Clone)]
+    struct Counting(Arc<AtomicUsize>);
+
+    impl Spawner for Counting {
+        fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            task();
+        }
+    }
+
+    #[test]
+    fn test_spawner() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let path = file_dir.path().join("file");
+        std::fs::write(&path, b"x").unwrap();
+
+        let spawner = Counting(Arc::new(AtomicUsize::new(0)));
+        let options = TrashOptions::new()
+            .location(location.clone())
+            .spawner(spawner.clone());
+        let files = block_on(move_to_trash_async(&path, options)).unwrap();
+        assert!(files.trash_file.exists());
+        let listed = location.clone();
+        let items = block_on(run_blocking(&spawner, move || {
+            crate::list_trash_in(&listed)
+        }));
+        assert_eq!(items.unwrap().len(), 1);
+        assert_eq!(spawner.0.load(Ordering::SeqCst), 2);
+
+        // A thread per task without one
+        let items = block_on(list_trash_async(location)).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(spawner.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_handle_spawner() {
+        let _env = lock_env();
+        let (file_dir, data_home, _) = fixture();
+        std::env::set_var("XDG_DATA_HOME", data_home.path());
+        let spawner = Counting(Arc::new(AtomicUsize::new(0)));
+        let trash = Trash::with_options(TrashOptions::new().spawner(spawner.clone()));
+        std::env::remove_var("XDG_DATA_HOME");
+
+        let path = file_dir.path().join("file");
+        std::fs::write(&path, b"x").unwrap();
+        trash.move_to_trash(&path).unwrap();
+        let mut stream = trash.stream();
+        assert!(block_on(stream.next()).unwrap().is_ok());
+        assert!(block_on(stream.next()).is_none());
+        assert_eq!(spawner.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,252 @@
+//! Listing a trash without blocking, behind the `async` feature
+//! Info files are read on a spawner, see `TrashOptions::spawner`, and
+//! handed over as they are read, so any executor can drive the stream
+
+use crate::spawn::{Spawner, ThreadSpawner};
+use crate::{TrashEntries, TrashError, TrashLocation, TrashedItem};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// How many items are read ahead of the consumer, at most
+const READ_AHEAD: usize = 64;
+
+/// The items of a trash as they are read, in directory order, from
+/// `stream_trash`, `stream_trash_in` or `Trash::stream`
+///
+/// It is a `futures_core::Stream`, and also has `next` for use without
+/// any stream combinators. Reading stays at most a few dozen items ahead
+/// of the consumer, so a slow consumer of a large trash does not have it
+/// all buffered.
+///
+/// ```no_run
+/// # async fn show() {
+/// let mut items = trash::stream_trash();
+/// while let Some(item) = items.next().await {
+///     match item {
+///         Ok(item) => println!("{}", item.original_path().display()),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TrashStream {
+    channel: Arc<Channel>,
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    shared: Mutex<Shared>,
+    /// Signalled when an item was taken or the stream dropped, for the
+    /// reader waiting for room
+    space: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Read and not yet taken
+    items: VecDeque<Result<TrashedItem, TrashError>>,
+    /// Whether every item has been read
+    done: bool,
+    /// Whether the stream was dropped, so reading can stop
+    dropped: bool,
+    /// Waiting for the next item
+    waker: Option<Waker>,
+}
+
+/// Streams the items of the home trash
+pub fn stream_trash() -> TrashStream {
+    stream_trash_in(&TrashLocation::Home)
+}
+
+/// Streams the items of the given trash, read on a thread of their own
+/// A trash which cannot be read at all yields the error and ends
+pub fn stream_trash_in(location: &TrashLocation) -> TrashStream {
+    let location = location.clone();
+    stream_entries(&ThreadSpawner, move || {
+        TrashEntries::new(&location, location.trash_dir()?)
+    })
+}
+
+/// Streams the items `entries` opens, reading them on `spawner`
+pub(crate) fn stream_entries<F>(spawner: &dyn Spawner, entries: F) -> TrashStream
+where
+    F: FnOnce() -> Result<TrashEntries, TrashError> + Send + 'static,
+{
+    let channel = Arc::new(Channel::default());
+    let reader = Arc::clone(&channel);
+    spawner.spawn_blocking(Box::new(move || {
+        let entries = entries();
+        let send = |item| {
+            let mut shared = reader.lock();
+            while shared.items.len() >= READ_AHEAD && !shared.dropped {
+                shared = reader.space.wait(shared).unwrap_or_else(|e| e.into_inner());
+            }
+            if shared.dropped {
+                return false;
+            }
+            shared.items.push_back(item);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+            true
+        };
+        match entries {
+            Ok(entries) => {
+                for item in entries {
+                    if !send(item) {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                send(Err(e));
+            }
+        }
+        let mut shared = reader.lock();
+        shared.done = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }));
+    TrashStream { channel }
+}
+
+impl TrashStream {
+    /// The next item if one has been read, `None` once all have been,
+    /// or `Poll::Pending` with `cx` woken up once there is more
+    pub fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<TrashedItem, TrashError>>> {
+        let mut shared = self.channel.lock();
+        if let Some(item) = shared.items.pop_front() {
+            self.channel.space.notify_one();
+            return Poll::Ready(Some(item));
+        }
+        if shared.done {
+            return Poll::Ready(None);
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Waits for the next item, `None` once all have been read
+    pub async fn next(&mut self) -> Option<Result<TrashedItem, TrashError>> {
+        std::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+}
+
+impl futures_core::Stream for TrashStream {
+    type Item = Result<TrashedItem, TrashError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        TrashStream::poll_next(self.get_mut(), cx)
+    }
+}
+
+impl Drop for TrashStream {
+    fn drop(&mut self) {
+        self.channel.lock().dropped = true;
+        self.channel.space.notify_one();
+    }
+}
+
+impl Channel {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::tests::fixture;
+    use crate::{move_to_trash_in, stream_trash_in, TrashLocation};
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs a future to completion on this thread, as an executor would
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Arc::new(Unpark(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_trash() {
+        let (file_dir, trash_dir, location) = fixture();
+        for i in 0..10 {
+            let path = file_dir.path().join(i.to_string());
+            std::fs::write(&path, b"x").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+
+        let mut names: Vec<_> = block_on(async {
+            let mut stream = stream_trash_in(&location);
+            let mut names = Vec::new();
+            while let Some(item) = stream.next().await {
+                names.push(
+                    item.unwrap()
+                        .internal_filename()
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+            }
+            names
+        });
+        names.sort_by_key(|name| name.parse::<u32>().unwrap());
+        let expected: Vec<_> = (0..10).map(|i| i.to_string()).collect();
+        assert_eq!(names, expected);
+
+        // A trash which was never used is simply empty
+        let unused = TrashLocation::Custom(trash_dir.path().join("unused"));
+        assert!(block_on(stream_trash_in(&unused).next()).is_none());
+    }
+
+    #[test]
+    fn test_stream_reads_ahead_only_so_far() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let count = super::READ_AHEAD + 10;
+        for i in 0..count {
+            let path = file_dir.path().join(i.to_string());
+            std::fs::write(&path, b"x").unwrap();
+            move_to_trash_in(&path, &location).unwrap();
+        }
+
+        let mut stream = stream_trash_in(&location);
+        // Give the reader time to read as far as it may
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(stream.channel.lock().items.len(), super::READ_AHEAD);
+        let mut taken = 0;
+        while block_on(futures_core_next(&mut stream)).is_some() {
+            taken += 1;
+        }
+        assert_eq!(taken, count);
+    }
+
+    /// Takes the next item through the `Stream` implementation
+    async fn futures_core_next(
+        stream: &mut crate::TrashStream,
+    ) -> Option<Result<crate::TrashedItem, crate::TrashError>> {
+        use futures_core::Stream;
+
+        std::future::poll_fn(|cx| std::pin::Pin::new(&mut *stream).poll_next(cx)).await
+    }
+}
@@ -0,0 +1,309 @@
+use crate::location::{TrashCache, TrashOwner};
+use crate::query::largest_of;
+#[cfg(feature = "async")]
+use crate::stream::stream_entries;
+use crate::{
+    purge_all, trash_path, undo_in, DeletionDay, Journal, RestoreStrategy, SizedItem, TrashConfig,
+    TrashEntries, TrashError, TrashFiles, TrashLocation, TrashOptions, TrashedItem, UndoOutcome,
+    JOURNAL_FILE,
+};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// A reusable handle to the trash of the user running this process
+///
+/// The home trash and the trashes of other devices are looked up once
+/// and remembered, which saves work when trashing many files in a row
+/// Every file is trashed as configured by the handle's `TrashOptions`
+///
+/// ```no_run
+/// let trash = trash::Trash::new();
+/// for path in &["a.txt", "b.txt"] {
+///     trash.move_to_trash(path)?;
+/// }
+/// # Ok::<(), trash::TrashError>(())
+/// ```
+#[derive(Debug)]
+pub struct Trash {
+    options: TrashOptions,
+    owner: TrashOwner,
+    cache: TrashCache,
+}
+
+impl Trash {
+    /// A handle using the defaults from the configuration file, see
+    /// `TrashConfig`
+    pub fn new() -> Self {
+        Self::with_options(TrashConfig::current().options())
+    }
+
+    /// A handle trashing files as configured by `options`
+    pub fn with_options(options: TrashOptions) -> Self {
+        let mut owner = TrashOwner::current();
+        if owner.home_trash.is_none() {
+            owner.home_trash = options.home_trash_fallback.clone();
+        }
+        let cache = TrashCache::new(&owner);
+        Self {
+            options,
+            owner,
+            cache,
+        }
+    }
+
+    /// The options files are trashed with
+    pub fn options(&self) -> &TrashOptions {
+        &self.options
+    }
+
+    /// Root of the home trash, if there is one
+    pub fn home_trash_dir(&self) -> Option<&Path> {
+        self.owner.home_trash.as_deref()
+    }
+
+    /// Moves a file or directory to the trash
+    pub fn move_to_trash<P: AsRef<Path>>(&self, path: P) -> Result<TrashFiles, TrashError> {
+        trash_path(
+            path.as_ref(),
+            &self.options,
+            Some((&self.owner, &self.cache)),
+        )
+    }
+
+    /// Lists the items in the home trash, most recently deleted first
+    pub fn list(&self) -> Result<Vec<TrashedItem>, TrashError> {
+        let mut items = self.entries()?.collect::<Result<Vec<_>, _>>()?;
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at()));
+        Ok(items)
+    }
+
+    /// Moves an item in the home trash back to the location it was deleted from
+    /// Returns the path the item was restored to
+    pub fn restore(&self, internal_filename: &OsStr) -> Result<PathBuf, TrashError> {
+        self.item(internal_filename)?
+            .restore(RestoreStrategy::FailOnConflict)
+    }
+
+    /// Permanently deletes an item from the home trash
+    pub fn purge(&self, internal_filename: &OsStr) -> Result<(), TrashError> {
+        self.item(internal_filename)?.purge()
+    }
+
+    /// Permanently deletes everything in the home trash
+    /// Returns the items which were deleted
+    pub fn empty(&self) -> Result<Vec<TrashedItem>, TrashError> {
+        purge_all(self.list()?)
+    }
+
+    /// The `n` largest items in the home trash, largest first
+    pub fn largest(&self, n: usize) -> Result<Vec<SizedItem>, TrashError> {
+        largest_of(self.entries()?, n)
+    }
+
+    /// The least recently deleted item in the home trash, if it isn't empty
+    /// Like `newest` and `deleted_on`, this reads the info files one by
+    /// one without listing and sorting the whole trash
+    pub fn oldest(&self) -> Result<Option<TrashedItem>, TrashError> {
+        self.entries()?
+            .try_fold(None, |oldest: Option<TrashedItem>, item| {
+                let item = item?;
+                Ok(match oldest {
+                    Some(oldest) if oldest.deleted_at() <= item.deleted_at() => Some(oldest),
+                    _ => Some(item),
+                })
+            })
+    }
+
+    /// The most recently deleted item in the home trash, if it isn't empty
+    pub fn newest(&self) -> Result<Option<TrashedItem>, TrashError> {
+        self.entries()?
+            .try_fold(None, |newest: Option<TrashedItem>, item| {
+                let item = item?;
+                Ok(match newest {
+                    Some(newest) if newest.deleted_at() >= item.deleted_at() => Some(newest),
+                    _ => Some(item),
+                })
+            })
+    }
+
+    /// The items in the home trash deleted on the given day in local time,
+    /// most recently deleted first
+    pub fn deleted_on(&self, day: DeletionDay) -> Result<Vec<TrashedItem>, TrashError> {
+        let mut items = Vec::new();
+        for item in self.entries()? {
+            let item = item?;
+            if item.deleted_at().date() == day {
+                items.push(item);
+            }
+        }
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at()));
+        Ok(items)
+    }
+
+    /// Streams the items in the home trash as they are read, on the
+    /// spawner of the options, see `TrashOptions::spawner`
+    #[cfg(feature = "async")]
+    pub fn stream(&self) -> crate::TrashStream {
+        let trash_dir = self.home_dir();
+        let policy = self.options.escape_policy;
+        let journal = self.options.journal.clone();
+        stream_entries(self.options.blocking_spawner(), move || {
+            Ok(TrashEntries::new(&TrashLocation::Home, trash_dir?)?
+                .escape_policy(policy)
+                .journal(journal))
+        })
+    }
+
+    /// Walks back the last `n` operations in the journal kept with
+    /// `TrashOptions::journal`, the one in the home trash for
+    /// `Journal::InTrash`, see `undo_in`
+    pub fn undo(&self, n: usize) -> Result<Vec<UndoOutcome>, TrashError> {
+        match &self.options.journal {
+            None => Err(TrashError::NoJournal),
+            Some(Journal::InTrash) => undo_in(self.home_dir()?.join(JOURNAL_FILE), n),
+            Some(Journal::File(path)) => undo_in(path, n),
+        }
+    }
+
+    fn entries(&self) -> Result<TrashEntries, TrashError> {
+        Ok(TrashEntries::new(&TrashLocation::Home, self.home_dir()?)?
+            .escape_policy(self.options.escape_policy)
+            .journal(self.options.journal.clone()))
+    }
+
+    fn home_dir(&self) -> Result<PathBuf, TrashError> {
+        self.owner.home_trash.clone().ok_or(TrashError::NoHomeTrash)
+    }
+
+    fn item(&self, internal_filename: &OsStr) -> Result<TrashedItem, TrashError> {
+        let item = TrashedItem::load_from(
+            internal_filename,
+            &TrashLocation::Home,
+            self.home_dir()?,
+            self.options.escape_policy,
+        )?;
+        Ok(item.with_journal(self.options.journal.clone()))
+    }
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::fixture;
+    use crate::tests::lock_env;
+    use crate::{EscapePolicy, Trash, TrashLocation, TrashOptions};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_handle_reuses_home_trash() {
+        let _env = lock_env();
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
+        std::env::set_var("XDG_DATA_HOME", temp_xdg_data_home.path().as_os_str());
+        let trash = Trash::new();
+        // Looked up once, so later changes are not picked up
+        std::env::remove_var("XDG_DATA_HOME");
+
+        let home_trash = temp_xdg_data_home.path().join("Trash");
+        assert_eq!(trash.home_trash_dir(), Some(home_trash.as_path()));
+        for name in &["one", "two"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"x").unwrap();
+            let files = trash.move_to_trash(&path).unwrap();
+            assert_eq!(files.location, TrashLocation::Home);
+            assert!(files.trash_file.starts_with(&home_trash));
+        }
+        let items = trash.list().unwrap();
+        assert_eq!(items.len(), 2);
+        trash.restore(items[0].internal_filename()).unwrap();
+        assert_eq!(trash.empty().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_oldest_newest() {
+        let _env = lock_env();
+        let file_dir = tempdir().expect("temp dir creation failed");
+        let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
+        std::env::set_var("XDG_DATA_HOME", temp_xdg_data_home.path().as_os_str());
+        let trash = Trash::new();
+        std::env::remove_var("XDG_DATA_HOME");
+        assert!(trash.oldest().unwrap().is_none());
+        assert!(trash.newest().unwrap().is_none());
+
+        for name in &["old", "middle", "new"] {
+            let path = file_dir.path().join(name);
+            std::fs::write(&path, b"x").unwrap();
+            trash.move_to_trash(&path).unwrap();
+        }
+        // Trashed within the same second, so spread the dates out
+        let info_dir = temp_xdg_data_home.path().join("Trash/info");
+        for (name, date) in &[
+            ("old", "2001-02-03T04:05:06"),
+            ("middle", "2001-02-04T10:00:00"),
+            ("new", "2001-02-04T11:00:00"),
+        ] {
+            let info_file = info_dir.join(format!("{}.trashinfo", name));
+            let info = std::fs::read_to_string(&info_file).unwrap();
+            let dated: Vec<String> = info
+                .lines()
+                .map(|line| {
+                    if line.starts_with("DeletionDate=") {
+                        format!("DeletionDate={}", date)
+                    } else {
+                        line.to_owned()
+                    }
+                })
+                .collect();
+            std::fs::write(&info_file, dated.join("\n")).unwrap();
+        }
+
+        assert_eq!(trash.oldest().unwrap().unwrap().internal_filename(), "old");
+        assert_eq!(trash.newest().unwrap().unwrap().internal_filename(), "new");
+        let day = trash.newest().unwrap().unwrap().deleted_at().date();
+        let names: Vec<_> = trash
+            .deleted_on(day)
+            .unwrap()
+            .iter()
+            .map(|item| item.internal_filename().to_os_string())
+            .collect();
+        assert_eq!(names, ["new", "middle"]);
+    }
+
+    #[test]
+    fn test_handle_escape_policy() {
+        let _env = lock_env();
+        let temp_xdg_data_home = tempdir().expect("temp dir creation failed");
+        std::env::set_var("XDG_DATA_HOME", temp_xdg_data_home.path().as_os_str());
+        let strict = Trash::with_options(TrashOptions::new());
+        let lossy = Trash::with_options(TrashOptions::new().escape_policy(EscapePolicy::Lossy));
+        std::env::remove_var("XDG_DATA_HOME");
+
+        let info_dir = temp_xdg_data_home.path().join("Trash/info");
+        std::fs::create_dir_all(&info_dir).unwrap();
+        std::fs::write(
+            info_dir.join("a.trashinfo"),
+            "[Trash Info]\nPath=/a%zz\nDeletionDate=2020-01-02T03:04:05\n",
+        )
+        .unwrap();
+        assert!(strict.list().is_err());
+        let items = lossy.list().unwrap();
+        assert_eq!(items[0].info().path, "/a%zz");
+        assert_eq!(items[0].info().malformed_path, Some(EscapePolicy::Lossy));
+        assert!(lossy.newest().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_handle_with_custom_location() {
+        let (file_dir, _trash_dir, location) = fixture();
+        let trash = Trash::with_options(TrashOptions::new().location(location.clone()));
+
+        let path = file_dir.path().join("file");
+        std::fs::write(&path, b"x").unwrap();
+        assert_eq!(trash.move_to_trash(&path).unwrap().location, location);
+    }
+}
@@ -0,0 +1,52 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Canonical decomposition (NFD) of a string, so that text differing
+/// only in normalization form, such as names written by macOS, compares equal
+pub(crate) fn decompose(text: &str) -> String {
+    text.nfd().collect()
+}
+
+/// Full Unicode case folding of a string, so that text differing only
+/// in case compares equal, `ß` with `ss` included
+pub(crate) fn fold_case(text: &str) -> String {
+    caseless::default_case_fold_str(text)
+}
+
+/// Case folding which also ignores the normalization form, as canonical
+/// caseless matching does: `NFD(fold(NFD(text)))`, decomposing again
+/// since folding can undo a decomposition, as with `İ`
+pub(crate) fn fold_case_canonical(text: &str) -> String {
+    decompose(&fold_case(&decompose(text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompose, fold_case, fold_case_canonical};
+
+    #[test]
+    fn test_decompose() {
+        assert_eq!(decompose("caf\u{e9}"), "cafe\u{301}");
+        assert_eq!(decompose("cafe\u{301}"), "cafe\u{301}");
+        // Ệ is E, then the dot below before the circumflex
+        assert_eq!(decompose("\u{1ec6}"), "E\u{323}\u{302}");
+        assert_eq!(decompose("E\u{302}\u{323}"), "E\u{323}\u{302}");
+        assert_eq!(decompose("\u{d55c}"), "\u{1112}\u{1161}\u{11ab}");
+        assert_eq!(decompose("plain.txt"), "plain.txt");
+    }
+
+    #[test]
+    fn test_fold_case() {
+        assert_eq!(fold_case("Report.PDF"), "report.pdf");
+        assert_eq!(fold_case("Stra\u{df}e"), "strasse");
+        assert_eq!(fold_case("STRASSE"), "strasse");
+        assert_eq!(
+            fold_case("\u{3a3}\u{3bf}\u{3c6}\u{3af}\u{3b1}\u{3c2}"),
+            fold_case("\u{3a3}\u{39f}\u{3a6}\u{38a}\u{391}\u{3a3}")
+        );
+        assert_eq!(
+            fold_case_canonical("\u{130}le.TXT"),
+            fold_case_canonical("i\u{307}le.txt")
+        );
+        assert_eq!(fold_case_canonical("\u{1ec6}"), "e\u{323}\u{302}");
+    }
+}
@@ -0,0 +1,140 @@
+use crate::platform::{OsStrExt, OsStringExt};
+use percent_encoding::{percent_decode, percent_encode, PATH_SEGMENT_ENCODE_SET};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+const SCHEME: &str = "trash:";
+
+/// An address in the `trash:///` URI scheme used by GIO and KIO
+///
+/// `trash:///name` refers to the trashed item with the internal filename
+/// `name`, and `trash:///name/inner/file` to a file inside a trashed directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashUri {
+    /// Internal filename of the trashed item
+    pub internal_filename: OsString,
+    /// Path within a trashed directory, empty for the item itself
+    pub inner_path: PathBuf,
+}
+
+impl TrashUri {
+    /// URI of a trashed item itself
+    pub fn new(internal_filename: OsString) -> Self {
+        Self {
+            internal_filename,
+            inner_path: PathBuf::new(),
+        }
+    }
+
+    /// Parses a `trash:` URI, returning `None` for other schemes or
+    /// for the root of the trash (`trash:///`) which names no item
+    pub fn parse(uri: &str) -> Option<Self> {
+        let rest = uri.strip_prefix(SCHEME)?;
+        let mut segments = rest
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| OsString::from_vec(percent_decode(segment.as_bytes()).collect()));
+
+        let internal_filename = segments.next()?;
+        if !is_plain_name(&internal_filename) {
+            return None;
+        }
+        let mut inner_path = PathBuf::new();
+        for segment in segments {
+            if !is_plain_name(&segment) {
+                return None;
+            }
+            inner_path.push(segment);
+        }
+        Some(Self {
+            internal_filename,
+            inner_path,
+        })
+    }
+
+    /// Location of the addressed file inside of a trash directory
+    pub fn resolve(&self, trash_dir: &Path) -> PathBuf {
+        trash_dir
+            .join("files")
+            .join(&self.internal_filename)
+            .join(&self.inner_path)
+    }
+}
+
+/// Whether a decoded segment names an entry of a directory and nothing
+/// else, so that a URI never escapes the trashed item or the trash,
+/// whether by `..`, an escaped `/` or an absolute path
+fn is_plain_name(segment: &OsStr) -> bool {
+    let bytes = segment.as_bytes();
+    !bytes.is_empty()
+        && bytes != b"."
+        && bytes != b".."
+        && !bytes.contains(&b'/')
+        && !bytes.contains(&0)
+}
+
+impl std::fmt::Display for TrashUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}//", SCHEME)?;
+        let segments = std::iter::once(self.internal_filename.as_os_str())
+            .chain(self.inner_path.iter().map(OsStr::new));
+        for segment in segments {
+            write!(
+                f,
+                "/{}",
+                percent_encode(segment.as_bytes(), PATH_SEGMENT_ENCODE_SET)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TrashUri;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_uri_round_trip() {
+        let uri = TrashUri::new(OsString::from_vec(b"100% sure \xff.txt.2".to_vec()));
+        let uri = TrashUri {
+            inner_path: PathBuf::from("sub dir/file"),
+            ..uri
+        };
+        let s = uri.to_string();
+        assert_eq!(s, "trash:///100%25%20sure%20%FF.txt.2/sub%20dir/file");
+        assert_eq!(TrashUri::parse(&s), Some(uri));
+
+        assert_eq!(
+            TrashUri::parse("trash:/notes.txt"),
+            Some(TrashUri::new(OsString::from("notes.txt")))
+        );
+        assert_eq!(TrashUri::parse("trash:///"), None);
+        assert_eq!(TrashUri::parse("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_uri_stays_in_trash() {
+        for uri in &[
+            "trash:///a/../../etc",
+            "trash:///..",
+            "trash:///.",
+            "trash:///%2E%2E/x",
+            "trash:///%2Fetc%2Fpasswd",
+            "trash:///a%2Fb",
+            "trash:///a/b%2F..%2F..%2Fc",
+            "trash:///a/.",
+            "trash:///a%00b",
+            "trash:///a/b%00",
+        ] {
+            assert_eq!(TrashUri::parse(uri), None, "{}", uri);
+        }
+        let uri = TrashUri::parse("trash:///a/b").unwrap();
+        assert_eq!(
+            uri.resolve(Path::new("/trash")),
+            Path::new("/trash/files/a/b")
+        );
+    }
+}